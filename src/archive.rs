@@ -0,0 +1,192 @@
+use std::{
+    fs::File,
+    io::{copy, Read},
+    path::{Path, PathBuf},
+};
+
+use flate2::read::MultiGzDecoder;
+use tempfile::TempDir;
+use zip::ZipArchive;
+
+use crate::error::Error;
+
+/// Signature at the start of a gzip file.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Signature at the start of a zip archive.
+const ZIP_MAGIC: [u8; 4] = *b"PK\x03\x04";
+
+/// Extensions of the files that go with a shapefile and are unpacked alongside it.
+const SHP_SIDECARS: [&str; 4] = ["shx", "dbf", "prj", "cpg"];
+
+/// How an input file was packed.
+pub enum Packing {
+    None,
+    Gzip,
+    /// A zip archive with all of its members and the member that was unpacked. KMZ archives are
+    /// read directly, so have no unpacked member.
+    Zip {
+        members: Vec<String>,
+        member: Option<String>,
+    },
+}
+
+/// An input file ready to be read, unpacked into a temporary directory if it was compressed or in
+/// an archive. The directory is removed when the input is dropped, so the input must outlive
+/// anything reading from its path.
+pub struct Input {
+    path: PathBuf,
+    packing: Packing,
+    _dir: Option<TempDir>,
+}
+
+impl Input {
+    /// Open the file at `path`, unpacking it if needed.
+    ///
+    /// Gzip files are decompressed to a file named without the `.gz` extension. Zip archives are
+    /// unpacked to the member named by `member`, matched on the full name or the file name, or
+    /// otherwise to the only shapefile or the only member. Shapefiles are unpacked with their
+    /// .shx, .dbf, .prj and .cpg files. Zip archives holding KML, or with a `.kmz` extension,
+    /// are left as they are when there is no `member`, as KMZ is read directly.
+    pub fn open(path: &PathBuf, member: Option<&str>) -> Result<Self, Error> {
+        let mut head = Vec::new();
+        File::open(path)
+            .and_then(|file| file.take(4).read_to_end(&mut head))
+            .map_err(|_| Error::CannotReadFile(path.clone()))?;
+
+        if head.starts_with(&GZIP_MAGIC) {
+            Self::gunzip(path)
+        } else if head.starts_with(&ZIP_MAGIC) {
+            Self::unzip(path, member)
+        } else {
+            Ok(Self::plain(path.clone(), Packing::None))
+        }
+    }
+
+    /// The path of the file to read.
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn packing(&self) -> &Packing {
+        &self.packing
+    }
+
+    fn plain(path: PathBuf, packing: Packing) -> Self {
+        Self {
+            path,
+            packing,
+            _dir: None,
+        }
+    }
+
+    fn gunzip(path: &PathBuf) -> Result<Self, Error> {
+        let name = path
+            .file_stem()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("data"));
+        let dir = tempfile::tempdir().map_err(Error::FileIOError)?;
+        let out_path = dir.path().join(name);
+
+        let mut decoder = File::open(path)
+            .map(MultiGzDecoder::new)
+            .map_err(|_| Error::CannotReadFile(path.clone()))?;
+        File::create(&out_path)
+            .and_then(|mut out| copy(&mut decoder, &mut out))
+            .map_err(|_| Error::CannotReadArchive(path.clone()))?;
+
+        Ok(Self {
+            path: out_path,
+            packing: Packing::Gzip,
+            _dir: Some(dir),
+        })
+    }
+
+    fn unzip(path: &PathBuf, member: Option<&str>) -> Result<Self, Error> {
+        let mut archive = File::open(path)
+            .map_err(|_| Error::CannotReadFile(path.clone()))
+            .and_then(|f| ZipArchive::new(f).map_err(|_| Error::CannotReadArchive(path.clone())))?;
+        let members: Vec<String> = archive
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(String::from)
+            .collect();
+
+        let chosen = match member {
+            Some(member) => members
+                .iter()
+                .find(|m| *m == member || file_name(m) == member)
+                .cloned()
+                .ok_or_else(|| Error::MissingArchiveMember(member.to_string()))?,
+            None => {
+                let is_kmz = has_extension(path, "kmz");
+                let shapefiles: Vec<String> = members
+                    .iter()
+                    .filter(|m| has_extension(m, "shp"))
+                    .cloned()
+                    .collect();
+                match shapefiles.as_slice() {
+                    [shp] if !is_kmz => shp.clone(),
+                    [] if is_kmz || members.iter().any(|m| has_extension(m, "kml")) => {
+                        return Ok(Self::plain(
+                            path.clone(),
+                            Packing::Zip {
+                                members,
+                                member: None,
+                            },
+                        ));
+                    }
+                    [] if members.len() == 1 => members[0].clone(),
+                    _ => return Err(Error::CannotChooseArchiveMember(path.clone())),
+                }
+            }
+        };
+
+        // Shapefiles need their sidecars, which share the name of the main file
+        let stem = chosen
+            .rsplit_once('.')
+            .map(|(stem, _)| stem)
+            .unwrap_or(&chosen);
+        let unpack: Vec<_> = members
+            .iter()
+            .filter(|m| {
+                *m == &chosen
+                    || (has_extension(&chosen, "shp")
+                        && m.rsplit_once('.').is_some_and(|(s, ext)| {
+                            s == stem && SHP_SIDECARS.contains(&ext.to_lowercase().as_str())
+                        }))
+            })
+            .collect();
+
+        let dir = tempfile::tempdir().map_err(Error::FileIOError)?;
+        for name in unpack {
+            let mut file = archive
+                .by_name(name)
+                .map_err(|_| Error::CannotReadArchive(path.clone()))?;
+            File::create(dir.path().join(file_name(name)))
+                .and_then(|mut out| copy(&mut file, &mut out))
+                .map_err(|_| Error::CannotReadArchive(path.clone()))?;
+        }
+
+        Ok(Self {
+            path: dir.path().join(file_name(&chosen)),
+            packing: Packing::Zip {
+                members,
+                member: Some(chosen),
+            },
+            _dir: Some(dir),
+        })
+    }
+}
+
+/// The last component of an archive member name.
+fn file_name(member: &str) -> &str {
+    member.rsplit('/').next().unwrap_or(member)
+}
+
+fn has_extension(path: impl AsRef<Path>, ext: &str) -> bool {
+    path.as_ref()
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+}
@@ -0,0 +1,68 @@
+use clap::Parser;
+
+/// We look in the current directory for a data.shp file by default
+const DEFAULT_PATH: &str = "./data.shp";
+
+/// Command line utility to cluster point datasets with DBSCAN. The points
+/// are loaded into a quadtree from an input file, which is used for the
+/// radius searches. Distances are measured using the Haversine formula.
+/// Outputs each feature's index, cluster, and whether it is a core, border,
+/// or noise point as csv on stdout.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The file to load the points from. If not provided will use the
+    /// {n}default at ./data.shp. Supports multiple geographic file types,
+    /// {n}but only point geometries are clustered.
+    #[arg(default_value = DEFAULT_PATH)]
+    pub path: std::path::PathBuf,
+
+    /// The maximum distance in meters between two points for one to be
+    /// {n}considered in the neighborhood of the other.
+    #[arg(short, long)]
+    pub eps: f64,
+
+    /// The minimum number of points, including the point itself, in a
+    /// {n}neighborhood for the point to be a core point.
+    #[arg(short, long = "min-points", default_value = "4")]
+    pub min_points: usize,
+
+    /// Print verbose logging to stderr.
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Pass this flag to generate a point quadtree, which is slightly more
+    /// {n}efficient for point data.
+    #[arg(short, long)]
+    pub point: bool,
+
+    /// Use a bounding box for the quadtree that is aligned with the complete
+    /// {n}boundaries of a sphere, with longitude split at the antimeridian.
+    /// {n}This option cannot be used with `-x` / `--bbox`.
+    #[arg(short, long, conflicts_with = "bbox")]
+    pub sphere: bool,
+
+    /// Use the provided bounding box. Bounding box should be provided in
+    /// {n}degrees as a list of comma separated values without spaces in the
+    /// {n}order lng_min, lat_min, lng_max, lat_max. This option cannot be used
+    /// {n}with --sphere.
+    #[arg(short = 'x', long, conflicts_with = "sphere")]
+    pub bbox: Option<String>,
+
+    /// Provide a customized maximum depth for the quadtree. Defaults to 10.
+    #[arg(short, long)]
+    pub depth: Option<u8>,
+
+    /// Provide a customized value for the maximum number of child entries
+    /// {n}before the quadtree splits. Defaults to 10.
+    #[arg(short, long)]
+    pub children: Option<usize>,
+
+    /// Write a per-cluster summary with the member count and centroid to
+    /// {n}a csv file at this path.
+    #[arg(long)]
+    pub summary: Option<std::path::PathBuf>,
+
+    /// Set the delimiter for the output. Defaults to a comma.
+    #[arg(long, short = 'l', default_value = ",")]
+    pub delimiter: String,
+}
@@ -0,0 +1,110 @@
+use std::collections::{BTreeMap, HashSet};
+
+use geo::Point;
+
+use geo_munge::qt::Quadtree;
+
+/// Classification of a feature after clustering, with the cluster id for non-noise features.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Label {
+    Core(usize),
+    Border(usize),
+    Noise,
+}
+
+/// Run DBSCAN over the features, with `eps` in radians.
+///
+/// Features are keyed by their index in the source file with all their point parts, so a
+/// multi-point feature is clustered as a single member whose neighborhood is the union of the
+/// neighborhoods of its parts. The neighborhood includes the feature itself.
+pub fn dbscan(
+    qt: &Quadtree,
+    features: &BTreeMap<usize, Vec<Point>>,
+    eps: f64,
+    min_points: usize,
+) -> BTreeMap<usize, Label> {
+    let mut labels = BTreeMap::new();
+    let mut cluster = 0;
+
+    for &index in features.keys() {
+        if labels.contains_key(&index) {
+            continue;
+        }
+
+        let neighbours = region(qt, features, index, eps);
+        if neighbours.len() < min_points {
+            labels.insert(index, Label::Noise);
+            continue;
+        }
+
+        // Expand the new cluster from the core point, noise reached from a core point becomes a
+        // border point, but is not expanded further
+        labels.insert(index, Label::Core(cluster));
+        let mut queue: Vec<usize> = neighbours.into_iter().collect();
+
+        while let Some(next) = queue.pop() {
+            match labels.get(&next) {
+                Some(Label::Noise) => {
+                    labels.insert(next, Label::Border(cluster));
+                }
+                Some(_) => {}
+                None => {
+                    let neighbours = region(qt, features, next, eps);
+                    if neighbours.len() >= min_points {
+                        labels.insert(next, Label::Core(cluster));
+                        queue.extend(neighbours);
+                    } else {
+                        labels.insert(next, Label::Border(cluster));
+                    }
+                }
+            }
+        }
+
+        cluster += 1;
+    }
+
+    labels
+}
+
+/// The set of feature indices within `eps` of any part of the feature at `index`. Failed
+/// searches are output to stderr and contribute nothing to the neighborhood.
+fn region(
+    qt: &Quadtree,
+    features: &BTreeMap<usize, Vec<Point>>,
+    index: usize,
+    eps: f64,
+) -> HashSet<usize> {
+    let mut neighbours = HashSet::new();
+
+    for point in features.get(&index).into_iter().flatten() {
+        match qt.within(point, index, eps) {
+            Ok(results) => neighbours.extend(results.into_iter().map(|(d, _)| d.index())),
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+
+    neighbours
+}
+
+/// Calculate the centroid of a set of points in radians as the normalized mean of their
+/// positions on the unit sphere, returning the result in degrees.
+pub fn centroid<'a>(points: impl Iterator<Item = &'a Point>) -> Option<Point> {
+    let (mut x, mut y, mut z, mut n) = (0.0, 0.0, 0.0, 0);
+
+    for p in points {
+        let (lng, lat) = (p.x(), p.y());
+        x += lat.cos() * lng.cos();
+        y += lat.cos() * lng.sin();
+        z += lat.sin();
+        n += 1;
+    }
+
+    if n == 0 {
+        return None;
+    }
+
+    let lng = y.atan2(x);
+    let lat = z.atan2((x * x + y * y).sqrt());
+
+    Some(Point::new(lng.to_degrees(), lat.to_degrees()))
+}
@@ -0,0 +1,142 @@
+mod args;
+mod dbscan;
+
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use clap::Parser;
+use csv::WriterBuilder;
+use geo::Point;
+use quadtree::{AsGeom, AsPoint, GeometryRef, MEAN_EARTH_RADIUS};
+
+use geo_munge::archive::Input;
+use geo_munge::error::Error;
+use geo_munge::qt::{make_bbox, open_source, QtData, Quadtree, SourceOptions};
+
+use crate::args::Args;
+use crate::dbscan::{centroid, dbscan, Label};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let verbose = args.verbose;
+    let eps = args.eps / MEAN_EARTH_RADIUS;
+
+    let delimiter = args.delimiter.as_bytes();
+    if delimiter.len() != 1 {
+        return Err(Box::new(Error::InvalidDelimiter));
+    }
+    let delimiter = delimiter[0];
+
+    // Unpack compressed files and archives once, as the file is read for the bounding box and the
+    // features, and keep the input until the end to keep the files
+    let input = Input::open(&args.path, None)?;
+    let path = input.path();
+
+    let source = SourceOptions::default();
+    let opts = QtData::new(
+        args.point,
+        make_bbox(path, &source, args.sphere, &args.bbox)?,
+        args.depth,
+        args.children,
+        source.clone(),
+    );
+
+    // Build the quadtree, keeping the points from every successful insert so each feature can
+    // be used as a query
+    let start = Instant::now();
+    let mut qt = Quadtree::new(opts);
+    let mut features: BTreeMap<usize, Vec<Point>> = BTreeMap::new();
+    for datum in open_source(path, &source)?.datums()? {
+        let res = datum.and_then(|d| {
+            if !matches!(d.as_geom(), GeometryRef::Point::<f64>(_)) {
+                return Err(Error::QueryRequiresPoint(d.index()));
+            }
+            let (index, point) = (d.index(), d.as_point());
+            qt.insert(d)
+                .map(|_| features.entry(index).or_default().push(point))
+        });
+
+        if let Err(err) = res {
+            eprintln!("{err}");
+        }
+    }
+    if verbose {
+        eprintln!(
+            "Quadtree with {} children built in {} ms",
+            qt.size(),
+            start.elapsed().as_millis()
+        );
+    }
+
+    let start = Instant::now();
+    let labels = dbscan(&qt, &features, eps, args.min_points);
+    if verbose {
+        eprintln!("Clustered in {} ms", start.elapsed().as_millis());
+    }
+
+    // Write out the classification of every feature
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(std::io::stdout());
+    writer.write_record(["index", "cluster", "type"])?;
+
+    for (index, label) in &labels {
+        let (cluster, label_type) = match label {
+            Label::Core(c) => (c.to_string(), "core"),
+            Label::Border(c) => (c.to_string(), "border"),
+            Label::Noise => (String::default(), "noise"),
+        };
+
+        if writer
+            .write_record([index.to_string(), cluster, label_type.to_string()])
+            .is_err()
+        {
+            eprintln!("Failed to write output line for feature at index {index}.");
+        }
+    }
+    writer.flush()?;
+
+    if let Some(path) = args.summary {
+        write_summary(&path, delimiter, &labels, &features)?;
+    }
+
+    Ok(())
+}
+
+/// Write the member count and centroid for each cluster to a csv file at `path`.
+fn write_summary(
+    path: &std::path::PathBuf,
+    delimiter: u8,
+    labels: &BTreeMap<usize, Label>,
+    features: &BTreeMap<usize, Vec<Point>>,
+) -> Result<(), Error> {
+    // Group the members of each cluster, noise is not summarized
+    let mut clusters: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (index, label) in labels {
+        match label {
+            Label::Core(c) | Label::Border(c) => clusters.entry(*c).or_default().push(*index),
+            Label::Noise => {}
+        }
+    }
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_path(path)
+        .map_err(|err| Error::CsvWriteError(err))?;
+    writer
+        .write_record(["cluster", "count", "lng", "lat"])
+        .map_err(|err| Error::CsvWriteError(err))?;
+
+    for (cluster, members) in clusters {
+        let points = members.iter().filter_map(|i| features.get(i)).flatten();
+        let (lng, lat) = centroid(points)
+            .map(|p| (p.x().to_string(), p.y().to_string()))
+            .unwrap_or_default();
+
+        writer
+            .write_record([cluster.to_string(), members.len().to_string(), lng, lat])
+            .map_err(|err| Error::CsvWriteError(err))?;
+    }
+
+    writer.flush().map_err(|err| Error::FileIOError(err))
+}
@@ -1,4 +1,8 @@
 use clap::{Parser, Subcommand};
+use geo_munge::encoding::TextEncoding;
+use geo_munge::error::parse_arg;
+use geo_munge::osm::TagFilter;
+use geo_munge::qt::Format;
 
 /// We look in the current directory for a data.shp file by default
 const DEFAULT_SHP_PATH: &str = "./data.shp";
@@ -15,11 +19,36 @@ pub struct Cli {
     /// {n}the default at ./data.shp.
     #[arg(global = true, default_value = DEFAULT_SHP_PATH)]
     pub path: std::path::PathBuf,
+
+    /// Override the format of the file, one of shp, geojson, topojson,
+    /// {n}kml, gpx, osm, wkt or csv. By default the format is detected
+    /// {n}from the file content, falling back to the extension.
+    #[arg(global = true, long, value_parser = parse_arg::<Format>)]
+    pub format: Option<Format>,
+
+    /// Override the text encoding of shapefile attributes or a csv, for
+    /// {n}example utf8, latin1, iso-8859-2, windows-1252 or cp850. By
+    /// {n}default shapefiles use the .cpg file or the dbf header.
+    #[arg(global = true, long, value_parser = parse_arg::<TextEncoding>)]
+    pub encoding: Option<TextEncoding>,
+
+    /// Only read features of an OSM file with this tag, passed as `key`
+    /// {n}for any value or `key=value`. Repeat to read features matching
+    /// {n}any of the tags.
+    #[arg(global = true, long, value_parser = parse_arg::<TagFilter>)]
+    pub tag: Vec<TagFilter>,
+
+    /// Member of a zip archive to read, by its name or file name. By
+    /// {n}default the only shapefile, or the only member, is read. Gzip
+    /// {n}files are always decompressed.
+    #[arg(global = true, long)]
+    pub member: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Command {
-    /// Print any file header contents to stdout.
+    /// Print any file header contents to stdout. Zip archives also list
+    /// {n}their members.
     Header,
 
     /// Print the number of shapes to stdout.
@@ -34,9 +63,17 @@ pub enum Command {
         /// Only works when --fields is also passed.
         #[arg(long)]
         types: bool,
+
+        /// List the path to every leaf value of nested fields, such as
+        /// {n}address.city or tags[0], rather than only the first level.
+        /// {n}Applies to GeoJSON and TopoJSON properties. Csv columns holding
+        /// {n}JSON are listed by column name only.
+        #[arg(long)]
+        deep: bool,
     },
 
-    /// Print the first level of any metadata to stdout in csv format.
+    /// Print the first level of any metadata to stdout in csv format,
+    /// {n}optionally followed by columns derived from each geometry.
     Data {
         /// Add a header row to the output data
         #[arg(long, short = 'r')]
@@ -57,5 +94,27 @@ pub enum Command {
         /// Add a sequential index field to each record
         #[arg(short, long)]
         index: bool,
+
+        /// Append the geometry of each record as a WKT column
+        #[arg(long)]
+        wkt: bool,
+
+        /// Append the geometry of each record as a GeoJSON geometry column
+        #[arg(long)]
+        geojson: bool,
+
+        /// Append the lng and lat of the centroid of each record's geometry
+        #[arg(long)]
+        centroid: bool,
+
+        /// Append the lng and lat of a point inside each record's geometry,
+        /// {n}which unlike the centroid is always on the geometry itself
+        #[arg(long)]
+        interior_point: bool,
+
+        /// Append the min lng, min lat, max lng and max lat of each
+        /// {n}record's geometry
+        #[arg(long)]
+        bbox: bool,
     },
 }
@@ -0,0 +1,272 @@
+use std::{iter::once, path::PathBuf};
+
+use csv::{StringRecord, WriterBuilder};
+use geo::{BoundingRect, Geometry, Point, Rect};
+use geo_munge::{
+    encoding::TextEncoding,
+    error::Error,
+    qt::{CsvLocation, CsvOptions, CsvSource, Source},
+    wkt::{parse_geometry_text, split_geometry},
+};
+
+use crate::geometry::combine_parts;
+use crate::{DataOpts, Meta, MetaResult};
+
+/// Number of rows sampled to infer the type of each column.
+const TYPE_SAMPLE_ROWS: usize = 1000;
+
+pub struct CsvMeta {
+    delimiter: u8,
+    source: CsvSource,
+}
+
+impl CsvMeta {
+    /// Build the meta for the csv at `path`, detecting the delimiter from the header row.
+    pub fn new(path: PathBuf, encoding: Option<TextEncoding>) -> Result<Self, Error> {
+        let source = CsvSource::new(path, CsvOptions::default(), encoding.unwrap_or_default());
+        let delimiter = source.delimiter()?;
+
+        Ok(Self { delimiter, source })
+    }
+
+    /// The lower-cased column headers, as used for `--fields`.
+    fn columns(&self) -> Result<Vec<String>, Error> {
+        Ok(self.source.fields()?.unwrap_or_default())
+    }
+
+    /// Scan every row for the bounds of the points or geometries, skipping rows that do not
+    /// parse.
+    fn scan_bbox(&self, location: CsvLocation) -> Result<Option<Rect>, Error> {
+        let mut bbox: Option<Rect> = None;
+
+        for record in self.source.reader()?.into_records().flatten() {
+            let rect = parse_location(&record, location).and_then(|g| g.bounding_rect());
+            if let Some(rect) = rect {
+                bbox = Some(match bbox {
+                    Some(b) => Rect::new(
+                        (b.min().x.min(rect.min().x), b.min().y.min(rect.min().y)),
+                        (b.max().x.max(rect.max().x), b.max().y.max(rect.max().y)),
+                    ),
+                    None => rect,
+                });
+            }
+        }
+
+        Ok(bbox)
+    }
+}
+
+impl Meta for CsvMeta {
+    fn headers(&self) -> MetaResult {
+        let fields = self.columns()?;
+        let name = |i: usize| fields.get(i).map(String::as_str).unwrap_or_default();
+
+        println!("Delimiter: {:?}", self.delimiter as char);
+        println!("Columns: {}", fields.len());
+
+        match self.source.location() {
+            Ok(location) => {
+                match location {
+                    CsvLocation::LngLat((lng, lat)) => {
+                        println!("Lng column: {} ({lng})", name(lng));
+                        println!("Lat column: {} ({lat})", name(lat));
+                    }
+                    CsvLocation::Geometry(i) => println!("Geometry column: {} ({i})", name(i)),
+                }
+                if let Some(bbox) = self.scan_bbox(location)? {
+                    println!(
+                        "Bounding box: [{}, {}, {}, {}]",
+                        bbox.min().x,
+                        bbox.min().y,
+                        bbox.max().x,
+                        bbox.max().y
+                    );
+                }
+            }
+            Err(_) => println!("Location columns: None"),
+        }
+
+        Ok(())
+    }
+
+    /// Types are inferred from the first rows of the file. Empty values are ignored, and columns
+    /// with values of more than one type are String.
+    fn fields(&self, show_types: bool, _: bool) -> MetaResult {
+        let fields = self.columns()?;
+
+        if !show_types {
+            for field in fields {
+                println!("{field}");
+            }
+            return Ok(());
+        }
+
+        let mut types: Vec<Option<ColumnType>> = vec![None; fields.len()];
+        for record in self
+            .source
+            .reader()?
+            .into_records()
+            .flatten()
+            .take(TYPE_SAMPLE_ROWS)
+        {
+            for (t, value) in types.iter_mut().zip(&record) {
+                let value = value.trim();
+                if !value.is_empty() {
+                    let value_type = ColumnType::infer(value);
+                    *t = Some(t.map_or(value_type, |t| t.merge(value_type)));
+                }
+            }
+        }
+
+        for (field, t) in fields.iter().zip(types) {
+            println!("{field} [{}]", t.unwrap_or(ColumnType::String).name());
+        }
+
+        Ok(())
+    }
+
+    fn count(&self) -> MetaResult {
+        println!("{}", self.source.count()?.unwrap_or_default());
+
+        Ok(())
+    }
+
+    /// Rows are written as they are, whether or not they have a valid location.
+    fn data(&self, opts: DataOpts) -> MetaResult {
+        let delimiter = opts.delimiter.as_bytes();
+        if delimiter.len() != 1 {
+            return Err(Box::new(Error::InvalidDelimiter));
+        }
+        let delimiter = delimiter[0];
+
+        let mut writer = WriterBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_writer(std::io::stdout());
+
+        // The header row is written as it is in the file, unlike the lower-cased field names
+        let mut reader = self.source.reader()?;
+        if opts.headers {
+            let fields = reader.headers().map_err(|err| Error::CsvParseError(err))?;
+            let header_iter = fields.iter().chain(opts.geometry.headers());
+            if opts.index {
+                writer.write_record(once("index").chain(header_iter))?;
+            } else {
+                writer.write_record(header_iter)?;
+            }
+        }
+
+        // Files without location columns have empty geometry values
+        let location = self.source.location().ok();
+
+        let n = opts.length.unwrap_or(usize::MAX);
+        for (i, record) in reader.into_records().enumerate().skip(opts.start).take(n) {
+            match record {
+                Ok(record) => {
+                    let index = i.to_string();
+                    // Split and combined like the other sources, so empty parts are dropped, but
+                    // kept in degrees
+                    let geometry = location
+                        .and_then(|l| parse_location(&record, l))
+                        .and_then(|g| combine_parts(split_geometry(g)));
+                    let geometry_values = opts.geometry.values(geometry.as_ref());
+                    let record_iter = record
+                        .iter()
+                        .chain(geometry_values.iter().map(String::as_str));
+                    let record_iter: Box<dyn Iterator<Item = &str>> = if opts.index {
+                        Box::new(once(index.as_str()).chain(record_iter))
+                    } else {
+                        Box::new(record_iter)
+                    };
+
+                    if writer.write_record(record_iter).is_err() {
+                        eprintln!("failed to write output for record at index {i}");
+                    }
+                }
+                Err(_) => eprintln!("cannot read record at index {i}"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The type of a csv column, from the most to the least specific.
+#[derive(Clone, Copy, PartialEq)]
+enum ColumnType {
+    Integer,
+    Number,
+    Bool,
+    Date,
+    String,
+}
+
+impl ColumnType {
+    fn infer(value: &str) -> Self {
+        if value.parse::<i64>().is_ok() {
+            Self::Integer
+        } else if value.parse::<f64>().is_ok() {
+            Self::Number
+        } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+            Self::Bool
+        } else if is_date(value) {
+            Self::Date
+        } else {
+            Self::String
+        }
+    }
+
+    /// The type of a column with values of both types. Integers widen to Numbers, any other mix
+    /// is a String.
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Self::Integer, Self::Number) | (Self::Number, Self::Integer) => Self::Number,
+            _ => Self::String,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Integer => "Integer",
+            Self::Number => "Number",
+            Self::Bool => "Bool",
+            Self::Date => "Date",
+            Self::String => "String",
+        }
+    }
+}
+
+/// Check for an ISO 8601 date, `YYYY-MM-DD`, optionally followed by a time after a `T` or a space.
+fn is_date(value: &str) -> bool {
+    let (date, time) = match value.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+    let parts: Vec<_> = date.split('-').collect();
+
+    let valid_date = match parts.as_slice() {
+        [y, m, d] if y.len() == 4 && m.len() == 2 && d.len() == 2 => {
+            y.parse::<u16>().is_ok()
+                && m.parse::<u8>().is_ok_and(|m| (1..=12).contains(&m))
+                && d.parse::<u8>().is_ok_and(|d| (1..=31).contains(&d))
+        }
+        _ => false,
+    };
+
+    valid_date && time.is_none_or(|t| t.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Parse the point or geometry of a row, in degrees, or None if it does not parse.
+fn parse_location(record: &StringRecord, location: CsvLocation) -> Option<Geometry> {
+    let value = |i: usize| record.get(i).unwrap_or_default().trim();
+    match location {
+        CsvLocation::LngLat((lng, lat)) => {
+            match (value(lng).parse::<f64>(), value(lat).parse::<f64>()) {
+                (Ok(lng), Ok(lat)) => Some(Geometry::Point(Point::new(lng, lat))),
+                _ => None,
+            }
+        }
+        CsvLocation::Geometry(i) => parse_geometry_text(value(i)).ok(),
+    }
+}
@@ -1,31 +1,46 @@
-use std::{collections::HashMap, fmt::Display, fs::read_to_string, iter::empty, path::PathBuf};
+use std::{collections::HashMap, fmt::Display, path::PathBuf};
 
-use csv::WriterBuilder;
-use geo_munge::error::Error;
-use geojson::{feature::Id, FeatureCollection, GeoJson, JsonValue};
+use geo_munge::{
+    error::Error,
+    geojson::stream_geojson,
+    json::leaf_paths,
+    qt::{Format, SourceOptions},
+};
+use geojson::{feature::Id, Feature, GeoJson, JsonValue};
 
+use crate::source::SourceMeta;
 use crate::{DataOpts, Meta, MetaResult};
 
 pub struct GeoJsonMeta {
     path: PathBuf,
+    // Count and data are the same as for the generic source
+    source: SourceMeta,
 }
 
 impl GeoJsonMeta {
     pub fn new(path: PathBuf) -> Self {
-        Self { path }
-    }
+        let source = SourceMeta::new(
+            path.clone(),
+            SourceOptions {
+                format: Some(Format::GeoJson),
+                ..Default::default()
+            },
+        );
 
-    fn geojson(&self) -> Result<GeoJson, Error> {
-        let geojson = read_to_string(&self.path)
-            .map_err(|_| Error::CannotReadFile(self.path.clone()))?
-            .parse::<GeoJson>()
-            .map_err(|_| Error::CannotParseFile(self.path.clone()))?;
+        Self { path, source }
+    }
 
-        Ok(geojson)
+    /// Stream the Features in the file one at a time. Geometries do not contain metadata, so are
+    /// an error.
+    fn features(&self) -> Result<impl Iterator<Item = Result<Feature, Error>>, Error> {
+        Ok(stream_geojson(&self.path)?.map(|record| match record? {
+            GeoJson::Feature(f) => Ok(f),
+            _ => Err(Error::TypeDoesNotContainMetadata),
+        }))
     }
 }
 
-fn print_bbox(bbox: &Option<Vec<f64>>) -> MetaResult {
+pub fn print_bbox(bbox: &Option<Vec<f64>>) -> MetaResult {
     if let Some(bbox) = bbox {
         println!(
             "Bounding box: [{}, {}, {}, {}]",
@@ -41,14 +56,18 @@ fn print_bbox(bbox: &Option<Vec<f64>>) -> MetaResult {
 
 impl Meta for GeoJsonMeta {
     fn headers(&self) -> MetaResult {
-        let geojson = self.geojson()?;
+        let mut stream = stream_geojson(&self.path)?;
+        if stream.collection().is_some_and(|fc| fc.bbox.is_none()) {
+            stream.skip_features()?;
+        }
 
-        match geojson {
-            GeoJson::FeatureCollection(fc) => {
-                println!("Top-level type: FeatureCollection");
-                print_bbox(&fc.bbox)?;
-            }
-            GeoJson::Feature(f) => {
+        if let Some(fc) = stream.collection() {
+            println!("Top-level type: FeatureCollection");
+            return print_bbox(&fc.bbox);
+        }
+
+        match stream.single()? {
+            Some(GeoJson::Feature(f)) => {
                 println!("Top-level type: Feature");
                 match f.geometry {
                     Some(g) => println!("Contained geometry: {}", g.value.type_name()),
@@ -56,11 +75,12 @@ impl Meta for GeoJsonMeta {
                 };
                 print_bbox(&f.bbox)?;
             }
-            GeoJson::Geometry(g) => {
+            Some(GeoJson::Geometry(g)) => {
                 println!("Top-level type: Geometry");
                 println!("Contained geometry: {}", g.value.type_name());
                 print_bbox(&g.bbox)?;
             }
+            _ => println!("Top-level type: Sequence"),
         };
 
         Ok(())
@@ -68,98 +88,24 @@ impl Meta for GeoJsonMeta {
 
     /// Print a list of metadata fields to stdout.
     ///
-    /// For GeoJson FeatureCollections, will print id (as its always possible),
-    /// then iterate through all the Features, capturing the first level of the
-    /// properties key. Feature will just return the top-level properties of the
-    /// Feature, and Geometry will error.
-    fn fields(&self, show_types: bool) -> MetaResult {
-        match self.geojson()? {
-            GeoJson::Geometry(_) => {
-                Err(Error::TypeDoesNotContainMetadata)?;
-            }
-            GeoJson::Feature(f) => {
-                // The id, if it exists, can be a string or a number
-                if show_types {
-                    match f.id {
-                        Some(Id::String(_)) => println!("id [String]"),
-                        Some(Id::Number(_)) => println!("id [Number]"),
-                        None => {}
-                    }
-                } else if f.id.is_some() {
-                    println!("id");
-                }
-
-                // If the properties key exists, loop through and print
-                if let Some(props) = f.properties {
-                    for (k, v) in props {
-                        if show_types {
-                            println!("{k} [{}]", json_type(&v));
-                        } else {
-                            println!("{k}");
-                        }
-                    }
-                }
-            }
-            GeoJson::FeatureCollection(fc) => {
-                // Eagerly loop through the collection to determine all metadata
-                // keys - this can be time consuming.
-                let (id_type, keys) = make_fields(&fc, None, None);
-
-                if id_type != IdType::None {
-                    if show_types {
-                        println!("id [{id_type}]");
-                    } else {
-                        println!("id");
-                    }
-                }
-
-                for (k, t) in keys {
-                    if show_types {
-                        println!("{k} [{t}]");
-                    } else {
-                        println!("{k}");
-                    }
-                }
-            }
-        };
-
-        Ok(())
+    /// Will print id if any Feature has one, then stream through all the
+    /// Features, capturing the first level of the properties key, or the path
+    /// to every leaf value when `deep` is set. Geometries will error.
+    fn fields(&self, show_types: bool, deep: bool) -> MetaResult {
+        print_fields(self.features()?, show_types, deep)
     }
 
     /// Print to number of top-level records.
     ///
-    /// For GeoJson this will be 1 for Feature or Geometry types, or the length
-    /// of the Features vector for FeatureCollections.
+    /// For GeoJson this will be 1 for Feature or Geometry types, the length
+    /// of the Features vector for FeatureCollections, or the number of records
+    /// in a sequence.
     fn count(&self) -> MetaResult {
-        let geojson = self.geojson()?;
-
-        match geojson {
-            GeoJson::Feature(_) | GeoJson::Geometry(_) => println!("{}", 1),
-            GeoJson::FeatureCollection(fc) => println!("{}", fc.features.len()),
-        }
-
-        Ok(())
+        self.source.count()
     }
 
     fn data(&self, opts: DataOpts) -> MetaResult {
-        match self.geojson()? {
-            GeoJson::Geometry(_) => {
-                Err(Error::TypeDoesNotContainMetadata)?;
-            }
-            GeoJson::Feature(f) => {
-                let fc = FeatureCollection {
-                    bbox: None,
-                    foreign_members: None,
-                    features: vec![f],
-                };
-                print_fc_meta(fc, opts)?;
-            }
-            GeoJson::FeatureCollection(fc) => {
-                print_fc_meta(fc, opts)?;
-            }
-        }
-
-        Ok(())
+        self.source.data(opts)
     }
 }
 
@@ -184,19 +130,15 @@ impl Display for IdType {
     }
 }
 
-fn make_fields<'a>(
-    fc: &'a FeatureCollection,
-    skip: Option<usize>,
-    n: Option<usize>,
-) -> (IdType, HashMap<String, &'static str>) {
+fn make_fields(
+    features: impl Iterator<Item = Result<Feature, Error>>,
+    deep: bool,
+) -> Result<(IdType, HashMap<String, &'static str>), Error> {
     let mut id_type = IdType::None;
     let mut keys = HashMap::new();
 
-    for f in fc
-        .into_iter()
-        .skip(skip.unwrap_or(0))
-        .take(n.unwrap_or(usize::MAX))
-    {
+    for f in features {
+        let f = f?;
         let t = match f.id {
             Some(Id::String(_)) => IdType::String,
             Some(Id::Number(_)) => IdType::Number,
@@ -209,21 +151,29 @@ fn make_fields<'a>(
             id_type = IdType::Mixed;
         }
 
-        if let Some(ref props) = f.properties {
-            for (k, v) in props {
+        if let Some(props) = f.properties {
+            let mut add_key = |k: String, v: &JsonValue| {
                 let t = json_type(v);
-                keys.entry(k.to_owned())
+                keys.entry(k)
                     .and_modify(|e| {
                         if *e != t {
                             *e = "Mixed"
                         }
                     })
                     .or_insert(t);
+            };
+
+            for (k, v) in props {
+                if deep {
+                    leaf_paths(&v, k, &mut add_key);
+                } else {
+                    add_key(k, &v);
+                }
             }
         }
     }
 
-    (id_type, keys)
+    Ok((id_type, keys))
 }
 
 /// Return a string representation of a Json type.
@@ -238,64 +188,31 @@ fn json_type(v: &JsonValue) -> &'static str {
     }
 }
 
-/// Convert a JsonValue to a string representation.
-/// Required in this application to avoid double escaping strings when
-/// converting for csv.
-fn json_value_to_string(v: Option<&JsonValue>) -> String {
-    match v {
-        Some(JsonValue::String(s)) => s.to_owned(),
-        Some(x) => x.to_string(),
-        None => String::default(),
-    }
-}
-
-fn print_fc_meta(fc: FeatureCollection, opts: DataOpts) -> MetaResult {
-    let delimiter = opts.delimiter.as_bytes();
-    if delimiter.len() != 1 {
-        return Err(Box::new(Error::InvalidDelimiter));
-    }
-    let delimiter = delimiter[0];
-
-    let mut writer = WriterBuilder::new()
-        .delimiter(delimiter)
-        .from_writer(std::io::stdout());
-
-    // TODO: Different size parameter for the length to scan for headers?
-    let (has_id, keys) = make_fields(&fc, Some(opts.start), opts.length);
-    let keys: Vec<_> = keys.keys().collect();
-
-    // Write out the header
-    if opts.headers {
-        if opts.index {
-            writer.write_field("index")?;
-        }
-        if has_id != IdType::None {
-            writer.write_field("id")?;
+/// Print the id and the property fields found in the Features, with their types if `show_types` is
+/// set.
+pub fn print_fields(
+    features: impl Iterator<Item = Result<Feature, Error>>,
+    show_types: bool,
+    deep: bool,
+) -> MetaResult {
+    // Eagerly loop through the features to determine all metadata keys -
+    // this can be time consuming.
+    let (id_type, keys) = make_fields(features, deep)?;
+
+    if id_type != IdType::None {
+        if show_types {
+            println!("id [{id_type}]");
+        } else {
+            println!("id");
         }
-        for k in &keys {
-            writer.write_field(k)?;
-        }
-        writer.write_record(empty::<&str>())?;
     }
 
-    for (i, f) in fc
-        .into_iter()
-        .skip(opts.start)
-        .take(opts.length.unwrap_or(usize::MAX))
-        .enumerate()
-    {
-        if opts.index {
-            writer.write_field(i.to_string())?;
-        }
-        if has_id != IdType::None {
-            match f.id {
-                Some(Id::String(s)) => writer.write_field(s)?,
-                Some(Id::Number(n)) => writer.write_field(n.to_string())?,
-                _ => {}
-            }
+    for (k, t) in keys {
+        if show_types {
+            println!("{k} [{t}]");
+        } else {
+            println!("{k}");
         }
-        let props = f.properties.unwrap_or_default();
-        writer.write_record(keys.iter().map(|k| json_value_to_string(props.get(*k))))?;
     }
 
     Ok(())
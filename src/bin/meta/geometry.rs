@@ -0,0 +1,136 @@
+use geo::{BoundingRect, Centroid, InteriorPoint, MapCoordsInPlace, Point};
+use geo_munge::wkt::write_wkt;
+use quadtree::Geometry;
+
+/// Geometry columns appended to each record written by `meta data`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GeometryColumns {
+    pub wkt: bool,
+    pub geojson: bool,
+    pub centroid: bool,
+    pub interior_point: bool,
+    pub bbox: bool,
+}
+
+impl GeometryColumns {
+    /// The header of each geometry column, in the order they are written.
+    pub fn headers(&self) -> Vec<&'static str> {
+        let mut headers = Vec::new();
+        if self.wkt {
+            headers.push("wkt");
+        }
+        if self.geojson {
+            headers.push("geojson");
+        }
+        if self.centroid {
+            headers.extend(["centroid_lng", "centroid_lat"]);
+        }
+        if self.interior_point {
+            headers.extend(["point_lng", "point_lat"]);
+        }
+        if self.bbox {
+            headers.extend(["min_lng", "min_lat", "max_lng", "max_lat"]);
+        }
+
+        headers
+    }
+
+    /// The value of each geometry column for a geometry in degrees. Records without a geometry have
+    /// empty values.
+    pub fn values(&self, geometry: Option<&geo::Geometry>) -> Vec<String> {
+        let point = |p: Option<Point>| match p {
+            Some(p) => vec![p.x().to_string(), p.y().to_string()],
+            None => vec![String::default(); 2],
+        };
+
+        let mut values = Vec::new();
+        if self.wkt {
+            values.push(geometry.map(write_wkt).unwrap_or_default());
+        }
+        if self.geojson {
+            values.push(
+                geometry
+                    .and_then(|g| serde_json::to_string(&geojson::Geometry::new(g.into())).ok())
+                    .unwrap_or_default(),
+            );
+        }
+        if self.centroid {
+            values.extend(point(geometry.and_then(|g| g.centroid())));
+        }
+        if self.interior_point {
+            values.extend(point(geometry.and_then(|g| g.interior_point())));
+        }
+        if self.bbox {
+            match geometry.and_then(|g| g.bounding_rect()) {
+                Some(rect) => values.extend(
+                    [rect.min().x, rect.min().y, rect.max().x, rect.max().y].map(|v| v.to_string()),
+                ),
+                None => values.extend(vec![String::default(); 4]),
+            }
+        }
+
+        values
+    }
+}
+
+/// Coordinates converted back to degrees are rounded to this many decimal places, about 0.1 mm,
+/// so that the round trip through radians doesn't add noise such as `-178.70000000000002`.
+const DEGREE_DECIMALS: i32 = 9;
+
+/// Combine the parts of a feature, as converted for the quadtree in radians, back into a single
+/// geometry in degrees.
+pub fn from_parts(parts: impl IntoIterator<Item = Geometry<f64>>) -> Option<geo::Geometry> {
+    let scale = 10f64.powi(DEGREE_DECIMALS);
+    let to_degrees = |v: f64| (v.to_degrees() * scale).round() / scale;
+
+    let mut geometry = combine_parts(parts)?;
+    geometry.map_coords_in_place(|c| geo::coord! { x: to_degrees(c.x), y: to_degrees(c.y) });
+
+    Some(geometry)
+}
+
+/// Combine the parts of a feature into a single geometry, keeping their coordinates. Parts of one
+/// type become the matching multi geometry, mixed parts a GeometryCollection.
+pub fn combine_parts(parts: impl IntoIterator<Item = Geometry<f64>>) -> Option<geo::Geometry> {
+    let mut parts: Vec<geo::Geometry> = parts
+        .into_iter()
+        .map(|part| match part {
+            Geometry::Point(p) => geo::Geometry::Point(p),
+            Geometry::LineString(l) => geo::Geometry::LineString(l),
+            Geometry::Polygon(p) => geo::Geometry::Polygon(p),
+        })
+        .collect();
+
+    if parts.len() <= 1 {
+        return parts.pop();
+    }
+    let geometry = if parts.iter().all(|p| matches!(p, geo::Geometry::Point(_))) {
+        geo::Geometry::MultiPoint(
+            parts
+                .into_iter()
+                .filter_map(|p| geo::Point::try_from(p).ok())
+                .collect(),
+        )
+    } else if parts
+        .iter()
+        .all(|p| matches!(p, geo::Geometry::LineString(_)))
+    {
+        geo::Geometry::MultiLineString(
+            parts
+                .into_iter()
+                .filter_map(|p| geo::LineString::try_from(p).ok())
+                .collect(),
+        )
+    } else if parts.iter().all(|p| matches!(p, geo::Geometry::Polygon(_))) {
+        geo::Geometry::MultiPolygon(
+            parts
+                .into_iter()
+                .filter_map(|p| geo::Polygon::try_from(p).ok())
+                .collect(),
+        )
+    } else {
+        geo::Geometry::GeometryCollection(geo::GeometryCollection(parts))
+    };
+
+    Some(geometry)
+}
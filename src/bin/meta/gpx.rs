@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+
+use geo_munge::{
+    gpx::{read_gpx, GPX_FIELDS},
+    qt::{Format, SourceOptions},
+};
+
+use crate::source::SourceMeta;
+use crate::{DataOpts, Meta, MetaResult};
+
+pub struct GpxMeta {
+    path: PathBuf,
+    // Count and data are the same as for the generic source
+    source: SourceMeta,
+}
+
+impl GpxMeta {
+    pub fn new(path: PathBuf) -> Self {
+        let source = SourceMeta::new(
+            path.clone(),
+            SourceOptions {
+                format: Some(Format::Gpx),
+                ..Default::default()
+            },
+        );
+
+        Self { path, source }
+    }
+}
+
+impl Meta for GpxMeta {
+    fn headers(&self) -> MetaResult {
+        let gpx = read_gpx(&self.path)?;
+
+        if let Some(creator) = gpx.creator {
+            println!("Creator: {creator}");
+        }
+        if let Some(metadata) = gpx.metadata {
+            if let Some(name) = metadata.name {
+                println!("Name: {name}");
+            }
+            if let Some(description) = metadata.description {
+                println!("Description: {description}");
+            }
+            if let Some(bounds) = metadata.bounds {
+                println!(
+                    "Bounds: [{}, {}, {}, {}]",
+                    bounds.min().x,
+                    bounds.min().y,
+                    bounds.max().x,
+                    bounds.max().y
+                );
+            }
+        }
+        println!("Waypoints: {}", gpx.waypoints.len());
+        println!("Routes: {}", gpx.routes.len());
+        println!("Tracks: {}", gpx.tracks.len());
+
+        Ok(())
+    }
+
+    /// GPX fields are fixed, and are all text except for the elevation.
+    fn fields(&self, show_types: bool, _: bool) -> MetaResult {
+        for field in GPX_FIELDS {
+            if show_types {
+                let field_type = match field {
+                    "ele" => "Number",
+                    "time" => "Time",
+                    _ => "String",
+                };
+                println!("{field} [{field_type}]");
+            } else {
+                println!("{field}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn count(&self) -> MetaResult {
+        self.source.count()
+    }
+
+    fn data(&self, opts: DataOpts) -> MetaResult {
+        self.source.data(opts)
+    }
+}
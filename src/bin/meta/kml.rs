@@ -1,24 +1,30 @@
-use std::{
-    collections::{HashMap, HashSet},
-    iter::empty,
-    path::PathBuf,
-};
+use std::{collections::HashSet, path::PathBuf};
 
-use csv::WriterBuilder;
 use geo_munge::{
-    error::Error,
-    kml::{read_kml, Kml, KmlItemRef},
+    kml::{extended_data, read_kml, Kml, KmlItemRef},
+    qt::{Format, SourceOptions},
 };
 
+use crate::source::SourceMeta;
 use crate::{DataOpts, Meta, MetaResult};
 
 pub struct KmlMeta {
     path: PathBuf,
+    // Count and data are the same as for the generic source
+    source: SourceMeta,
 }
 
 impl KmlMeta {
     pub fn new(path: PathBuf) -> Self {
-        Self { path }
+        let source = SourceMeta::new(
+            path.clone(),
+            SourceOptions {
+                format: Some(Format::Kml),
+                ..Default::default()
+            },
+        );
+
+        Self { path, source }
     }
 }
 
@@ -57,110 +63,52 @@ impl Meta for KmlMeta {
     }
 
     /// Limited support of fields for KML - only reports fields from Placemark
-    /// objects.
-    fn fields(&self, _: bool) -> MetaResult {
+    /// objects. KML values are all text, so types are only more specific for
+    /// extended data fields declared in a Schema.
+    fn fields(&self, show_types: bool, _: bool) -> MetaResult {
         let kml = Kml::from_path(&self.path)?;
 
-        let fields = make_fields(&kml, None, None);
+        let fields = make_fields(&kml);
+        let types = kml.schema_types();
 
         for field in fields {
-            println!("{field}");
+            if show_types {
+                let field_type = types.get(&field).map(String::as_str).unwrap_or("string");
+                println!("{field} [{field_type}]");
+            } else {
+                println!("{field}");
+            }
         }
 
         Ok(())
     }
 
     fn count(&self) -> MetaResult {
-        let kml = Kml::from_path(&self.path)?;
-        let count = &kml.into_iter().count();
-
-        println!("{count}");
-
-        Ok(())
+        self.source.count()
     }
 
     fn data(&self, opts: DataOpts) -> MetaResult {
-        let delimiter = opts.delimiter.as_bytes();
-        if delimiter.len() != 1 {
-            return Err(Box::new(Error::InvalidDelimiter));
-        }
-        let delimiter = delimiter[0];
-
-        let mut writer = WriterBuilder::new()
-            .delimiter(delimiter)
-            .from_writer(std::io::stdout());
-
-        let kml = Kml::from_path(&self.path)?;
-        let fields: Vec<_> = make_fields(&kml, Some(opts.start), opts.length)
-            .into_iter()
-            .collect();
-
-        // Write out the header
-        if opts.headers {
-            if opts.index {
-                writer.write_field("index")?;
-            }
-            for field in &fields {
-                writer.write_field(field)?;
-            }
-            writer.write_record(empty::<&str>())?;
-        }
-
-        for (i, item) in kml
-            .iter()
-            .skip(opts.start)
-            .take(opts.length.unwrap_or(usize::MAX))
-            .enumerate()
-        {
-            if opts.index {
-                writer.write_field(i.to_string())?;
-            }
-            match item {
-                KmlItemRef::Placemark(p) => {
-                    // Put children into a hash map rather than finding each time
-                    // Unclear that this will be more efficient
-                    let data: HashMap<&String, &Option<String>> =
-                        HashMap::from_iter(p.children.iter().map(|e| (&e.name, &e.content)));
-
-                    writer.write_record(fields.iter().map(|f| {
-                        match f.as_str() {
-                            "name" => p.name.to_owned().unwrap_or_default(),
-                            "description" => p.description.to_owned().unwrap_or_default(),
-                            _ => data
-                                .get(f)
-                                .and_then(|d| d.as_ref())
-                                .map(|s| s.to_owned())
-                                .unwrap_or_default(),
-                        }
-                    }))?;
-                }
-                // No meta for shapes
-                _ => {
-                    writer.write_record(fields.iter().map(|_| ""))?;
-                }
-            }
-        }
-
-        Ok(())
+        self.source.data(opts)
     }
 }
 
 /// Limited support of fields for KML - only reports fields from Placemark
 /// objects.
 // TODO: Does/should this have the same semantics as the proximity version?
-fn make_fields(kml: &Kml, skip: Option<usize>, n: Option<usize>) -> HashSet<String> {
+fn make_fields(kml: &Kml) -> HashSet<String> {
     // Placemarks can always have name and desc, so add them even if not guaranteed
     let mut fields = HashSet::from(["name".to_string(), "description".to_string()]);
 
-    for d in kml
-        .iter()
-        .skip(skip.unwrap_or(0))
-        .take(n.unwrap_or(usize::MAX))
-    {
+    for d in kml.iter() {
         if let KmlItemRef::Placemark(p) = d {
-            for child in &p.children {
+            for child in p.children.iter().filter(|c| c.name != "ExtendedData") {
                 fields.insert(child.name.to_string());
             }
+            for (name, _) in extended_data(p) {
+                fields.insert(name.to_string());
+            }
+            // Includes the synthetic folder and style attributes
+            fields.extend(p.attrs.keys().cloned());
         }
     }
 
@@ -1,17 +1,33 @@
 mod args;
+mod csv;
 mod geojson;
+mod geometry;
+mod gpx;
 mod kml;
+mod osm;
 mod shapefile;
+mod source;
+mod topojson;
 
 use std::path::PathBuf;
 
 use clap::Parser;
+use geo_munge::archive::{Input, Packing};
+use geo_munge::encoding::TextEncoding;
 use geo_munge::error::Error;
+use geo_munge::osm::TagFilter;
+use geo_munge::qt::{Format, SourceOptions};
 
 use crate::args::{Cli, Command};
+use crate::csv::CsvMeta;
 use crate::geojson::GeoJsonMeta;
+use crate::geometry::GeometryColumns;
+use crate::gpx::GpxMeta;
 use crate::kml::KmlMeta;
+use crate::osm::OsmMeta;
 use crate::shapefile::ShapefileMeta;
+use crate::source::SourceMeta;
+use crate::topojson::TopoJsonMeta;
 
 type MetaResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -21,6 +37,7 @@ struct DataOpts {
     pub start: usize,
     pub length: Option<usize>,
     pub index: bool,
+    pub geometry: GeometryColumns,
 }
 
 trait Meta {
@@ -33,8 +50,9 @@ trait Meta {
     /// Depending on the input format, fields may be nested, not representable,
     /// or sparsely populated. This method makes a best effort only to print
     /// what it can in a flattened format, but makes no promises of being
-    /// exhaustive.
-    fn fields(&self, show_types: bool) -> MetaResult;
+    /// exhaustive. With `deep`, formats with nested fields list the path to
+    /// every leaf value instead of the first level.
+    fn fields(&self, show_types: bool, deep: bool) -> MetaResult;
 
     /// Print the number of top-level records to stdout.
     fn count(&self) -> MetaResult;
@@ -47,38 +65,86 @@ trait Meta {
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Cli::parse();
 
+    // Unpack compressed files and archives, which must be kept until the end to keep the files
+    let input = Input::open(&args.path, args.member.as_deref())?;
+
     // Load the appropriate meta based on the incoming file type
-    let meta = get_meta_from_path(args.path)?;
+    let meta = get_meta_from_path(input.path().clone(), args.format, args.encoding, args.tag)?;
 
     match args.command {
-        Command::Header => meta.headers(),
+        Command::Header => {
+            print_packing(input.packing());
+            meta.headers()
+        }
         Command::Count => meta.count(),
-        Command::Fields { types } => meta.fields(types),
+        Command::Fields { types, deep } => meta.fields(types, deep),
         Command::Data {
             headers,
             delimiter,
             start,
             length,
             index,
+            wkt,
+            geojson,
+            centroid,
+            interior_point,
+            bbox,
         } => meta.data(DataOpts {
             headers,
             delimiter,
             start,
             length,
             index,
+            geometry: GeometryColumns {
+                wkt,
+                geojson,
+                centroid,
+                interior_point,
+                bbox,
+            },
         }),
     }
 }
 
-fn get_meta_from_path(path: PathBuf) -> Result<Box<dyn Meta>, Error> {
-    match path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .ok_or(Error::CannotParseFileExtension(path.clone()))?
-    {
-        "shp" => Ok(Box::new(ShapefileMeta::new(path))),
-        "json" | "geojson" => Ok(Box::new(GeoJsonMeta::new(path))),
-        "kml" | "kmz" => Ok(Box::new(KmlMeta::new(path))),
-        _ => Err(Error::UnsupportedFileType),
+/// Print how the input was packed, with the members of zip archives.
+fn print_packing(packing: &Packing) {
+    match packing {
+        Packing::None => {}
+        Packing::Gzip => println!("Compression: gzip"),
+        Packing::Zip { members, member } => {
+            println!("Archive members:");
+            for m in members {
+                println!("  {m}");
+            }
+            if let Some(member) = member {
+                println!("Reading member: {member}");
+            }
+        }
+    }
+}
+
+fn get_meta_from_path(
+    path: PathBuf,
+    format: Option<Format>,
+    encoding: Option<TextEncoding>,
+    tags: Vec<TagFilter>,
+) -> Result<Box<dyn Meta>, Error> {
+    // Records are always read through the format's source. Formats with a dedicated meta add
+    // richer header and field output on top
+    match Format::resolve(&path, format)? {
+        Format::Shapefile => Ok(Box::new(ShapefileMeta::new(path, encoding))),
+        Format::GeoJson => Ok(Box::new(GeoJsonMeta::new(path))),
+        Format::Kml => Ok(Box::new(KmlMeta::new(path))),
+        Format::Gpx => Ok(Box::new(GpxMeta::new(path))),
+        Format::TopoJson => Ok(Box::new(TopoJsonMeta::new(path))),
+        Format::Osm => Ok(Box::new(OsmMeta::new(path, tags))),
+        Format::Wkt => Ok(Box::new(SourceMeta::new(
+            path,
+            SourceOptions {
+                format: Some(Format::Wkt),
+                ..Default::default()
+            },
+        ))),
+        Format::Csv => Ok(Box::new(CsvMeta::new(path, encoding)?)),
     }
 }
@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use geo_munge::{
+    osm::{read_osm, TagFilter, OSM_FIELDS},
+    qt::{Format, SourceOptions},
+};
+
+use crate::source::SourceMeta;
+use crate::{DataOpts, Meta, MetaResult};
+
+pub struct OsmMeta {
+    path: PathBuf,
+    filter: Vec<TagFilter>,
+    // Count and data are the same as for the generic source
+    source: SourceMeta,
+}
+
+impl OsmMeta {
+    pub fn new(path: PathBuf, filter: Vec<TagFilter>) -> Self {
+        let source = SourceMeta::new(
+            path.clone(),
+            SourceOptions {
+                format: Some(Format::Osm),
+                osm_tags: filter.clone(),
+                ..Default::default()
+            },
+        );
+
+        Self {
+            path,
+            filter,
+            source,
+        }
+    }
+}
+
+impl Meta for OsmMeta {
+    fn headers(&self) -> MetaResult {
+        let osm = read_osm(&self.path)?;
+
+        if let Some(bounds) = osm.bounds {
+            println!(
+                "Bounds: [{}, {}, {}, {}]",
+                bounds.min().x,
+                bounds.min().y,
+                bounds.max().x,
+                bounds.max().y
+            );
+        }
+        println!("Nodes: {}", osm.node_count());
+        println!("Ways: {}", osm.way_count());
+        println!("Relations: {}", osm.relation_count());
+        println!("Features: {}", osm.count(&self.filter));
+
+        Ok(())
+    }
+
+    /// Fields are the id and type, then every tag key on the features. Tags are always text.
+    fn fields(&self, show_types: bool, _: bool) -> MetaResult {
+        let tags = read_osm(&self.path)?.tag_keys(&self.filter);
+
+        for field in OSM_FIELDS.iter().map(|f| f.to_string()).chain(tags) {
+            if show_types {
+                let field_type = match field.as_str() {
+                    "osm_id" => "Number",
+                    _ => "String",
+                };
+                println!("{field} [{field_type}]");
+            } else {
+                println!("{field}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn count(&self) -> MetaResult {
+        self.source.count()
+    }
+
+    fn data(&self, opts: DataOpts) -> MetaResult {
+        self.source.data(opts)
+    }
+}
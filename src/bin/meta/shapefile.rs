@@ -1,25 +1,48 @@
-use std::{fs::File, io::BufReader, iter::once, path::PathBuf};
-
-use csv::WriterBuilder;
-use geo_munge::{error::Error, shp::convert_dbase_field_opt};
-use shapefile::Reader;
-
+use std::path::PathBuf;
+
+use geo::{Point, Rect};
+use geo_munge::{
+    encoding::TextEncoding,
+    error::Error,
+    qt::{Format, SourceOptions},
+    shp::{
+        dbf::{dbf_encoding, open_shapefile, ShpReader},
+        prj::{read_prj, Crs},
+    },
+};
+
+use crate::source::SourceMeta;
 use crate::{DataOpts, Meta, MetaResult};
 
 pub struct ShapefileMeta {
     path: PathBuf,
+    encoding: Option<TextEncoding>,
+    // Count and data are the same as for the generic source
+    source: SourceMeta,
 }
 
 impl ShapefileMeta {
-    pub fn new(path: PathBuf) -> Self {
-        Self { path }
+    pub fn new(path: PathBuf, encoding: Option<TextEncoding>) -> Self {
+        let source = SourceMeta::new(
+            path.clone(),
+            SourceOptions {
+                format: Some(Format::Shapefile),
+                encoding,
+                ..Default::default()
+            },
+        );
+
+        Self {
+            path,
+            encoding,
+            source,
+        }
     }
 
-    pub fn reader(&self) -> Result<Reader<BufReader<File>, BufReader<File>>, Error> {
-        // Load the shapefile, exiting with an error if the file cannot read
-        // Then build the quadtree
-        shapefile::Reader::from_path(&self.path)
-            .map_err(|_| Error::CannotReadFile(self.path.clone()))
+    pub fn reader(&self) -> Result<ShpReader, Error> {
+        // Load the shapefile, exiting with an error if the file cannot read, decoding the text
+        // attributes with the declared or overridden encoding
+        open_shapefile(&self.path, self.encoding)
     }
 
     // TODO: 2024 edition fix added the `+ use<>` to this return, unsure what it is doing, check if it remains
@@ -55,12 +78,37 @@ impl Meta for ShapefileMeta {
             "Bounding box: [{}, {}, {}, {}]",
             bbox_min.x, bbox_min.y, bbox_max.x, bbox_max.y
         );
+
+        // Projected shapefiles also get the bbox in lng/lat, as used by the other tools. A system
+        // that cannot be read is only an error when building the quadtree, so is reported here
+        match read_prj(&self.path) {
+            Ok(Crs::Geographic) => {}
+            Ok(crs) => {
+                let bbox =
+                    crs.reproject_rect(Rect::new(Point::from(bbox_min), Point::from(bbox_max)));
+                println!(
+                    "Bounding box (lng/lat): [{}, {}, {}, {}]",
+                    bbox.min().x,
+                    bbox.min().y,
+                    bbox.max().x,
+                    bbox.max().y
+                );
+            }
+            Err(Error::UnsupportedCrs(crs)) => {
+                println!("Coordinate system: {crs} (unsupported)")
+            }
+            Err(err) => println!("Coordinate system: Unreadable ({err})"),
+        }
         println!("File length: {}", header.file_length);
+        println!(
+            "Attribute encoding: {}",
+            dbf_encoding(&self.path, self.encoding)?
+        );
 
         Ok(())
     }
 
-    fn fields(&self, show_types: bool) -> MetaResult {
+    fn fields(&self, show_types: bool, _: bool) -> MetaResult {
         for v in self.field_iter(show_types)? {
             println!("{}", v);
         }
@@ -69,69 +117,10 @@ impl Meta for ShapefileMeta {
     }
 
     fn count(&self) -> MetaResult {
-        let count = self.reader()?.iter_shapes_and_records().count();
-
-        println!("{count}");
-
-        Ok(())
+        self.source.count()
     }
 
     fn data(&self, opts: DataOpts) -> MetaResult {
-        let delimiter = opts.delimiter.as_bytes();
-        if delimiter.len() != 1 {
-            return Err(Box::new(Error::InvalidDelimiter));
-        }
-        let delimiter = delimiter[0];
-
-        let mut reader = self.reader()?;
-        let mut writer = WriterBuilder::new()
-            .delimiter(delimiter)
-            .from_writer(std::io::stdout());
-
-        // Need to get the headers in order whether or not they are being printed
-        // Underlying hashmap knocks things out of order so need to iterate in
-        // order to preserve consistency
-        let field_iter: Vec<_> = self.field_iter(false)?.collect();
-
-        if opts.headers {
-            // Add an index field at the front if the option is set
-            if opts.index {
-                writer.write_record(once(&"index".to_string()).chain(&field_iter))?;
-            } else {
-                writer.write_record(&field_iter)?;
-            }
-        }
-
-        let n = opts.length.unwrap_or(usize::MAX);
-        for (i, record) in reader
-            .iter_shapes_and_records()
-            .enumerate()
-            .skip(opts.start)
-            .take(n)
-        {
-            match record {
-                Ok((_, record)) => {
-                    // Use the fields vector to iterate in-order
-                    let record_iter = field_iter
-                        .iter()
-                        .map(|f| convert_dbase_field_opt(record.get(f)));
-
-                    // Insert the stringified index as the first item in the
-                    // Iterator if the option is set
-                    let record_iter: Box<dyn Iterator<Item = String>> = if opts.index {
-                        Box::new(once(i.to_string()).chain(record_iter))
-                    } else {
-                        Box::new(record_iter)
-                    };
-
-                    if writer.write_record(record_iter).is_err() {
-                        eprintln!("failed to write output for record at index {i}");
-                    }
-                }
-                Err(_) => eprintln!("cannot read record at index {i}"),
-            }
-        }
-
-        Ok(())
+        self.source.data(opts)
     }
 }
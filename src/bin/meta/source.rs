@@ -0,0 +1,213 @@
+use std::{
+    io::Stdout,
+    iter::{empty, from_fn, once},
+    path::PathBuf,
+};
+
+use csv::{Writer, WriterBuilder};
+use geo_munge::{
+    error::Error,
+    qt::{open_source, Format, Source, SourceOptions},
+};
+use quadtree::Geometry;
+
+use crate::geometry::from_parts;
+use crate::{DataOpts, Meta, MetaResult};
+
+/// Generic [`Meta`] implementation for any format in the registry, built only on the library's
+/// [`geo_munge::qt::Source`] trait. Formats with a dedicated implementation wrap this for their
+/// count and data, adding richer header and field output, but any new format is supported here
+/// without further work.
+pub struct SourceMeta {
+    path: PathBuf,
+    opts: SourceOptions,
+}
+
+impl SourceMeta {
+    pub fn new(path: PathBuf, opts: SourceOptions) -> Self {
+        Self { path, opts }
+    }
+
+    fn open(&self) -> Result<Box<dyn Source>, Error> {
+        open_source(&self.path, &self.opts)
+    }
+}
+
+impl Meta for SourceMeta {
+    fn headers(&self) -> MetaResult {
+        let source = self.open()?;
+
+        println!(
+            "Format: {:?}",
+            Format::resolve(&self.path, self.opts.format)?
+        );
+        if let Some(bbox) = source.bbox()? {
+            println!(
+                "Bounding box: [{}, {}, {}, {}]",
+                bbox.min().x,
+                bbox.min().y,
+                bbox.max().x,
+                bbox.max().y
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Types are not available from the generic source, so are never printed.
+    fn fields(&self, _: bool, _: bool) -> MetaResult {
+        let fields = self
+            .open()?
+            .fields()?
+            .ok_or(Error::TypeDoesNotContainMetadata)?;
+
+        for field in fields {
+            println!("{field}");
+        }
+
+        Ok(())
+    }
+
+    /// Falls back to counting the distinct feature indices if the source cannot provide a count.
+    fn count(&self) -> MetaResult {
+        let mut source = self.open()?;
+
+        let count = match source.count()? {
+            Some(count) => count,
+            None => {
+                let mut last = None;
+                source
+                    .datums()?
+                    .flatten()
+                    .filter(|d| last.replace(d.index()) != Some(d.index()))
+                    .count()
+            }
+        };
+
+        println!("{count}");
+
+        Ok(())
+    }
+
+    /// Every record is written for sources that can read metadata without the geometry, with
+    /// empty geometry columns for features whose geometry cannot be read. Other sources are
+    /// written from their datums, skipping features whose geometry cannot be read. Geometry errors
+    /// are reported to stderr, as they are when building the quadtree.
+    fn data(&self, opts: DataOpts) -> MetaResult {
+        let delimiter = opts.delimiter.as_bytes();
+        if delimiter.len() != 1 {
+            return Err(Box::new(Error::InvalidDelimiter));
+        }
+        let delimiter = delimiter[0];
+
+        let mut writer = WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(std::io::stdout());
+
+        let mut source = self.open()?;
+        let fields = source.fields()?;
+
+        // Write out the header
+        if opts.headers {
+            let field_iter = fields
+                .iter()
+                .flatten()
+                .map(String::as_str)
+                .chain(opts.geometry.headers());
+            if opts.index {
+                writer.write_record(once("index").chain(field_iter))?;
+            } else {
+                writer.write_record(field_iter)?;
+            }
+        }
+
+        let end = opts.start.saturating_add(opts.length.unwrap_or(usize::MAX));
+
+        if let Some(records) = source.records()? {
+            // The geometry is read from a second source, as the records and the datums both borrow
+            // their source. Records are still written if the geometry cannot be read at all
+            let mut geometry_source = self.open()?;
+            let mut datums = geometry_source
+                .datums()
+                .unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    Box::new(empty())
+                })
+                .peekable();
+
+            for (index, record) in records.enumerate().take(end) {
+                // Multi-part features are split into consecutive datums with the same index
+                let mut parts = Vec::new();
+                while let Some(res) =
+                    datums.next_if(|res| res.as_ref().map_or(true, |d| d.index() <= index))
+                {
+                    match res {
+                        Ok(datum) => parts.push(datum.geometry().clone()),
+                        Err(err) => eprintln!("{err}"),
+                    }
+                }
+
+                match record {
+                    Ok(record) if index >= opts.start => write_row(
+                        &mut writer,
+                        &opts,
+                        index,
+                        record.iter_str(&fields, true),
+                        parts,
+                    ),
+                    Ok(_) => {}
+                    Err(err) => eprintln!("{err}"),
+                }
+            }
+
+            return Ok(());
+        }
+
+        // Multi-part features are split into consecutive datums with the same index, so take the
+        // meta from the first datum for each feature and the geometry from all of them
+        let mut datums = source
+            .datums()?
+            .filter_map(|res| res.map_err(|err| eprintln!("{err}")).ok())
+            .peekable();
+        let features = from_fn(move || {
+            let datum = datums.next()?;
+            let mut parts = vec![datum.geometry().clone()];
+            while let Some(part) = datums.next_if(|d| d.index() == datum.index()) {
+                parts.push(part.geometry().clone());
+            }
+            Some((datum, parts))
+        })
+        .skip(opts.start)
+        .take(end - opts.start);
+
+        for (datum, parts) in features {
+            // Nested JSON is written as text, matching the GeoJSON data output
+            let meta = datum.meta_iter(&fields, true);
+            write_row(&mut writer, &opts, datum.index(), meta, parts);
+        }
+
+        Ok(())
+    }
+}
+
+/// Write the row for the feature at `index`, with its metadata and the geometry columns built from
+/// its parts.
+fn write_row(
+    writer: &mut Writer<Stdout>,
+    opts: &DataOpts,
+    index: usize,
+    meta: impl Iterator<Item = String>,
+    parts: Vec<Geometry<f64>>,
+) {
+    let geometry = from_parts(parts);
+    let record_iter = meta.chain(opts.geometry.values(geometry.as_ref()));
+    let res = if opts.index {
+        writer.write_record(once(index.to_string()).chain(record_iter))
+    } else {
+        writer.write_record(record_iter)
+    };
+
+    if res.is_err() {
+        eprintln!("failed to write output for record at index {index}");
+    }
+}
@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+
+use geo_munge::{
+    error::Error,
+    geojson::read_topojson,
+    qt::{Format, SourceOptions},
+};
+use geojson::Feature;
+
+use crate::geojson::{print_bbox, print_fields};
+use crate::source::SourceMeta;
+use crate::{DataOpts, Meta, MetaResult};
+
+pub struct TopoJsonMeta {
+    path: PathBuf,
+    // Count and data are the same as for the generic source
+    source: SourceMeta,
+}
+
+impl TopoJsonMeta {
+    pub fn new(path: PathBuf) -> Self {
+        let source = SourceMeta::new(
+            path.clone(),
+            SourceOptions {
+                format: Some(Format::TopoJson),
+                ..Default::default()
+            },
+        );
+
+        Self { path, source }
+    }
+
+    /// The decoded Features of every object in the topology, in order.
+    fn features(&self) -> Result<impl Iterator<Item = Result<Feature, Error>>, Error> {
+        Ok(read_topojson(&self.path)?.features().map(Ok))
+    }
+}
+
+impl Meta for TopoJsonMeta {
+    fn headers(&self) -> MetaResult {
+        let topology = read_topojson(&self.path)?;
+
+        println!("Top-level type: Topology");
+        print_bbox(&topology.bbox)?;
+        println!("Objects:");
+        for object in topology.objects {
+            println!("  {} ({} geometries)", object.name, object.features.len());
+        }
+
+        Ok(())
+    }
+
+    /// Print the id and the properties fields of the geometries in every object, as for GeoJSON.
+    fn fields(&self, show_types: bool, deep: bool) -> MetaResult {
+        print_fields(self.features()?, show_types, deep)
+    }
+
+    /// Print the number of geometries across all the objects.
+    fn count(&self) -> MetaResult {
+        self.source.count()
+    }
+
+    fn data(&self, opts: DataOpts) -> MetaResult {
+        self.source.data(opts)
+    }
+}
@@ -0,0 +1,157 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use quadtree::MEAN_EARTH_RADIUS;
+
+use geo_munge::error::Error;
+use geo_munge::qt::{open_source, ParsedRecord, SourceOptions};
+
+use crate::run::{FindResult, Match};
+use crate::{CsvWriter, InputSettings};
+
+/// Running totals for the input points assigned to a single reference feature.
+struct Summary {
+    /// Metadata fields for the feature, built once when the feature is first seen.
+    meta: Vec<String>,
+    count: usize,
+    min: f64,
+    max: f64,
+    total: f64,
+    /// Sum of the parsed values of the `--sum-field` column.
+    sum: f64,
+    /// Number of assigned points where the `--sum-field` column could be parsed.
+    sum_count: usize,
+}
+
+impl Summary {
+    fn new(meta: Vec<String>) -> Self {
+        Self {
+            meta,
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            total: 0.0,
+            sum: 0.0,
+            sum_count: 0,
+        }
+    }
+
+    fn add(&mut self, distance: f64, parsed: &ParsedRecord, settings: &InputSettings) {
+        self.count += 1;
+        self.min = self.min.min(distance);
+        self.max = self.max.max(distance);
+        self.total += distance;
+
+        let value = settings
+            .sum_index
+            .and_then(|i| parsed.record.get(i))
+            .and_then(|v| v.trim().parse::<f64>().ok());
+        if let Some(value) = value {
+            self.sum += value;
+            self.sum_count += 1;
+        }
+    }
+}
+
+/// Accumulates find results by reference feature index, to output the reverse view of the
+/// matches: how many input points were assigned to each feature.
+#[derive(Default)]
+pub(crate) struct Aggregator {
+    summaries: BTreeMap<usize, Summary>,
+}
+
+impl Aggregator {
+    /// Add the result of a find/knn to the running totals.
+    ///
+    /// Every returned match counts as an assignment, so with `-k` each input point is assigned to
+    /// up to `k` features. Errors are output to stderr as they would be when writing rows.
+    pub(crate) fn add(&mut self, settings: &InputSettings, output: Result<FindResult, Error>) {
+        match output {
+            Ok(FindResult::One(ref parsed, m)) => {
+                self.add_match(m, parsed, settings);
+            }
+            Ok(FindResult::Many(ref parsed, matches)) => {
+                for m in matches {
+                    self.add_match(m, parsed, settings);
+                }
+            }
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+
+    fn add_match(&mut self, m: Match, parsed: &ParsedRecord, settings: &InputSettings) {
+        let datum = m.datum;
+
+        // Matches are built without metadata in aggregate mode, so build it for new features
+        self.summaries
+            .entry(datum.index())
+            .or_insert_with(|| {
+                Summary::new(
+                    datum
+                        .meta_iter(&settings.fields, settings.nested_json)
+                        .collect(),
+                )
+            })
+            .add(m.distance, parsed, settings);
+    }
+
+    /// Write one row per reference feature to the writer, ordered by the feature index.
+    ///
+    /// If empty features are requested, the reference file at `path` is read again with the
+    /// `source` options to pick up the features that were never matched.
+    pub(crate) fn write(
+        mut self,
+        mut writer: CsvWriter,
+        path: &PathBuf,
+        source: &SourceOptions,
+        settings: &InputSettings,
+    ) -> Result<(), Error> {
+        if settings.include_empty {
+            for datum in open_source(path, source)?.datums()?.flatten() {
+                self.summaries.entry(datum.index()).or_insert_with(|| {
+                    Summary::new(
+                        datum
+                            .meta_iter(&settings.fields, settings.nested_json)
+                            .collect(),
+                    )
+                });
+            }
+        }
+
+        for (index, summary) in self.summaries {
+            let mut row = vec![index.to_string(), summary.count.to_string()];
+
+            // Distances are in meters, truncated at mm, and blank where nothing was assigned
+            if summary.count > 0 {
+                row.push(format!("{:.3}", summary.min * MEAN_EARTH_RADIUS));
+                row.push(format!(
+                    "{:.3}",
+                    summary.total / summary.count as f64 * MEAN_EARTH_RADIUS
+                ));
+                row.push(format!("{:.3}", summary.max * MEAN_EARTH_RADIUS));
+            } else {
+                row.extend([String::default(), String::default(), String::default()]);
+            }
+
+            if settings.sum_index.is_some() {
+                row.push(summary.sum.to_string());
+                if summary.sum_count > 0 {
+                    row.push((summary.sum / summary.sum_count as f64).to_string());
+                } else {
+                    row.push(String::default());
+                }
+            }
+
+            if writer
+                .write_record(row.into_iter().chain(summary.meta))
+                .is_err()
+            {
+                eprintln!(
+                    "Failed to write output line for feature at index {}.",
+                    index
+                );
+            }
+        }
+
+        writer.flush().map_err(|err| Error::FileIOError(err))
+    }
+}
@@ -1,4 +1,8 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use geo_munge::encoding::TextEncoding;
+use geo_munge::error::parse_arg;
+use geo_munge::osm::TagFilter;
+use geo_munge::qt::Format;
 
 /// We look in the current directory for a data.shp file by default
 const DEFAULT_PATH: &str = "./data.shp";
@@ -10,10 +14,72 @@ const DEFAULT_PATH: &str = "./data.shp";
 #[derive(Parser, Debug)]
 pub struct Args {
     /// The file to use to assemble the QuadTree. If not provided will use
-    /// {n}the default at ./data.shp. Supports multiple geographic file types.
+    /// {n}the default at ./data.shp. Supports multiple geographic file types,
+    /// {n}which may be gzip compressed or in a zip archive.
     #[arg(default_value = DEFAULT_PATH)]
     pub path: std::path::PathBuf,
 
+    /// Override the format of the quadtree file, one of shp, geojson,
+    /// {n}topojson, kml, gpx, osm, wkt or csv. By default the format is
+    /// {n}detected from the file content, falling back to the extension.
+    #[arg(long, value_parser = parse_arg::<Format>)]
+    pub format: Option<Format>,
+
+    /// Set the delimiter for a csv quadtree file. Independent of the
+    /// {n}delimiter for the test points. By default the most frequent of a
+    /// {n}comma, tab, semicolon or pipe in the header row is used.
+    #[arg(long = "ref-delimiter")]
+    pub ref_delimiter: Option<String>,
+
+    /// Set the quote character for a csv quadtree file. Defaults to a
+    /// {n}double quote.
+    #[arg(long = "ref-quote", default_value = "\"")]
+    pub ref_quote: String,
+
+    /// Read a csv quadtree file that has no header row. The lng and lat
+    /// {n}columns must then be set with --ref-lng-lat, or the geometry
+    /// {n}column with --ref-geometry, and metadata fields are named by their
+    /// {n}0-indexed column position.
+    #[arg(long = "ref-no-headers", requires = "ref_location")]
+    pub ref_no_headers: bool,
+
+    /// 0-indexed positions of the lng and lat columns in a csv quadtree
+    /// {n}file, passed as `lng,lat`. Used instead of looking for lng and
+    /// {n}lat headers.
+    #[arg(
+        long = "ref-lng-lat",
+        value_delimiter = ',',
+        num_args = 2,
+        group = "ref_location"
+    )]
+    pub ref_lng_lat: Option<Vec<usize>>,
+
+    /// Column of a csv quadtree file holding a WKT or hex WKB geometry for
+    /// {n}each row, used instead of lng and lat. By default a wkt, wkb,
+    /// {n}geometry or geom column is used if there are no lng and lat
+    /// {n}columns.
+    #[arg(long = "ref-geometry", group = "ref_location")]
+    pub ref_geometry: Option<String>,
+
+    /// Only load features of an OSM quadtree file with this tag, passed
+    /// {n}as `key` for any value or `key=value`, such as amenity=hospital.
+    /// {n}Repeat to load features matching any of the tags.
+    #[arg(long = "ref-tag", value_parser = parse_arg::<TagFilter>)]
+    pub ref_tag: Vec<TagFilter>,
+
+    /// Member of a zip quadtree file to read, by its name or file name.
+    /// {n}By default the only shapefile, or the only member, is read.
+    /// {n}Shapefiles are read with their .shx, .dbf, .prj and .cpg files.
+    #[arg(long)]
+    pub member: Option<String>,
+
+    /// Text encoding of a csv quadtree file or of shapefile attributes,
+    /// {n}for example utf8, latin1, iso-8859-2, windows-1252 or cp850.
+    /// {n}Csvs default to UTF-8. Shapefiles default to the encoding in
+    /// {n}the .cpg file or the dbf header, and otherwise UTF-8.
+    #[arg(long, value_parser = parse_arg::<TextEncoding>)]
+    pub encoding: Option<TextEncoding>,
+
     /// Print verbose logging to stderr.
     #[arg(short, long)]
     pub verbose: bool,
@@ -34,6 +100,14 @@ pub struct Args {
     #[arg(short)]
     pub k: Option<usize>,
 
+    /// When retrieving `k` nearest neighbors, return at most one row per
+    /// {n}source feature. Multi-part features are split into parts on load,
+    /// {n}so without this flag the same feature can appear several times.
+    /// {n}Only the nearest part is kept and the search continues until `k`
+    /// {n}distinct features are found.
+    #[arg(long, requires = "k")]
+    pub distinct: bool,
+
     /// Constrain the search radius by a maximum distance in meters. If not
     /// {n}included, the search ring is unbounded, but if provided, no
     /// {n}points outside the radius will be selected.
@@ -70,20 +144,73 @@ pub struct Args {
     #[arg(long = "single-thread")]
     pub single_thread: bool,
 
+    /// Set the number of threads used for multithreaded searches. Output is
+    /// {n}always written from one additional thread. Defaults to the number
+    /// {n}of logical CPUs.
+    #[arg(long, conflicts_with = "single_thread")]
+    pub threads: Option<usize>,
+
     /// Provide an optional list of any metadata fields from the quadtree
     /// {n}data that should be output with the match. The input's index
     /// {n}in load order and the `id` field will automatically be added.
     /// {n}Any other must be provided here as a comma separated list of
-    /// {n}field names.
+    /// {n}field names. GeoJSON properties and csv columns holding JSON can
+    /// {n}be reached with a path such as address.city or tags[0].
     #[arg(long, value_delimiter = ',')]
     pub fields: Option<Vec<String>>,
 
+    /// Write --fields that hold JSON arrays or objects as compact JSON text.
+    /// {n}By default they are left empty.
+    #[arg(long = "nested-json")]
+    pub nested_json: bool,
+
     /// The output printer will look for a field in the input csv called "id"
     /// {n}and print it as a unique identifier of the row in addition to the
     /// {n}index. To override the default of "id" use this field.
     #[arg(long = "id-label")]
     pub id_label: Option<String>,
 
+    /// Summarise the matches per reference feature rather than writing one
+    /// {n}row per input point. Once the input stream is exhausted, one row is
+    /// {n}written for each matched feature with the count of assigned input
+    /// {n}points and the min, mean, and max distance to them.
+    #[arg(long)]
+    pub aggregate: bool,
+
+    /// With --aggregate, also write the sum and mean of this numeric column
+    /// {n}from the input stream for each feature. Values that cannot be
+    /// {n}parsed as numbers are skipped.
+    #[arg(long = "sum-field", requires = "aggregate")]
+    pub sum_field: Option<String>,
+
+    /// With --aggregate, also write rows for reference features that have
+    /// {n}no input points assigned. This reads the reference file a second
+    /// {n}time once the input stream is exhausted.
+    #[arg(long = "include-empty", requires = "aggregate")]
+    pub include_empty: bool,
+
+    /// Find the nearest other feature for every feature in the quadtree file
+    /// {n}instead of reading test points from stdin. Writes the index of each
+    /// {n}feature, the index of its nearest neighbour, the distance between
+    /// {n}them, and any `--fields` from both sides. Only point features can be
+    /// {n}used as the query.
+    #[arg(long = "self-join", conflicts_with_all = ["k", "aggregate"])]
+    pub self_join: bool,
+
+    /// With --self-join, also skip other parts of the same source feature,
+    /// {n}so multi-point features are not matched with themselves.
+    #[arg(long = "exclude-parts", requires = "self_join")]
+    pub exclude_parts: bool,
+
+    /// Output the full distance matrix between the input points and every
+    /// {n}reference feature rather than the nearest neighbors. `long` writes
+    /// {n}one row per pair in the standard output layout, `wide` writes one
+    /// {n}row per input point with a distance column for each feature index.
+    /// {n}Multi-part features use the distance to their nearest part. Combine
+    /// {n}with `-r` to prune pairs further apart than the radius.
+    #[arg(long, value_enum, conflicts_with_all = ["k", "aggregate", "self_join"])]
+    pub matrix: Option<MatrixFormat>,
+
     /// Set the delimiter for both the input test points and the output
     /// {n}results. Defaults to a comma. Will error of a valid single
     /// {n}character is not provided. This program will always use the
@@ -91,3 +218,12 @@ pub struct Args {
     #[arg(long, short = 'l', default_value = ",")]
     pub delimiter: String,
 }
+
+/// Output layouts for the distance matrix.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum MatrixFormat {
+    /// One row per input point and reference feature pair.
+    Long,
+    /// One row per input point, with one column per reference feature.
+    Wide,
+}
@@ -10,13 +10,18 @@ use geo_munge::qt::ParsedRecord;
 use crate::args::Args;
 use crate::InputSettings;
 
-pub fn build_input_settings(args: &Args) -> Result<(Reader<Stdin>, InputSettings), Error> {
-    // convert the delimiter into something useful for csv
-    let delimiter = args.delimiter.as_bytes();
+/// Convert the delimiter argument into something useful for csv.
+pub fn parse_delimiter(delimiter: &str) -> Result<u8, Error> {
+    let delimiter = delimiter.as_bytes();
     if delimiter.len() != 1 {
         return Err(Error::InvalidDelimiter);
     }
-    let delimiter = delimiter[0];
+
+    Ok(delimiter[0])
+}
+
+pub fn build_input_settings(args: &Args) -> Result<(Reader<Stdin>, InputSettings), Error> {
+    let delimiter = parse_delimiter(&args.delimiter)?;
 
     // Set up the reader based on the passed input
     // Note that the reader must have headers that contain a lat and lng field,
@@ -29,9 +34,12 @@ pub fn build_input_settings(args: &Args) -> Result<(Reader<Stdin>, InputSettings
     // Get the label to look for the id
     let id_label = args.id_label.clone().unwrap_or("id".to_string());
 
+    let sum_label = args.sum_field.as_ref().map(|f| f.to_lowercase());
+
     let mut id_index = None;
     let mut lat_index = None;
     let mut lng_index = None;
+    let mut sum_index = None;
 
     // Then look through the fields to find the id as well as the lng and lat fields
     for (i, cur_header) in reader
@@ -50,6 +58,15 @@ pub fn build_input_settings(args: &Args) -> Result<(Reader<Stdin>, InputSettings
         } else if cur_header == "lng" {
             lng_index = Some(i);
         }
+
+        if sum_label.as_deref() == Some(cur_header) {
+            sum_index = Some(i);
+        }
+    }
+
+    // A requested sum field must exist to be aggregated
+    if let (Some(sum_label), None) = (&sum_label, sum_index) {
+        return Err(Error::MissingInputField(sum_label.to_string()));
     }
 
     // Settings are only valid if we have an index for both the lat and lng
@@ -64,8 +81,17 @@ pub fn build_input_settings(args: &Args) -> Result<(Reader<Stdin>, InputSettings
                 delimiter,
                 // Drop in extra useful information from the args
                 k: args.k,
+                distinct: args.distinct,
                 r: args.r,
                 fields: args.fields.clone(),
+                nested_json: args.nested_json,
+                aggregate: args.aggregate,
+                sum_index,
+                sum_label,
+                include_empty: args.include_empty,
+                matrix: args.matrix,
+                // Populated once the quadtree file is known to be valid
+                matrix_columns: Vec::new(),
                 verbose: args.verbose,
             },
         ))
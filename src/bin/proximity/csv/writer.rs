@@ -1,11 +1,13 @@
-use std::io::Stdout;
+use std::{collections::HashMap, io::Stdout};
 
 use csv::{Writer, WriterBuilder};
 use quadtree::MEAN_EARTH_RADIUS;
 
 use geo_munge::error::Error;
-use geo_munge::qt::{datum::Datum, ParsedRecord};
+use geo_munge::qt::ParsedRecord;
 
+use crate::args::MatrixFormat;
+use crate::run::Match;
 use crate::InputSettings;
 
 // Output the header row with base and additional `--fields`. Will output the
@@ -16,15 +18,44 @@ pub fn make_csv_writer<'a>(settings: &InputSettings) -> Result<Writer<Stdout>, E
         .delimiter(settings.delimiter)
         .from_writer(std::io::stdout());
 
-    // Base fields to include in the output
-    let base_fields = [
-        "input_index",
-        &settings.id_label,
-        "lng",
-        "lat",
-        "distance",
-        "find_index",
-    ];
+    // Base fields to include in the output, aggregated output has one row per reference feature
+    // so does not include any of the input fields
+    let base_fields: Vec<String> = if settings.aggregate {
+        let mut fields = vec![
+            "find_index".to_string(),
+            "count".to_string(),
+            "min_distance".to_string(),
+            "mean_distance".to_string(),
+            "max_distance".to_string(),
+        ];
+        if let Some(label) = &settings.sum_label {
+            fields.push(format!("sum_{label}"));
+            fields.push(format!("mean_{label}"));
+        }
+        fields
+    } else if settings.matrix == Some(MatrixFormat::Wide) {
+        // One distance column per reference feature, so no metadata fields are output
+        let mut fields = vec![
+            "input_index".to_string(),
+            settings.id_label.to_string(),
+            "lng".to_string(),
+            "lat".to_string(),
+        ];
+        fields.extend(settings.matrix_columns.iter().map(|i| i.to_string()));
+        return writer
+            .write_record(&fields)
+            .map(|_| writer)
+            .map_err(|err| Error::CsvWriteError(err));
+    } else {
+        vec![
+            "input_index".to_string(),
+            settings.id_label.to_string(),
+            "lng".to_string(),
+            "lat".to_string(),
+            "distance".to_string(),
+            "find_index".to_string(),
+        ]
+    };
 
     // Set up the slice of additional fields to pull from the metdata
     let tmp_vec = Vec::new();
@@ -33,26 +64,28 @@ pub fn make_csv_writer<'a>(settings: &InputSettings) -> Result<Writer<Stdout>, E
         .as_ref()
         .unwrap_or(&tmp_vec)
         .iter()
-        .map(AsRef::as_ref);
+        .map(String::as_str);
 
     writer
-        .write_record(base_fields.into_iter().chain(field_slice))
+        .write_record(base_fields.iter().map(String::as_str).chain(field_slice))
         .map_err(|err| Error::CsvWriteError(err))?;
 
     Ok(writer)
 }
 
 pub struct WriteData<'a> {
-    pub datum: &'a Datum,
-    pub distance: f64,
+    pub m: &'a Match<'a>,
     pub parsed: &'a ParsedRecord,
     pub settings: &'a InputSettings,
 }
 
 pub fn write_line(w: &mut Writer<Stdout>, data: WriteData) {
     let WriteData {
-        datum,
-        distance,
+        m: Match {
+            datum,
+            distance,
+            meta,
+        },
         parsed,
         settings,
     } = data;
@@ -74,8 +107,41 @@ pub fn write_line(w: &mut Writer<Stdout>, data: WriteData) {
         datum.index().to_string(),
     ];
 
-    let meta_iter = datum.meta_iter(&settings.fields);
-    if w.write_record(base_fields.into_iter().chain(meta_iter))
+    if w.write_record(base_fields.iter().chain(meta)).is_err() {
+        eprintln!(
+            "Failed to write output line for record at index {}.",
+            parsed.index
+        );
+    }
+}
+
+/// Write a row of the wide distance matrix, with a distance column for each of the reference
+/// features in `settings.matrix_columns`. Features not in the results are left blank.
+pub fn write_wide_line(
+    w: &mut Writer<Stdout>,
+    parsed: &ParsedRecord,
+    matches: &[Match],
+    settings: &InputSettings,
+) {
+    let distances: HashMap<usize, f64> = matches
+        .iter()
+        .map(|m| (m.datum.index(), m.distance))
+        .collect();
+
+    let base_fields = [
+        parsed.index.to_string(),
+        parsed.id.clone().unwrap_or_default(),
+        parsed.record.get(settings.lng_index).unwrap().to_string(),
+        parsed.record.get(settings.lat_index).unwrap().to_string(),
+    ];
+    let distance_iter = settings.matrix_columns.iter().map(|i| {
+        distances
+            .get(i)
+            .map(|d| format!("{:.3}", d * MEAN_EARTH_RADIUS))
+            .unwrap_or_default()
+    });
+
+    if w.write_record(base_fields.into_iter().chain(distance_iter))
         .is_err()
     {
         eprintln!(
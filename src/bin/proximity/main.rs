@@ -1,19 +1,28 @@
+mod aggregate;
 mod args;
 mod csv;
 mod multi_thread;
 mod run;
+mod self_join;
 mod single_thread;
 
 use clap::Parser;
 use quadtree::MEAN_EARTH_RADIUS;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
 use std::time::Instant;
 
-use crate::args::Args;
-use crate::csv::reader::build_input_settings;
+use crate::aggregate::Aggregator;
+use crate::args::{Args, MatrixFormat};
+use crate::csv::reader::{build_input_settings, parse_delimiter};
 use crate::csv::writer::make_csv_writer;
-use geo_munge::qt::{make_bbox, QtData, Quadtree};
+use geo_munge::archive::Input;
+use geo_munge::error::Error;
+use geo_munge::qt::{make_bbox, open_source, CsvOptions, QtData, Quadtree, SourceOptions};
 
 use multi_thread::exec_multi_thread;
+use run::{run_output, FindResult};
+use self_join::{build_self_join_settings, exec_self_join};
 use single_thread::exec_single_thread;
 
 // TODO: Refine the API and implementation
@@ -49,8 +58,16 @@ pub struct InputSettings {
     pub id_label: String,
     pub delimiter: u8,
     pub k: Option<usize>,
+    pub distinct: bool,
     pub r: Option<f64>,
     pub fields: Option<Vec<String>>,
+    pub nested_json: bool,
+    pub aggregate: bool,
+    pub sum_index: Option<usize>,
+    pub sum_label: Option<String>,
+    pub include_empty: bool,
+    pub matrix: Option<MatrixFormat>,
+    pub matrix_columns: Vec<usize>,
     pub verbose: bool,
 }
 
@@ -60,23 +77,120 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     args.r = args.r.map(|r| r / MEAN_EARTH_RADIUS);
     let verbose = args.verbose;
     let single_thread = args.single_thread;
-    let print_qt = args.print;
+
+    // Size the pool used for the parallel searches before any work is done
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+
+    let source = build_source_options(&args)?;
+
+    // Unpack compressed files and archives once, as the quadtree file may be read several times,
+    // and keep the input until the end to keep the files
+    let input = Input::open(&args.path, args.member.as_deref())?;
+    let path = input.path();
+
+    // Self joins compare the quadtree file to itself, so there is no input stream
+    if args.self_join {
+        let (settings, csv_writer) = build_self_join_settings(&args, source.clone())?;
+        let qt = build_quadtree(&args, path, source)?;
+
+        let start = Instant::now();
+        exec_self_join(csv_writer, &qt, path, &settings)?;
+        if verbose {
+            eprintln!("Finished in {} ms", start.elapsed().as_millis());
+        }
+
+        return Ok(());
+    }
 
     // Set up csv parsing before building the quadtree so we can abort early if
     // it crashes on setup
-    let (csv_reader, settings) = build_input_settings(&args)?;
+    let (csv_reader, mut settings) = build_input_settings(&args)?;
+
+    // The wide matrix needs a column for every feature up front, which requires an extra pass
+    // through the quadtree file
+    if settings.matrix == Some(MatrixFormat::Wide) {
+        let columns: BTreeSet<_> = open_source(path, &source)?
+            .datums()?
+            .flatten()
+            .map(|datum| datum.index())
+            .collect();
+        settings.matrix_columns = columns.into_iter().collect();
+    }
+
     let csv_writer = make_csv_writer(&settings)?;
 
+    let qt = build_quadtree(&args, path, source.clone())?;
+
+    // After loading the quadtree, iterate through all the incoming test records
+    // In aggregate mode, results are accumulated and written once the input is exhausted,
+    // otherwise each result is written as it arrives
+    let start = Instant::now();
+    if settings.aggregate {
+        let mut aggregator = Aggregator::default();
+        exec(csv_reader, &qt, &settings, single_thread, |output| {
+            aggregator.add(&settings, output)
+        });
+        aggregator.write(csv_writer, path, &source, &settings)?;
+    } else {
+        let mut csv_writer = csv_writer;
+        exec(csv_reader, &qt, &settings, single_thread, |output| {
+            run_output(&mut csv_writer, &settings, output)
+        });
+    }
+    if settings.verbose {
+        eprintln!("Finished in {} ms", start.elapsed().as_millis());
+    }
+
+    // Return Ok from main if everything ran correctly
+    Ok(())
+}
+
+/// Build the options for reading the quadtree file from the arguments.
+fn build_source_options(args: &Args) -> Result<SourceOptions, Error> {
+    let quote = args.ref_quote.as_bytes();
+    if quote.len() != 1 {
+        return Err(Error::InvalidQuote);
+    }
+
+    Ok(SourceOptions {
+        format: args.format,
+        encoding: args.encoding,
+        csv: CsvOptions {
+            delimiter: args
+                .ref_delimiter
+                .as_deref()
+                .map(parse_delimiter)
+                .transpose()?,
+            quote: quote[0],
+            has_headers: !args.ref_no_headers,
+            // Clap ensures exactly two values are passed
+            lng_lat: args.ref_lng_lat.as_ref().map(|v| (v[0], v[1])),
+            geometry: args.ref_geometry.clone(),
+        },
+        osm_tags: args.ref_tag.clone(),
+        // The archive member is unpacked before any source is opened
+        member: None,
+    })
+}
+
+/// Build the quadtree from the unpacked file at `path` with the options provided in the arguments,
+/// logging progress to stderr as requested.
+fn build_quadtree(args: &Args, path: &PathBuf, source: SourceOptions) -> Result<Quadtree, Error> {
     // Set up the options for constructing the quadtree
     let opts = QtData::new(
         args.point,
-        make_bbox(&args.path, args.sphere, &args.bbox)?,
+        make_bbox(path, &source, args.sphere, &args.bbox)?,
         args.depth,
         args.children,
+        source,
     );
 
     // Now build the quadtree
-    if verbose {
+    if args.verbose {
         let qt_type = if opts.is_point_qt { "point" } else { "bounds" };
         eprintln!(
             "Building {} quadtree: depth={}, children={}",
@@ -85,38 +199,44 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     let start = Instant::now();
-    let qt = Quadtree::from_path(args.path, opts)?;
-    if verbose || print_qt {
+    let qt = Quadtree::from_path(path.clone(), opts)?;
+    if args.verbose || args.print {
         eprintln!(
             "Quadtree with {} children built in {} ms",
             qt.size(),
             start.elapsed().as_millis()
         )
     }
-    if print_qt {
+    if args.print {
         eprintln!("{}", qt);
     }
 
-    // After loading the quadtree, iterate through all the incoming test records
-    // Run multi-threaded by default, but use the argument to select single-threaded if required
-    let start = Instant::now();
+    Ok(qt)
+}
+
+/// Run the input stream through the quadtree, passing each result to `emit`.
+///
+/// Run multi-threaded by default, but use the argument to select single-threaded if required.
+fn exec<F>(
+    csv_reader: CsvReader,
+    qt: &Quadtree,
+    settings: &InputSettings,
+    single_thread: bool,
+    emit: F,
+) where
+    F: FnMut(Result<FindResult, Error>) + Send,
+{
     if single_thread {
         if settings.verbose {
             eprintln!("Starting single-threaded execution");
         }
 
-        exec_single_thread(csv_reader, csv_writer, &qt, &settings);
+        exec_single_thread(csv_reader, qt, settings, emit);
     } else {
-        if verbose {
+        if settings.verbose {
             eprintln!("Starting multi-threaded execution");
         }
 
-        exec_multi_thread(csv_reader, csv_writer, &qt, &settings);
-    }
-    if settings.verbose {
-        eprintln!("Finished in {} ms", start.elapsed().as_millis());
+        exec_multi_thread(csv_reader, qt, settings, emit);
     }
-
-    // Return Ok from main if everything ran correctly
-    Ok(())
 }
@@ -1,47 +1,49 @@
+use std::sync::mpsc::sync_channel;
+
+use geo_munge::error::Error;
 use geo_munge::qt::Quadtree;
 use rayon::prelude::*;
 
 use crate::{
-    run::{run_find, run_output},
-    CsvReader, CsvWriter, InputSettings,
+    run::{run_find, FindResult},
+    CsvReader, InputSettings,
 };
 
-pub(super) fn exec_multi_thread(
+/// Number of results per finder thread that can be queued for the writer before the finders block.
+/// This bounds memory use when the writer cannot keep up with the searches.
+const QUEUE_PER_THREAD: usize = 64;
+
+pub(super) fn exec_multi_thread<F>(
     csv_reader: CsvReader,
-    mut csv_writer: CsvWriter,
     qt: &Quadtree,
     settings: &InputSettings,
-) {
-    // We set up a thread scope to run the work in parallel with the output. We need a scipe rather
-    // than just spawning a thread for one of the two tasks so that we don't run into issues with
-    // thread lifetimes and moving values. We don't use Rayon's join either because the channel may
-    // cause join to deadlock. The scope also ensures all the threads are closed before returning.
-    //
-    // TODO: The metadata processing is currently done in printing, which is using a single
-    // thread. Should this be rearchitected to deal with meta inside the parallel iterator? This
-    // may only may only make sense if the meta stuff takes a while (test with flamegraph)
-    rayon::scope(|s| {
-        // Use channel to send the find output to the printing routine
-        let (sender, receiver) = std::sync::mpsc::channel();
+    mut emit: F,
+) where
+    F: FnMut(Result<FindResult, Error>) + Send,
+{
+    // Use a bounded channel to send the find output to the output routine, so the finders apply
+    // backpressure to the reader rather than filling memory when output is slow
+    let (sender, receiver) = sync_channel(rayon::current_num_threads() * QUEUE_PER_THREAD);
 
-        s.spawn(|_| {
-            // Use a bridge to parallelize after reading and a channel to re-serialize after the
-            // computation
-            csv_reader
-                .into_records()
-                .enumerate()
-                .par_bridge()
-                .for_each_with(sender, |s, enum_record| {
-                    let output = run_find(enum_record, &qt, &settings);
-                    s.send(output)
-                        .expect("Receiver closed unexpectedly, aborting");
-                });
+    // We set up a thread scope to run the work in parallel with the output. The scope ensures all
+    // the threads are closed before returning. The output gets its own thread rather than being
+    // a task in the rayon pool: finders block when the channel is full, and a blocked pool could
+    // otherwise starve the output task of a thread and deadlock.
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            receiver.into_iter().for_each(|output| emit(output));
         });
 
-        s.spawn(|_| {
-            receiver
-                .into_iter()
-                .for_each(|output| run_output(&mut csv_writer, settings, output));
-        });
+        // Use a bridge to parallelize after reading and the channel to re-serialize after the
+        // computation. The sender is dropped once the input is exhausted, closing the channel
+        csv_reader
+            .into_records()
+            .enumerate()
+            .par_bridge()
+            .for_each_with(sender, |s, enum_record| {
+                let output = run_find(enum_record, &qt, &settings);
+                s.send(output)
+                    .expect("Receiver closed unexpectedly, aborting");
+            });
     });
 }
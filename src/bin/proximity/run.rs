@@ -1,15 +1,45 @@
 use geo_munge::error::Error;
-use geo_munge::qt::{ParsedRecord, Quadtree, SearchResult};
+use geo_munge::qt::{datum::Datum, ParsedRecord, Quadtree, SearchResult};
 
+use crate::args::MatrixFormat;
 use crate::csv::reader::parse_record;
-use crate::csv::writer::{write_line, WriteData};
+use crate::csv::writer::{write_line, write_wide_line, WriteData};
 use crate::{CsvWriter, InputSettings};
 
 pub(crate) type EnumeratedRecord = (usize, Result<csv::StringRecord, csv::Error>);
 
+/// A single match from a search, with the metadata fields already built. Building the fields
+/// here rather than on output keeps the work on the parallel finders rather than the single
+/// output thread.
+pub(crate) struct Match<'a> {
+    pub datum: &'a Datum,
+    pub distance: f64,
+    pub meta: Vec<String>,
+}
+
+impl<'a> Match<'a> {
+    fn new((datum, distance): SearchResult<'a>, settings: &InputSettings) -> Self {
+        // The wide matrix has no room for metadata, and aggregates only need it once per feature,
+        // so don't build any
+        let meta = if settings.aggregate || settings.matrix == Some(MatrixFormat::Wide) {
+            Vec::new()
+        } else {
+            datum
+                .meta_iter(&settings.fields, settings.nested_json)
+                .collect()
+        };
+
+        Self {
+            datum,
+            distance,
+            meta,
+        }
+    }
+}
+
 pub(crate) enum FindResult<'a> {
-    One(ParsedRecord, SearchResult<'a>),
-    Many(ParsedRecord, Vec<SearchResult<'a>>),
+    One(ParsedRecord, Match<'a>),
+    Many(ParsedRecord, Vec<Match<'a>>),
 }
 
 /// Calculates matches in the quadtree from the provided record.
@@ -22,16 +52,32 @@ pub(crate) fn run_find<'a>(
     settings: &InputSettings,
 ) -> Result<FindResult<'a>, Error> {
     let (csv_idx, record) = enum_record;
-    match (parse_record(csv_idx, record, &settings)?, settings.k) {
-        (parsed, None) | (parsed, Some(1)) => {
-            let results = qt.find(&parsed, settings.r)?;
-            Ok(FindResult::One(parsed, results))
+    let parsed = parse_record(csv_idx, record, &settings)?;
+
+    // The matrix is a knn over every datum in the quadtree, keeping the nearest part of each
+    // feature. Nothing in range is still a valid, if empty, matrix row
+    let results = if settings.matrix.is_some() {
+        match qt.knn_distinct(&parsed, qt.size(), settings.r) {
+            Err(Error::FindError(_, quadtree::Error::NoneInRadius)) => Vec::new(),
+            res => res?,
         }
-        (parsed, Some(k)) => {
-            let results = qt.knn(&parsed, k, settings.r)?;
-            Ok(FindResult::Many(parsed, results))
+    } else {
+        match settings.k {
+            None | Some(1) => {
+                let result = qt.find(&parsed, settings.r)?;
+                return Ok(FindResult::One(parsed, Match::new(result, settings)));
+            }
+            Some(k) if settings.distinct => qt.knn_distinct(&parsed, k, settings.r)?,
+            Some(k) => qt.knn(&parsed, k, settings.r)?,
         }
-    }
+    };
+
+    let matches = results
+        .into_iter()
+        .map(|result| Match::new(result, settings))
+        .collect();
+
+    Ok(FindResult::Many(parsed, matches))
 }
 
 /// Outputs the result of a find/knn.
@@ -45,24 +91,27 @@ pub(crate) fn run_output(
     output: Result<FindResult, Error>,
 ) {
     match output {
-        Ok(FindResult::One(ref parsed, (datum, distance))) => {
+        Ok(FindResult::One(ref parsed, ref m)) => {
             write_line(
                 writer,
                 WriteData {
-                    datum,
-                    distance,
+                    m,
                     parsed,
                     settings,
                 },
             );
         }
-        Ok(FindResult::Many(ref parsed, results)) => {
-            for (datum, distance) in results {
+        Ok(FindResult::Many(ref parsed, ref matches))
+            if settings.matrix == Some(MatrixFormat::Wide) =>
+        {
+            write_wide_line(writer, parsed, matches, settings);
+        }
+        Ok(FindResult::Many(ref parsed, ref matches)) => {
+            for m in matches {
                 write_line(
                     writer,
                     WriteData {
-                        datum,
-                        distance,
+                        m,
                         parsed,
                         settings,
                     },
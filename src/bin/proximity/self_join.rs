@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use csv::WriterBuilder;
+use quadtree::MEAN_EARTH_RADIUS;
+
+use geo_munge::error::Error;
+use geo_munge::qt::{open_source, Quadtree, SourceOptions};
+
+use crate::args::Args;
+use crate::csv::reader::parse_delimiter;
+use crate::CsvWriter;
+
+/// Settings for a self join, where the quadtree file is also the source of the test points.
+pub struct SelfJoinSettings {
+    pub exclude_parts: bool,
+    pub source: SourceOptions,
+    pub r: Option<f64>,
+    pub fields: Option<Vec<String>>,
+    pub nested_json: bool,
+}
+
+/// Build the self join settings from the args and set up the writer with the header row. Each
+/// requested field is output for both the feature and its neighbour, the latter prefixed with
+/// `neighbour_`.
+pub fn build_self_join_settings(
+    args: &Args,
+    source: SourceOptions,
+) -> Result<(SelfJoinSettings, CsvWriter), Error> {
+    let mut writer = WriterBuilder::new()
+        .delimiter(parse_delimiter(&args.delimiter)?)
+        .from_writer(std::io::stdout());
+
+    let fields = args.fields.clone().unwrap_or_default();
+    let header = ["index", "neighbour_index", "distance"]
+        .into_iter()
+        .map(String::from)
+        .chain(fields.iter().cloned())
+        .chain(fields.iter().map(|f| format!("neighbour_{f}")));
+
+    writer
+        .write_record(header)
+        .map_err(|err| Error::CsvWriteError(err))?;
+
+    Ok((
+        SelfJoinSettings {
+            exclude_parts: args.exclude_parts,
+            source,
+            r: args.r,
+            fields: args.fields.clone(),
+            nested_json: args.nested_json,
+        },
+        writer,
+    ))
+}
+
+/// Query every feature in the file at `path` against the quadtree built from the same file.
+///
+/// The file is read a second time to get the queries, so each datum is streamed through rather
+/// than being held twice in memory. Failed searches are output to stderr.
+pub fn exec_self_join(
+    mut writer: CsvWriter,
+    qt: &Quadtree,
+    path: &PathBuf,
+    settings: &SelfJoinSettings,
+) -> Result<(), Error> {
+    for datum in open_source(path, &settings.source)?.datums()? {
+        let result = datum.and_then(|datum| {
+            let (neighbour, distance) =
+                qt.find_neighbour(&datum, settings.exclude_parts, settings.r)?;
+
+            let base_fields = [
+                datum.index().to_string(),
+                neighbour.index().to_string(),
+                // Distance in meters, truncated at mm
+                format!("{:.3}", distance * MEAN_EARTH_RADIUS),
+            ];
+            let row = base_fields
+                .into_iter()
+                .chain(datum.meta_iter(&settings.fields, settings.nested_json))
+                .chain(neighbour.meta_iter(&settings.fields, settings.nested_json));
+
+            if writer.write_record(row).is_err() {
+                eprintln!(
+                    "Failed to write output line for feature at index {}.",
+                    datum.index()
+                );
+            }
+
+            Ok(())
+        });
+
+        if let Err(err) = result {
+            eprintln!("{err}");
+        }
+    }
+
+    writer.flush().map_err(|err| Error::FileIOError(err))
+}
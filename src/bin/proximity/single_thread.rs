@@ -1,18 +1,21 @@
+use geo_munge::error::Error;
 use geo_munge::qt::Quadtree;
 
 use crate::{
-    run::{run_find, run_output},
-    CsvReader, CsvWriter, InputSettings,
+    run::{run_find, FindResult},
+    CsvReader, InputSettings,
 };
 
-pub(super) fn exec_single_thread(
+pub(super) fn exec_single_thread<F>(
     mut csv_reader: CsvReader,
-    mut csv_writer: CsvWriter,
     qt: &Quadtree,
     settings: &InputSettings,
-) {
+    mut emit: F,
+) where
+    F: FnMut(Result<FindResult, Error>),
+{
     csv_reader.records().enumerate().for_each(|enum_record| {
         let output = run_find(enum_record, &qt, &settings);
-        run_output(&mut csv_writer, &settings, output);
+        emit(output);
     });
 }
@@ -0,0 +1,197 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use encoding_rs::Encoding;
+
+use crate::error::Error;
+
+/// Byte order mark at the start of some UTF-8 files.
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Characters for the bytes 0x80 to 0xFF in the DOS code page 437.
+static CP437_HIGH: LazyLock<Vec<char>> = LazyLock::new(|| {
+    "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»░▒▓│┤╡╢╖╕╣║╗╝╜╛┐\
+     └┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}"
+        .chars()
+        .collect()
+});
+
+/// Characters for the bytes 0x80 to 0xFF in the DOS code page 850.
+static CP850_HIGH: LazyLock<Vec<char>> = LazyLock::new(|| {
+    "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜø£Ø×ƒáíóúñÑªº¿®¬½¼¡«»░▒▓│┤ÁÂÀ©╣║╗╝¢¥┐\
+     └┴┬├─┼ãÃ╚╔╩╦╠═╬¤ðÐÊËÈıÍÎÏ┘┌█▄¦Ì▀ÓßÔÒõÕµþÞÚÛÙýÝ¯´\u{ad}±‗¾¶§÷¸°¨·¹³²■\u{a0}"
+        .chars()
+        .collect()
+});
+
+/// Single-byte text encodings supported for reading reference data, plus UTF-8.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, decoded byte for byte. Kept separate from the other ISO-8859 encodings as
+    /// `encoding_rs` treats it as Windows-1252.
+    Latin1,
+    /// The other ISO-8859 and the Windows-125x code pages.
+    SingleByte(&'static Encoding),
+    Cp437,
+    Cp850,
+}
+
+impl TextEncoding {
+    /// Decode `bytes` to a string, replacing any invalid sequences.
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        match self {
+            Self::Utf8 => String::from_utf8_lossy(bytes),
+            Self::Latin1 => Cow::Owned(bytes.iter().map(|b| *b as char).collect()),
+            Self::SingleByte(encoding) => encoding.decode_without_bom_handling(bytes).0,
+            Self::Cp437 => decode_high(bytes, &CP437_HIGH),
+            Self::Cp850 => decode_high(bytes, &CP850_HIGH),
+        }
+    }
+
+    /// The encoding for a code page number, as used in `.cpg` files.
+    pub fn from_code_page(code_page: u16) -> Option<Self> {
+        match code_page {
+            437 => Some(Self::Cp437),
+            850 => Some(Self::Cp850),
+            1250..=1258 => {
+                Encoding::for_label(format!("windows-{code_page}").as_bytes()).map(Self::SingleByte)
+            }
+            28591 => Some(Self::Latin1),
+            28592..=28606 => Self::iso_8859(code_page - 28590),
+            65001 => Some(Self::Utf8),
+            _ => None,
+        }
+    }
+
+    /// The ISO-8859 encoding with the part number `part`.
+    fn iso_8859(part: u16) -> Option<Self> {
+        match part {
+            1 => Some(Self::Latin1),
+            _ => Encoding::for_label(format!("iso-8859-{part}").as_bytes()).map(Self::SingleByte),
+        }
+    }
+
+    /// Wrap `reader` so that it produces UTF-8 from this encoding, stripping any UTF-8 byte order
+    /// mark from the start of the stream.
+    pub fn reader<'a, R: BufRead + 'a>(&self, mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+        match self {
+            Self::Utf8 => {
+                if reader.fill_buf()?.starts_with(UTF8_BOM) {
+                    reader.consume(UTF8_BOM.len());
+                }
+                Ok(Box::new(reader))
+            }
+            _ => Ok(Box::new(DecodeReader {
+                inner: reader,
+                encoding: *self,
+                decoded: Vec::new(),
+                pos: 0,
+            })),
+        }
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Utf8 => write!(f, "UTF-8"),
+            Self::Latin1 => write!(f, "ISO-8859-1"),
+            Self::SingleByte(encoding) => write!(f, "{}", encoding.name()),
+            Self::Cp437 => write!(f, "IBM437"),
+            Self::Cp850 => write!(f, "IBM850"),
+        }
+    }
+}
+
+/// Parse an encoding name, as passed to `--encoding` on the command line or found in a `.cpg`
+/// file. Accepts names such as `utf8`, `latin1`, `iso-8859-2`, `windows-1252`, `cp850`, and bare
+/// code page numbers.
+impl FromStr for TextEncoding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase().replace([' ', '-', '_'], "");
+        let unknown = || Error::UnknownEncoding(s.trim().to_string());
+
+        match name.as_str() {
+            "utf8" => Ok(Self::Utf8),
+            "latin1" => Ok(Self::Latin1),
+            _ => {
+                let number = |prefixes: &[&str]| {
+                    prefixes
+                        .iter()
+                        .find_map(|p| name.strip_prefix(p))
+                        .and_then(|n| n.parse::<u16>().ok())
+                };
+
+                // Some .cpg files contain the ISO-8859 part in the form 88591, which must be
+                // checked before treating the name as a bare code page number
+                if let Some(part) = number(&["iso8859", "8859"]) {
+                    Self::iso_8859(part)
+                } else if let Some(code_page) = number(&["windows", "cp", "ibm", "dos", ""]) {
+                    Self::from_code_page(code_page)
+                } else {
+                    None
+                }
+                .ok_or_else(unknown)
+            }
+        }
+    }
+}
+
+/// Decode a DOS code page, where the bytes below 0x80 are ASCII.
+fn decode_high<'a>(bytes: &'a [u8], high: &[char]) -> Cow<'a, str> {
+    if bytes.is_ascii() {
+        return Cow::Borrowed(std::str::from_utf8(bytes).unwrap_or_default());
+    }
+
+    Cow::Owned(
+        bytes
+            .iter()
+            .map(|b| match b {
+                0x80.. => high[(b - 0x80) as usize],
+                _ => *b as char,
+            })
+            .collect(),
+    )
+}
+
+/// Reader adapter that transcodes a single-byte encoding to UTF-8 as it is read.
+struct DecodeReader<R> {
+    inner: R,
+    encoding: TextEncoding,
+    decoded: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Refill the decoded buffer from the next chunk of the inner reader once it is drained.
+        // All the encodings other than UTF-8 are single-byte, so chunks can be decoded alone.
+        if self.pos == self.decoded.len() {
+            let raw = self.inner.fill_buf()?;
+            if raw.is_empty() {
+                return Ok(0);
+            }
+
+            self.decoded.clear();
+            self.pos = 0;
+            self.decoded
+                .extend_from_slice(self.encoding.decode(raw).as_bytes());
+
+            let len = raw.len();
+            self.inner.consume(len);
+        }
+
+        let len = buf.len().min(self.decoded.len() - self.pos);
+        buf[..len].copy_from_slice(&self.decoded[self.pos..self.pos + len]);
+        self.pos += len;
+
+        Ok(len)
+    }
+}
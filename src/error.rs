@@ -1,4 +1,4 @@
-use std::{fmt, path::PathBuf};
+use std::{fmt, path::PathBuf, str::FromStr};
 
 /// Custom error enum for emitting on failure.
 ///
@@ -9,9 +9,20 @@ pub enum Error {
     CannotReadFile(PathBuf),
     CannotParseFile(PathBuf),
     CannotParseFileExtension(PathBuf),
+    CannotReadArchive(PathBuf),
+    CannotChooseArchiveMember(PathBuf),
+    MissingArchiveMember(String),
     UnsupportedFileType,
+    CannotDetectFormat(PathBuf),
+    UnknownFormat(String),
+    UnknownEncoding(String),
+    UnsupportedCrs(String),
+    InvalidWkt(String),
+    InvalidWkb(String),
+    InvalidTagFilter(String),
     UnexpectedEndOfInput,
     InvalidDelimiter,
+    InvalidQuote,
     InvalidBoundingBox,
     MissingBoundingBox,
     TypeDoesNotContainMetadata,
@@ -20,10 +31,13 @@ pub enum Error {
     ShapefileParseError(shapefile::Error),
     ShapeFileWriteError(shapefile::Error),
     MissingLatLngField,
+    MissingInputField(String),
+    MissingGeometryField(String),
     CannotParseRecord(usize, ParseType),
     UnsupportedGeometry(UnsupportedGeoType),
     InsertFailed(usize, quadtree::Error),
     InsertFailedRequiresPoint(usize),
+    QueryRequiresPoint(usize),
     FindError(usize, quadtree::Error),
     FailedToDeserialize(PathBuf, serde_json::Error),
     ExecPipelineFailed(std::io::Error),
@@ -36,6 +50,7 @@ pub enum ParseType {
     GeoJson,
     Shapefile,
     Csv,
+    Wkt,
     MissingGeometry,
 }
 
@@ -79,9 +94,24 @@ impl fmt::Display for Error {
                 "Cannot parse file extension for file {}",
                 path.to_string_lossy()
             ),
+            Self::CannotReadArchive(path) => write!(f, "Cannot unpack the compressed file or archive {}", path.to_string_lossy()),
+            Self::CannotChooseArchiveMember(path) => write!(f, "Cannot choose which member of the archive {} to read, name one with --member", path.to_string_lossy()),
+            Self::MissingArchiveMember(member) => write!(f, "The archive has no member {}", member),
             Self::UnsupportedFileType => write!(f, "Unsupported file type"),
+            Self::CannotDetectFormat(path) => write!(
+                f,
+                "Cannot detect the format of file {}, pass --format to set it explicitly",
+                path.to_string_lossy()
+            ),
+            Self::UnknownFormat(format) => write!(f, "Unknown format {}", format),
+            Self::UnknownEncoding(encoding) => write!(f, "Unknown text encoding {}", encoding),
+            Self::UnsupportedCrs(crs) => write!(f, "Unsupported coordinate reference system {} in the .prj file, only lng/lat degrees from Greenwich and Mercator, transverse Mercator and Lambert conformal conic projections on the WGS84, NAD83 or ETRS89 datums can be read", crs),
+            Self::InvalidWkt(reason) => write!(f, "Invalid WKT: {}", reason),
+            Self::InvalidWkb(reason) => write!(f, "Invalid WKB: {}", reason),
+            Self::InvalidTagFilter(filter) => write!(f, "Invalid tag filter {}, expected key or key=value", filter),
             Self::UnexpectedEndOfInput => write!(f, "Unexpected end of input"),
             Self::InvalidDelimiter => write!(f, "Invalid delimiter provided"),
+            Self::InvalidQuote => write!(f, "Invalid quote character provided"),
             Self::InvalidBoundingBox => write!(f, "The bounding box provided in the source file or on the command line is not valid"),
             Self::MissingBoundingBox => write!(f, "A bounding box was expected but not found"),
             Self::TypeDoesNotContainMetadata => write!(f, "The provided type was expected to contain metadata, but it does not"),
@@ -90,6 +120,8 @@ impl fmt::Display for Error {
             Self::ShapefileParseError(err) => write!(f, "Error parsing shapefile input: {}", err),
             Self::ShapeFileWriteError(err) => write!(f, "Error writing to shapefile: {}", err),
             Self::MissingLatLngField => write!(f, "The test points are missing a lng or lat field"),
+            Self::MissingInputField(field) => write!(f, "The test points are missing the field {}", field),
+            Self::MissingGeometryField(field) => write!(f, "The reference data is missing the geometry field {}", field),
             Self::CannotParseRecord(i, parse_type) => {
                 let type_str = match parse_type {
                     ParseType::Lng => "Lng parsing failed",
@@ -97,6 +129,7 @@ impl fmt::Display for Error {
                     ParseType::GeoJson => "GeoJson feature parsing failed",
                     ParseType::Shapefile => "Shapefile parsing failed",
                     ParseType::Csv => "CSV parsing failed",
+                    ParseType::Wkt => "WKT or WKB parsing failed",
                     ParseType::MissingGeometry => "Missing geometry",
                 };
                 write!(f, "Failed to parse record at index {}: {}", i, type_str)
@@ -104,6 +137,7 @@ impl fmt::Display for Error {
             Self::UnsupportedGeometry(geo_type) => write!(f, "Unsupported geometry type encountered: {}", geo_type),
             Self::InsertFailed(i, err) => write!(f, "Insert failed for geometry at index {}: {}", i, display_qt_err(err)),
             Self::InsertFailedRequiresPoint(i) => write!(f, "Cannot insert non-point geometry into point quadtree at index {}, to enable bounds mode, create the quadtree without the -p flag", i),
+            Self::QueryRequiresPoint(i) => write!(f, "Cannot search from non-point geometry at index {}, only point geometries can be used as the query", i),
             Self::FindError(i, err) => write!(f, "Match for input record at index {}, failed: {}", i, display_qt_err(err)),
             Self::FailedToDeserialize(path, err) => write!(f, "Deserialization failed for file {}, error provided: {}", path.to_string_lossy(), err),
             Self::ExecPipelineFailed(err) => write!(f, "Run execution failure: {}", err) ,
@@ -112,6 +146,12 @@ impl fmt::Display for Error {
     }
 }
 
+/// Parse a command line argument with its [`FromStr`] implementation, reporting failures with the
+/// library's error message. Used as a clap `value_parser` for the library types.
+pub fn parse_arg<T: FromStr<Err = Error>>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|err: Error| err.to_string())
+}
+
 fn display_qt_err(err: &quadtree::Error) -> &'static str {
     match err {
         quadtree::Error::Empty => "QuadTree is empty",
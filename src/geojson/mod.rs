@@ -1,3 +1,6 @@
+mod stream;
+mod topojson;
+
 use std::{fs::read_to_string, iter::once, path::PathBuf};
 
 use geojson::GeoJson;
@@ -5,6 +8,11 @@ use quadtree::{Geometry, ToRadians};
 
 use crate::error::Error;
 
+pub use self::stream::{stream_geojson, GeoJsonStream};
+pub use self::topojson::{read_topojson, Topology, TopologyObject};
+
+/// Read and parse the whole of a GeoJSON file. Prefer [`stream_geojson`] for anything that only
+/// needs the Features one at a time.
 pub fn read_geojson(path: &PathBuf) -> Result<GeoJson, Error> {
     read_to_string(&path)
         .map_err(|_| Error::CannotReadFile(path.clone()))?
@@ -13,7 +21,8 @@ pub fn read_geojson(path: &PathBuf) -> Result<GeoJson, Error> {
 }
 
 /// Convert a GeoJson geometry into the appropriate quadtree-enabled type. Outputs an iterator as
-/// it flattens multi-geometries into their single geometry counterparts.
+/// it flattens multi-geometries and GeometryCollections, at any depth, into their single geometry
+/// counterparts.
 pub fn convert_geom(
     input: &geojson::Geometry,
 ) -> Box<dyn Iterator<Item = Result<Geometry<f64>, geojson::Error>>> {
@@ -55,11 +64,14 @@ pub fn convert_geom(
             })),
             Err(err) => Box::new(once(Err(err))),
         },
-        geojson::Value::GeometryCollection(_) => {
-            Box::new(once(Err(geojson::Error::ExpectedType {
-                expected: "not GeometryCollection".to_string(),
-                actual: "GeometryCollection".to_string(),
-            })))
-        }
+        // Collections are flattened recursively. The members are converted up front so the output
+        // does not borrow the input
+        geojson::Value::GeometryCollection(gs) => Box::new(
+            gs.iter()
+                .map(convert_geom)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .flatten(),
+        ),
     }
 }
@@ -0,0 +1,632 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+use geojson::{Feature, FeatureCollection, GeoJson, JsonObject};
+
+use crate::error::{Error, ParseType};
+
+/// Record separator that starts each record in a GeoJSON Text Sequence (RFC 8142).
+const RECORD_SEPARATOR: u8 = 0x1e;
+
+/// Byte order mark at the start of some UTF-8 files.
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Open a [`GeoJsonStream`] over the file at `path`.
+pub fn stream_geojson(path: &PathBuf) -> Result<GeoJsonStream<BufReader<File>>, Error> {
+    let file = File::open(path).map_err(|_| Error::CannotReadFile(path.clone()))?;
+
+    GeoJsonStream::new(BufReader::new(file), path.clone())
+}
+
+/// How the records are laid out in the stream.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Layout {
+    /// Elements of the `features` array of a top-level FeatureCollection.
+    FeatureCollection,
+    /// A single Feature or Geometry, or a sequence of them either separated by the RFC 8142 record
+    /// separator or by newlines.
+    Sequence,
+}
+
+/// Streaming reader over the records in a GeoJSON file, holding only one record in memory at a
+/// time. Reads FeatureCollections one Feature at a time, single Features and Geometries, GeoJSON
+/// Text Sequences (RFC 8142), and newline-delimited GeoJSON.
+///
+/// Only Features and Geometries are emitted. Any FeatureCollection found within a sequence is
+/// expanded into its Features, including a FeatureCollection at the start of a sequence, which is
+/// read one Feature at a time before the stream continues with the records that follow it.
+pub struct GeoJsonStream<R> {
+    reader: R,
+    path: PathBuf,
+    layout: Layout,
+    /// The top-level FeatureCollection without its Features, when the layout is a collection. Holds
+    /// the members read so far.
+    collection: Option<FeatureCollection>,
+    /// Records that have been parsed but not yet emitted.
+    pending: VecDeque<GeoJson>,
+    index: usize,
+    done: bool,
+    buf: Vec<u8>,
+}
+
+impl<R: BufRead> GeoJsonStream<R> {
+    /// Create a stream from `reader`, reading up to the first record to determine the layout.
+    /// `path` is only used for error reporting.
+    pub fn new(reader: R, path: PathBuf) -> Result<Self, Error> {
+        let mut stream = Self {
+            reader,
+            path,
+            layout: Layout::Sequence,
+            collection: None,
+            pending: VecDeque::new(),
+            index: 0,
+            done: false,
+            buf: Vec::new(),
+        };
+        stream.read_start()?;
+
+        Ok(stream)
+    }
+
+    /// The top-level FeatureCollection with an empty `features` array, if the file is a
+    /// FeatureCollection. Members that come after the `features` array are only added once the
+    /// stream has been read to the end, see [`Self::skip_features`]. A FeatureCollection followed
+    /// by other records is the start of a sequence, so is not a top-level FeatureCollection once
+    /// they are found.
+    pub fn collection(&self) -> Option<&FeatureCollection> {
+        self.collection.as_ref()
+    }
+
+    /// The record in the file if it holds a single Feature or Geometry, rather than a
+    /// FeatureCollection or a sequence. Reads the first two records, so is only useful before
+    /// iterating the stream.
+    pub fn single(&mut self) -> Result<Option<GeoJson>, Error> {
+        if self.layout != Layout::Sequence || self.index > 0 {
+            return Ok(None);
+        }
+
+        match (self.next().transpose()?, self.next()) {
+            (Some(record), None) => Ok(Some(record)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Skip past the remaining Features of a top-level FeatureCollection without parsing them, to
+    /// read the members that come after the `features` array into [`Self::collection`]. Does
+    /// nothing for sequences, and stops at the end of the collection if it is followed by other
+    /// records.
+    pub fn skip_features(&mut self) -> Result<(), Error> {
+        if self.layout != Layout::FeatureCollection {
+            return Ok(());
+        }
+
+        self.pending.clear();
+        while !self.done {
+            let mut raw = std::mem::take(&mut self.buf);
+            raw.clear();
+            let more = self.next_raw_feature(&mut raw);
+            self.buf = raw;
+            match more {
+                // Skipped Features still count towards the index of later records
+                Ok(true) => self.index += 1,
+                Ok(false) if self.layout == Layout::Sequence => break,
+                Ok(false) => self.done = true,
+                Err(err) => {
+                    self.done = true;
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the start of the file. A top-level object is scanned member by member until the
+    /// `features` array is found, at which point the stream is positioned on its first element.
+    /// Objects without one are parsed whole as the first record of a sequence.
+    fn read_start(&mut self) -> Result<(), Error> {
+        let has_bom = self
+            .reader
+            .fill_buf()
+            .map_err(|_| Error::CannotReadFile(self.path.clone()))?
+            .starts_with(UTF8_BOM);
+        if has_bom {
+            self.reader.consume(UTF8_BOM.len());
+        }
+
+        self.skip_whitespace()?;
+        if self.peek()? != Some(b'{') {
+            // Either a text sequence or invalid, which is reported on the first record
+            return Ok(());
+        }
+
+        let mut raw = Vec::new();
+        self.push_byte(&mut raw)?;
+        loop {
+            self.skip_whitespace()?;
+            match self.peek()? {
+                Some(b'}') => {
+                    self.push_byte(&mut raw)?;
+                    break;
+                }
+                Some(b',') => self.push_byte(&mut raw)?,
+                Some(b'"') => {
+                    let start = raw.len();
+                    self.read_value(&mut raw)?;
+                    let is_features = &raw[start..] == b"\"features\"";
+                    self.read_colon(&mut raw)?;
+
+                    if is_features && self.peek()? == Some(b'[') {
+                        self.next_byte()?;
+                        // Build the header from the members so far, which may not include the type
+                        raw.truncate(start);
+                        if raw.last() == Some(&b',') {
+                            raw.pop();
+                        }
+                        raw.push(b'}');
+                        self.layout = Layout::FeatureCollection;
+                        return self.add_members(&raw);
+                    }
+
+                    self.read_value(&mut raw)?;
+                }
+                _ => return Err(self.parse_error()),
+            }
+        }
+
+        let record = serde_json::from_slice(&raw).map_err(|_| self.parse_error())?;
+        self.push_record(record);
+
+        Ok(())
+    }
+
+    /// Read the next record from the `features` array, or `None` at the end of the array.
+    fn next_feature(&mut self) -> Result<Option<GeoJson>, Error> {
+        let mut raw = std::mem::take(&mut self.buf);
+        raw.clear();
+        let more = self.next_raw_feature(&mut raw);
+        let feature = more.map(|more| more.then(|| serde_json::from_slice::<Feature>(&raw)));
+        self.buf = raw;
+
+        match feature? {
+            Some(feature) => feature
+                .map(|f| Some(GeoJson::Feature(f)))
+                .map_err(|_| Error::CannotParseRecord(self.index, ParseType::GeoJson)),
+            // The collection was the first record of a sequence, so continue with the rest
+            None if self.layout == Layout::Sequence => self.next_sequence(),
+            None => Ok(None),
+        }
+    }
+
+    /// Copy the next element of the `features` array into `raw` without parsing it. Returns false
+    /// at the end of the array, after reading the members of the collection that follow it. If
+    /// there are more records after the collection, the stream continues as a sequence.
+    fn next_raw_feature(&mut self, raw: &mut Vec<u8>) -> Result<bool, Error> {
+        self.skip_whitespace()?;
+        if self.peek()? == Some(b',') {
+            self.next_byte()?;
+            self.skip_whitespace()?;
+        }
+        match self.peek()? {
+            Some(b']') => {
+                self.next_byte()?;
+                self.read_end()?;
+                self.skip_whitespace()?;
+                if self.peek()?.is_some() {
+                    self.layout = Layout::Sequence;
+                    self.collection = None;
+                }
+                return Ok(false);
+            }
+            None => return Err(self.parse_error()),
+            _ => {}
+        }
+
+        self.read_value(raw)?;
+
+        Ok(true)
+    }
+
+    /// Read the members of the top-level FeatureCollection that follow the `features` array,
+    /// through to the close of the collection.
+    fn read_end(&mut self) -> Result<(), Error> {
+        let mut raw = vec![b'{'];
+        loop {
+            self.skip_whitespace()?;
+            match self.peek()? {
+                Some(b'}') => {
+                    self.next_byte()?;
+                    break;
+                }
+                Some(b',') => {
+                    self.next_byte()?;
+                }
+                Some(b'"') => {
+                    if raw.len() > 1 {
+                        raw.push(b',');
+                    }
+                    self.read_value(&mut raw)?;
+                    self.read_colon(&mut raw)?;
+                    self.read_value(&mut raw)?;
+                }
+                _ => return Err(self.parse_error()),
+            }
+        }
+        raw.push(b'}');
+
+        self.add_members(&raw)
+    }
+
+    /// Add the members in the JSON object `raw` to the top-level FeatureCollection. Any type other
+    /// than FeatureCollection is an error, and members that are not part of the specification are
+    /// kept as foreign members.
+    fn add_members(&mut self, raw: &[u8]) -> Result<(), Error> {
+        let members: JsonObject = serde_json::from_slice(raw).map_err(|_| self.parse_error())?;
+        let collection = self.collection.get_or_insert_with(|| FeatureCollection {
+            bbox: None,
+            features: Vec::new(),
+            foreign_members: None,
+        });
+
+        for (key, value) in members {
+            match key.as_str() {
+                "type" if value == "FeatureCollection" => {}
+                "bbox" => match serde_json::from_value(value) {
+                    Ok(bbox) => collection.bbox = Some(bbox),
+                    Err(_) => return Err(Error::CannotParseFile(self.path.clone())),
+                },
+                "type" => return Err(Error::CannotParseFile(self.path.clone())),
+                _ => {
+                    collection
+                        .foreign_members
+                        .get_or_insert_with(JsonObject::new)
+                        .insert(key, value);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the next record from a sequence, or `None` at the end of the file. Loops to skip past
+    /// any empty FeatureCollections.
+    fn next_sequence(&mut self) -> Result<Option<GeoJson>, Error> {
+        while self.pending.is_empty() {
+            self.skip_whitespace()?;
+            if self.peek()?.is_none() {
+                return Ok(None);
+            }
+
+            let mut raw = std::mem::take(&mut self.buf);
+            raw.clear();
+            self.read_value(&mut raw)?;
+            let record = serde_json::from_slice::<GeoJson>(&raw);
+            self.buf = raw;
+
+            let record =
+                record.map_err(|_| Error::CannotParseRecord(self.index, ParseType::GeoJson))?;
+            self.push_record(record);
+        }
+
+        Ok(self.pending.pop_front())
+    }
+
+    /// Queue a record to be emitted, expanding FeatureCollections into their Features.
+    fn push_record(&mut self, record: GeoJson) {
+        match record {
+            GeoJson::FeatureCollection(fc) => self
+                .pending
+                .extend(fc.features.into_iter().map(GeoJson::Feature)),
+            record => self.pending.push_back(record),
+        }
+    }
+
+    /// Copy the next complete JSON value into `out` without interpreting it. Strings, objects and
+    /// arrays are read through to their close, and other values up to the next delimiter. It is an
+    /// error for there to be no value, as the stream would otherwise never advance.
+    fn read_value(&mut self, out: &mut Vec<u8>) -> Result<(), Error> {
+        let start = out.len();
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        loop {
+            let b = match self.peek()? {
+                Some(b) => b,
+                None if depth == 0 && !in_string && out.len() > start => return Ok(()),
+                None => return Err(self.parse_error()),
+            };
+
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if b == b'\\' {
+                    escaped = true;
+                } else if b == b'"' {
+                    in_string = false;
+                }
+            } else {
+                match b {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' | b',' | b' ' | b'\t' | b'\n' | b'\r' | RECORD_SEPARATOR
+                        if depth == 0 =>
+                    {
+                        return if out.len() > start {
+                            Ok(())
+                        } else {
+                            Err(self.parse_error())
+                        };
+                    }
+                    b'}' | b']' => depth -= 1,
+                    _ => {}
+                }
+            }
+
+            self.push_byte(out)?;
+
+            if depth == 0 && !in_string && matches!(b, b'"' | b'}' | b']') {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Read the colon between the name and value of a member into `out`, skipping the whitespace
+    /// around it.
+    fn read_colon(&mut self, out: &mut Vec<u8>) -> Result<(), Error> {
+        self.skip_whitespace()?;
+        if self.peek()? != Some(b':') {
+            return Err(self.parse_error());
+        }
+        self.push_byte(out)?;
+        self.skip_whitespace()
+    }
+
+    /// Skip whitespace, including the record separators of a text sequence.
+    fn skip_whitespace(&mut self) -> Result<(), Error> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r' | RECORD_SEPARATOR) = self.peek()? {
+            self.next_byte()?;
+        }
+
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, Error> {
+        self.reader
+            .fill_buf()
+            .map(|buf| buf.first().copied())
+            .map_err(|_| Error::CannotReadFile(self.path.clone()))
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>, Error> {
+        let b = self.peek()?;
+        if b.is_some() {
+            self.reader.consume(1);
+        }
+
+        Ok(b)
+    }
+
+    fn push_byte(&mut self, out: &mut Vec<u8>) -> Result<(), Error> {
+        if let Some(b) = self.next_byte()? {
+            out.push(b);
+        }
+
+        Ok(())
+    }
+
+    fn parse_error(&self) -> Error {
+        Error::CannotParseFile(self.path.clone())
+    }
+}
+
+impl<R: BufRead> Iterator for GeoJsonStream<R> {
+    type Item = Result<GeoJson, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(record) = self.pending.pop_front() {
+            self.index += 1;
+            return Some(Ok(record));
+        }
+        if self.done {
+            return None;
+        }
+
+        let next = match self.layout {
+            Layout::FeatureCollection => self.next_feature(),
+            Layout::Sequence => self.next_sequence(),
+        };
+
+        match next {
+            Ok(Some(record)) => {
+                self.index += 1;
+                Some(Ok(record))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            // A record that fails to parse has still been read through, so the stream can
+            // continue, but any other error leaves the stream in an unknown position
+            Err(err @ Error::CannotParseRecord(_, _)) => {
+                self.index += 1;
+                Some(Err(err))
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use geojson::feature::Id;
+
+    use super::*;
+
+    fn stream(text: &str) -> GeoJsonStream<Cursor<&[u8]>> {
+        GeoJsonStream::new(Cursor::new(text.as_bytes()), PathBuf::from("test.geojson")).unwrap()
+    }
+
+    /// The ids of the Features in the stream, with `None` for Geometries and records that fail.
+    fn ids(stream: GeoJsonStream<Cursor<&[u8]>>) -> Vec<Option<String>> {
+        stream
+            .map(|record| match record {
+                Ok(GeoJson::Feature(Feature {
+                    id: Some(Id::String(id)),
+                    ..
+                })) => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn feature(id: &str) -> String {
+        format!(
+            r#"{{"type":"Feature","id":"{id}","properties":{{}},"geometry":{{"type":"Point","coordinates":[1,2]}}}}"#
+        )
+    }
+
+    #[test]
+    fn feature_collection() {
+        let text = format!(
+            r#"{{"type":"FeatureCollection","bbox":[0,1,2,3],"features":[{}, {}]}}"#,
+            feature("a"),
+            feature("b")
+        );
+        let stream = stream(&text);
+
+        let fc = stream.collection().unwrap();
+        assert_eq!(fc.bbox, Some(vec![0.0, 1.0, 2.0, 3.0]));
+        assert_eq!(ids(stream), [Some("a".into()), Some("b".into())]);
+    }
+
+    #[test]
+    fn members_after_features() {
+        let text = format!(
+            r#"{{"features":[{}],"type":"FeatureCollection","bbox":[0,1,2,3],"name":"x"}}"#,
+            feature("a")
+        );
+        let mut stream = stream(&text);
+        assert_eq!(stream.collection().unwrap().bbox, None);
+
+        stream.skip_features().unwrap();
+        let fc = stream.collection().unwrap();
+        assert_eq!(fc.bbox, Some(vec![0.0, 1.0, 2.0, 3.0]));
+        assert_eq!(fc.foreign_members.as_ref().unwrap()["name"], "x");
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn other_type_is_an_error() {
+        let text = r#"{"type":"Topology","features":[]}"#;
+        let res = GeoJsonStream::new(Cursor::new(text.as_bytes()), PathBuf::new());
+
+        assert!(matches!(res, Err(Error::CannotParseFile(_))));
+    }
+
+    #[test]
+    fn text_sequence() {
+        let text = format!("\x1e{}\n\x1e{}\n", feature("a"), feature("b"));
+        let stream = stream(&text);
+
+        assert!(stream.collection().is_none());
+        assert_eq!(ids(stream), [Some("a".into()), Some("b".into())]);
+    }
+
+    #[test]
+    fn newline_delimited() {
+        let text = format!(
+            "{}\n{{\"type\":\"Point\",\"coordinates\":[1,2]}}\n{}\n",
+            feature("a"),
+            feature("b")
+        );
+
+        assert_eq!(
+            ids(stream(&text)),
+            [Some("a".into()), None, Some("b".into())]
+        );
+    }
+
+    #[test]
+    fn single_record() {
+        let text = feature("a");
+        let record = stream(&text).single().unwrap();
+        assert!(matches!(record, Some(GeoJson::Feature(_))));
+
+        let text = format!("{}\n{}\n", feature("a"), feature("b"));
+        assert!(stream(&text).single().unwrap().is_none());
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let text = format!(
+            "\u{feff}{{\"type\":\"FeatureCollection\",\"features\":[{}]}}",
+            feature("a")
+        );
+        let stream = stream(&text);
+
+        assert!(stream.collection().is_some());
+        assert_eq!(ids(stream), [Some("a".into())]);
+    }
+
+    #[test]
+    fn sequence_starting_with_collection() {
+        let text = format!(
+            "{{\"type\":\"FeatureCollection\",\"features\":[{}, {}]}}\n{}\n",
+            feature("a"),
+            feature("b"),
+            feature("c")
+        );
+        let mut records = stream(&text);
+        assert!(records.collection().is_some());
+
+        assert!(matches!(records.next(), Some(Ok(GeoJson::Feature(_)))));
+        assert_eq!(
+            ids(records),
+            [Some("b".into()), Some("c".into())],
+            "records after the collection are read"
+        );
+
+        // Skipping the Features finds the following records, so it is not a single collection
+        let mut skipped = stream(&text);
+        skipped.skip_features().unwrap();
+        assert!(skipped.collection().is_none());
+        assert!(skipped.single().unwrap().is_none());
+    }
+
+    #[test]
+    fn collections_within_sequence() {
+        let text = format!(
+            "{}\n{{\"type\":\"FeatureCollection\",\"features\":[{}, {}]}}\n",
+            feature("a"),
+            feature("b"),
+            feature("c")
+        );
+
+        assert_eq!(
+            ids(stream(&text)),
+            [Some("a".into()), Some("b".into()), Some("c".into())]
+        );
+    }
+
+    #[test]
+    fn invalid_record_in_sequence() {
+        let text = format!(
+            "{}\n{{\"type\":\"Unknown\"}}\n{}\n",
+            feature("a"),
+            feature("b")
+        );
+        let records: Vec<_> = stream(&text).collect();
+
+        assert_eq!(records.len(), 3);
+        assert!(matches!(records[1], Err(Error::CannotParseRecord(1, _))));
+        assert!(matches!(records[2], Ok(GeoJson::Feature(_))));
+    }
+}
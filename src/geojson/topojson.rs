@@ -0,0 +1,393 @@
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+use geojson::{feature::Id, Feature, JsonObject, JsonValue, Position};
+
+use crate::error::Error;
+
+/// A TopoJSON topology decoded into GeoJSON Features, one list for each named object.
+pub struct Topology {
+    pub bbox: Option<Vec<f64>>,
+    pub objects: Vec<TopologyObject>,
+}
+
+/// A named object in a topology. Objects that are a GeometryCollection have a Feature for each of
+/// their geometries, any other object is a single Feature.
+pub struct TopologyObject {
+    pub name: String,
+    pub features: Vec<Feature>,
+}
+
+impl Topology {
+    /// Iterate the Features of all the objects in order.
+    pub fn features(self) -> impl Iterator<Item = Feature> {
+        self.objects.into_iter().flat_map(|o| o.features)
+    }
+}
+
+/// Transform from quantized positions back to lng/lat.
+#[derive(Clone, Copy)]
+struct Transform {
+    scale: [f64; 2],
+    translate: [f64; 2],
+}
+
+impl Transform {
+    fn apply(&self, x: f64, y: f64) -> Position {
+        vec![
+            x * self.scale[0] + self.translate[0],
+            y * self.scale[1] + self.translate[1],
+        ]
+    }
+}
+
+/// Read and decode a TopoJSON file. Arcs are decoded once, including delta-encoded quantized arcs,
+/// then stitched into the LineStrings and Polygons of each geometry.
+pub fn read_topojson(path: &PathBuf) -> Result<Topology, Error> {
+    let parse_error = || Error::CannotParseFile(path.clone());
+
+    let json: JsonValue = read_to_string(path)
+        .map_err(|_| Error::CannotReadFile(path.clone()))
+        .and_then(|s| serde_json::from_str(&s).map_err(|_| parse_error()))?;
+    let topology = json.as_object().ok_or_else(parse_error)?;
+    if topology.get("type").and_then(JsonValue::as_str) != Some("Topology") {
+        return Err(parse_error());
+    }
+
+    let transform = match topology.get("transform") {
+        Some(t) => Some(parse_transform(t).ok_or_else(parse_error)?),
+        None => None,
+    };
+    let arcs = match topology.get("arcs") {
+        Some(arcs) => decode_arcs(arcs, transform).ok_or_else(parse_error)?,
+        None => Vec::new(),
+    };
+    let decoder = Decoder { arcs, transform };
+
+    let objects = topology
+        .get("objects")
+        .and_then(JsonValue::as_object)
+        .ok_or_else(parse_error)?
+        .iter()
+        .map(|(name, object)| {
+            let object = object.as_object()?;
+            let features = match object.get("type").and_then(JsonValue::as_str) {
+                Some("GeometryCollection") => object
+                    .get("geometries")?
+                    .as_array()?
+                    .iter()
+                    .map(|g| decoder.feature(g.as_object()?))
+                    .collect::<Option<Vec<_>>>()?,
+                _ => vec![decoder.feature(object)?],
+            };
+
+            Some(TopologyObject {
+                name: name.to_string(),
+                features,
+            })
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(parse_error)?;
+
+    let bbox = topology
+        .get("bbox")
+        .and_then(|b| serde_json::from_value(b.clone()).ok());
+
+    Ok(Topology { bbox, objects })
+}
+
+fn parse_transform(value: &JsonValue) -> Option<Transform> {
+    let pair = |key: &str| -> Option<[f64; 2]> {
+        let v = value.get(key)?.as_array()?;
+        Some([v.first()?.as_f64()?, v.get(1)?.as_f64()?])
+    };
+
+    Some(Transform {
+        scale: pair("scale")?,
+        translate: pair("translate")?,
+    })
+}
+
+/// Decode the arcs to lng/lat positions. Quantized arcs are delta-encoded, so each position is
+/// the sum of all the positions before it in the arc.
+fn decode_arcs(arcs: &JsonValue, transform: Option<Transform>) -> Option<Vec<Vec<Position>>> {
+    arcs.as_array()?
+        .iter()
+        .map(|arc| {
+            let (mut x, mut y) = (0.0, 0.0);
+            arc.as_array()?
+                .iter()
+                .map(|p| {
+                    let (px, py) = xy(p)?;
+                    Some(match transform {
+                        Some(t) => {
+                            x += px;
+                            y += py;
+                            t.apply(x, y)
+                        }
+                        None => vec![px, py],
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The first two values of a position, ignoring any others.
+fn xy(position: &JsonValue) -> Option<(f64, f64)> {
+    let p = position.as_array()?;
+    Some((p.first()?.as_f64()?, p.get(1)?.as_f64()?))
+}
+
+/// Converts TopoJSON geometry objects to GeoJSON using the decoded arcs.
+struct Decoder {
+    arcs: Vec<Vec<Position>>,
+    transform: Option<Transform>,
+}
+
+impl Decoder {
+    fn feature(&self, object: &JsonObject) -> Option<Feature> {
+        let id = match object.get("id") {
+            Some(JsonValue::String(s)) => Some(Id::String(s.to_string())),
+            Some(JsonValue::Number(n)) => Some(Id::Number(n.clone())),
+            _ => None,
+        };
+        let properties = object
+            .get("properties")
+            .and_then(JsonValue::as_object)
+            .cloned();
+
+        Some(Feature {
+            bbox: None,
+            geometry: self.geometry(object)?.map(geojson::Geometry::new),
+            id,
+            properties,
+            foreign_members: None,
+        })
+    }
+
+    /// Convert a geometry object, which is `None` inside the option for a null geometry.
+    fn geometry(&self, object: &JsonObject) -> Option<Option<geojson::Value>> {
+        let coords = || object.get("coordinates")?.as_array();
+        let arcs = || object.get("arcs")?.as_array();
+
+        let value = match object.get("type").and_then(JsonValue::as_str) {
+            Some("Point") => geojson::Value::Point(self.point(object.get("coordinates")?)?),
+            Some("MultiPoint") => geojson::Value::MultiPoint(
+                coords()?
+                    .iter()
+                    .map(|p| self.point(p))
+                    .collect::<Option<_>>()?,
+            ),
+            Some("LineString") => geojson::Value::LineString(self.line(arcs()?)?),
+            Some("MultiLineString") => geojson::Value::MultiLineString(self.lines(arcs()?)?),
+            Some("Polygon") => geojson::Value::Polygon(self.lines(arcs()?)?),
+            Some("MultiPolygon") => geojson::Value::MultiPolygon(
+                arcs()?
+                    .iter()
+                    .map(|p| self.lines(p.as_array()?))
+                    .collect::<Option<_>>()?,
+            ),
+            Some("GeometryCollection") => geojson::Value::GeometryCollection(
+                object
+                    .get("geometries")?
+                    .as_array()?
+                    .iter()
+                    .filter_map(|g| match g.as_object().map(|g| self.geometry(g)) {
+                        Some(Some(Some(value))) => Some(Some(geojson::Geometry::new(value))),
+                        Some(Some(None)) => None,
+                        _ => Some(None),
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            None => return Some(None),
+            _ => return None,
+        };
+
+        Some(Some(value))
+    }
+
+    /// Points are quantized but not delta-encoded.
+    fn point(&self, position: &JsonValue) -> Option<Position> {
+        let (x, y) = xy(position)?;
+        Some(match self.transform {
+            Some(t) => t.apply(x, y),
+            None => vec![x, y],
+        })
+    }
+
+    fn lines(&self, lines: &[JsonValue]) -> Option<Vec<Vec<Position>>> {
+        lines.iter().map(|l| self.line(l.as_array()?)).collect()
+    }
+
+    /// Stitch arcs into a single line. Negative indexes are the ones' complement of an arc that
+    /// is reversed, and consecutive arcs share their end and start position.
+    fn line(&self, indexes: &[JsonValue]) -> Option<Vec<Position>> {
+        let mut line: Vec<Position> = Vec::new();
+
+        for i in indexes {
+            let i = i.as_i64()?;
+            let index = if i < 0 { !i } else { i };
+            let arc = self.arcs.get(usize::try_from(index).ok()?)?;
+            let points: Box<dyn Iterator<Item = &Position>> = if i < 0 {
+                Box::new(arc.iter().rev())
+            } else {
+                Box::new(arc.iter())
+            };
+
+            let skip = if line.is_empty() { 0 } else { 1 };
+            line.extend(points.skip(skip).cloned());
+        }
+
+        Some(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// The example topology from the TopoJSON specification.
+    fn example() -> JsonValue {
+        json!({
+            "type": "Topology",
+            "transform": {
+                "scale": [0.0005000500050005, 0.00010001000100010001],
+                "translate": [100, 0]
+            },
+            "objects": {
+                "example": {
+                    "type": "GeometryCollection",
+                    "geometries": [
+                        {"type": "Point", "properties": {"prop0": "value0"}, "coordinates": [4000, 5000]},
+                        {"type": "LineString", "properties": {"prop0": "value0"}, "arcs": [0]},
+                        {"type": "Polygon", "properties": {"prop0": "value0"}, "arcs": [[-2]]}
+                    ]
+                }
+            },
+            "arcs": [
+                [[4000, 0], [1999, 9999], [2000, -9999], [2000, 9999]],
+                [[0, 0], [0, 9999], [2000, 0], [0, -9999], [-2000, 0]]
+            ]
+        })
+    }
+
+    fn decoder(topology: &JsonValue) -> Decoder {
+        let transform = topology.get("transform").and_then(parse_transform);
+        let arcs = decode_arcs(&topology["arcs"], transform).unwrap();
+        Decoder { arcs, transform }
+    }
+
+    /// The example is quantized, so positions are only close to the round numbers.
+    fn assert_positions(actual: &[Position], expected: &[[f64; 2]]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a[0] - e[0]).abs() < 1e-3 && (a[1] - e[1]).abs() < 1e-3,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    fn geometries(topology: &JsonValue) -> Vec<geojson::Value> {
+        let decoder = decoder(topology);
+        topology["objects"]["example"]["geometries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|g| decoder.geometry(g.as_object().unwrap()).unwrap().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn quantized_arcs_are_delta_decoded() {
+        let decoder = decoder(&example());
+
+        assert_positions(
+            &decoder.arcs[0],
+            &[[102.0, 0.0], [103.0, 1.0], [104.0, 0.0], [105.0, 1.0]],
+        );
+        assert_positions(
+            &decoder.arcs[1],
+            &[
+                [100.0, 0.0],
+                [100.0, 1.0],
+                [101.0, 1.0],
+                [101.0, 0.0],
+                [100.0, 0.0],
+            ],
+        );
+    }
+
+    #[test]
+    fn points_are_not_delta_decoded() {
+        match &geometries(&example())[0] {
+            geojson::Value::Point(p) => assert_positions(&[p.clone()], &[[102.0, 0.5]]),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn reversed_arcs() {
+        let geometries = geometries(&example());
+
+        match &geometries[1] {
+            geojson::Value::LineString(l) => {
+                assert_positions(l, &[[102.0, 0.0], [103.0, 1.0], [104.0, 0.0], [105.0, 1.0]])
+            }
+            other => panic!("{other:?}"),
+        }
+        match &geometries[2] {
+            geojson::Value::Polygon(p) => {
+                assert_eq!(p.len(), 1);
+                assert_positions(
+                    &p[0],
+                    &[
+                        [100.0, 0.0],
+                        [101.0, 0.0],
+                        [101.0, 1.0],
+                        [100.0, 1.0],
+                        [100.0, 0.0],
+                    ],
+                );
+            }
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn arcs_are_stitched_at_shared_positions() {
+        let decoder = decoder(&json!({
+            "arcs": [[[0, 0], [1, 0]], [[1, 0], [1, 1]], [[1, 1], [0, 0]]]
+        }));
+
+        let ring = decoder.line(json!([0, 1, 2]).as_array().unwrap()).unwrap();
+        assert_positions(&ring, &[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]]);
+
+        let reversed = decoder
+            .line(json!([-3, -2, -1]).as_array().unwrap())
+            .unwrap();
+        assert_positions(&reversed, &[[0.0, 0.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]);
+    }
+
+    #[test]
+    fn null_geometry() {
+        let decoder = decoder(&example());
+        let feature = decoder
+            .feature(json!({"properties": {"name": "none"}}).as_object().unwrap())
+            .unwrap();
+
+        assert!(feature.geometry.is_none());
+        assert_eq!(feature.property("name"), Some(&json!("none")));
+    }
+
+    #[test]
+    fn unknown_arc_is_an_error() {
+        let decoder = decoder(&example());
+
+        assert!(decoder.line(json!([2]).as_array().unwrap()).is_none());
+        assert!(decoder.line(json!([-3]).as_array().unwrap()).is_none());
+    }
+}
@@ -0,0 +1,119 @@
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+use geo::LineString;
+use gpx::{Gpx, Route, Track, Waypoint};
+use quadtree::{Geometry, ToRadians};
+
+use crate::error::Error;
+
+/// The metadata fields available for every GPX feature.
+pub const GPX_FIELDS: [&str; 5] = ["name", "desc", "type", "time", "ele"];
+
+/// Return a [`Gpx`] object loaded from a `.gpx` file.
+pub fn read_gpx(path: &PathBuf) -> Result<Gpx, Error> {
+    let file = File::open(path).map_err(|_| Error::CannotReadFile(path.clone()))?;
+
+    gpx::read(BufReader::new(file)).map_err(|_| Error::CannotParseFile(path.clone()))
+}
+
+/// Metadata of a waypoint, route or track, kept separate from the points so that long tracks are
+/// not copied into every part. Routes and tracks have no time or elevation of their own, so use
+/// those of their first point.
+#[derive(Clone, Debug, Default)]
+pub struct GpxRecord {
+    pub name: Option<String>,
+    pub desc: Option<String>,
+    pub type_: Option<String>,
+    /// The time in RFC 3339 format.
+    pub time: Option<String>,
+    pub ele: Option<f64>,
+}
+
+impl GpxRecord {
+    /// The string value of one of the [`GPX_FIELDS`], empty for any other field.
+    pub fn field(&self, field: &str) -> String {
+        match field {
+            "name" => self.name.clone().unwrap_or_default(),
+            "desc" => self.desc.clone().unwrap_or_default(),
+            "type" => self.type_.clone().unwrap_or_default(),
+            "time" => self.time.clone().unwrap_or_default(),
+            "ele" => self.ele.map(|e| e.to_string()).unwrap_or_default(),
+            _ => String::default(),
+        }
+    }
+
+    fn from_waypoint(w: &Waypoint) -> Self {
+        Self {
+            name: w.name.clone(),
+            desc: w.description.clone(),
+            type_: w.type_.clone(),
+            time: w.time.as_ref().and_then(|t| t.format().ok()),
+            ele: w.elevation,
+        }
+    }
+
+    /// Metadata of a route or track, with the time and elevation of `first`.
+    fn from_line(
+        name: &Option<String>,
+        desc: &Option<String>,
+        type_: &Option<String>,
+        first: Option<&Waypoint>,
+    ) -> Self {
+        let first = first.map(Self::from_waypoint).unwrap_or_default();
+
+        Self {
+            name: name.clone(),
+            desc: desc.clone(),
+            type_: type_.clone(),
+            ..first
+        }
+    }
+
+    fn from_route(r: &Route) -> Self {
+        Self::from_line(&r.name, &r.description, &r.type_, r.points.first())
+    }
+
+    fn from_track(t: &Track) -> Self {
+        let first = t.segments.iter().flat_map(|s| s.points.first()).next();
+        Self::from_line(&t.name, &t.description, &t.type_, first)
+    }
+}
+
+/// Iterate the features of a GPX file, first the waypoints, then the routes, then the tracks. Each
+/// feature is emitted with its geometries in radians and its metadata. Waypoints are Points,
+/// routes are a single LineString, and tracks have a LineString for each segment. Routes and
+/// segments with fewer than two points are not lines so are dropped, which can leave a feature with
+/// no geometries.
+pub fn gpx_features(gpx: Gpx) -> impl Iterator<Item = (Vec<Geometry<f64>>, GpxRecord)> {
+    let waypoints = gpx.waypoints.into_iter().map(|w| {
+        let mut point = w.point();
+        point.to_radians_in_place();
+        (vec![Geometry::Point(point)], GpxRecord::from_waypoint(&w))
+    });
+
+    let routes = gpx.routes.into_iter().map(|r| {
+        let line = line_geometry(r.linestring());
+        (line.into_iter().collect(), GpxRecord::from_route(&r))
+    });
+
+    let tracks = gpx.tracks.into_iter().map(|t| {
+        let segments = t
+            .segments
+            .iter()
+            .filter_map(|s| line_geometry(s.linestring()))
+            .collect();
+        (segments, GpxRecord::from_track(&t))
+    });
+
+    waypoints.chain(routes).chain(tracks)
+}
+
+/// Convert a line to radians, or `None` if it has fewer than two points.
+fn line_geometry(mut line: LineString<f64>) -> Option<Geometry<f64>> {
+    if line.0.len() < 2 {
+        return None;
+    }
+    line.to_radians_in_place();
+
+    Some(Geometry::LineString(line))
+}
@@ -0,0 +1,98 @@
+use serde_json::{Map, Value};
+
+/// One step of a path into a JSON value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Parse a field path of dotted keys with optional array indexes, such as `address.city` or
+/// `tags[0]`. Returns `None` if the path is malformed.
+pub fn parse_path(path: &str) -> Option<Vec<Segment<'_>>> {
+    let mut segments = Vec::new();
+
+    for part in path.split('.') {
+        let (key, mut indexes) = match part.find('[') {
+            Some(i) => (&part[..i], &part[i..]),
+            None => (part, ""),
+        };
+        // Only a leading index, as in `[0].name`, can come without a key
+        if key.is_empty() && !(segments.is_empty() && !indexes.is_empty()) {
+            return None;
+        }
+        if !key.is_empty() {
+            segments.push(Segment::Key(key));
+        }
+
+        while !indexes.is_empty() {
+            let close = indexes.find(']')?;
+            segments.push(Segment::Index(indexes[1..close].parse().ok()?));
+            indexes = &indexes[close + 1..];
+            if !indexes.is_empty() && !indexes.starts_with('[') {
+                return None;
+            }
+        }
+    }
+
+    Some(segments)
+}
+
+/// Follow `segments` into `value`.
+pub fn lookup<'a>(value: &'a Value, segments: &[Segment]) -> Option<&'a Value> {
+    segments
+        .iter()
+        .try_fold(value, |value, segment| match (segment, value) {
+            (Segment::Key(key), Value::Object(map)) => map.get(*key),
+            (Segment::Index(i), Value::Array(array)) => array.get(*i),
+            _ => None,
+        })
+}
+
+/// Find `field` in a map of JSON properties. An exact key match is preferred, so keys that contain
+/// dots are still found, then `field` is treated as a path.
+pub fn lookup_field<'a>(map: &'a Map<String, Value>, field: &str) -> Option<&'a Value> {
+    map.get(field).or_else(|| {
+        let segments = parse_path(field)?;
+        match segments.split_first()? {
+            (Segment::Key(key), rest) => lookup(map.get(*key)?, rest),
+            _ => None,
+        }
+    })
+}
+
+/// Convert a JSON value to an output string. Strings are unquoted, and nulls are empty. Arrays and
+/// Objects are written as compact JSON text when `nested_json` is set, and are empty otherwise.
+pub fn value_to_string(value: Option<&Value>, nested_json: bool) -> String {
+    match value {
+        Some(Value::Null) | None => String::default(),
+        Some(Value::Number(n)) => n.to_string(),
+        Some(Value::String(s)) => s.to_owned(),
+        Some(Value::Bool(b)) => b.to_string(),
+        Some(v @ (Value::Array(_) | Value::Object(_))) if nested_json => v.to_string(),
+        Some(Value::Array(_) | Value::Object(_)) => String::default(),
+    }
+}
+
+/// Call `f` with the path and value of every leaf below `value`, where `prefix` is the path to
+/// `value` itself. Empty Arrays and Objects are treated as leaves.
+pub fn leaf_paths(value: &Value, prefix: String, f: &mut impl FnMut(String, &Value)) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, v) in map {
+                let path = if prefix.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                leaf_paths(v, path, f);
+            }
+        }
+        Value::Array(array) if !array.is_empty() => {
+            for (i, v) in array.iter().enumerate() {
+                leaf_paths(v, format!("{prefix}[{i}]"), f);
+            }
+        }
+        _ => f(prefix, value),
+    }
+}
@@ -1,26 +1,33 @@
-use std::{iter::FlatMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    iter::{empty, once, FlatMap},
+    path::PathBuf,
+};
 
 use kml::{types::*, KmlReader};
 use quadtree::{Geometry, ToRadians};
 
 use crate::error::{Error, UnsupportedGeoType};
 
-/// Return a [`kml::Kml`] object loaded from a `.kml` or `.kmz` file.
+/// Return a [`kml::Kml`] object loaded from a `.kml` or `.kmz` file. KMZ archives are detected
+/// from the zip signature, so the extension is not required.
 pub fn read_kml(path: &PathBuf) -> Result<kml::Kml, Error> {
-    let ext = path
-        .extension()
-        .ok_or(Error::CannotParseFileExtension(path.clone()))?;
+    let mut magic = [0; 4];
+    let is_kmz = File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .map(|_| &magic == b"PK\x03\x04")
+        .unwrap_or(false);
 
-    if ext == "kml" {
-        KmlReader::<_, f64>::from_path(path.clone())
-            .map_err(|_| Error::CannotReadFile(path.clone()))
-            .and_then(|mut r| r.read().map_err(|_| Error::CannotParseFile(path.clone())))
-    } else if ext == "kmz" {
+    if is_kmz {
         KmlReader::<_, f64>::from_kmz_path(path.clone())
             .map_err(|_| Error::CannotReadFile(path.clone()))
             .and_then(|mut r| r.read().map_err(|_| Error::CannotParseFile(path.clone())))
     } else {
-        Err(Error::UnsupportedFileType)
+        KmlReader::<_, f64>::from_path(path.clone())
+            .map_err(|_| Error::CannotReadFile(path.clone()))
+            .and_then(|mut r| r.read().map_err(|_| Error::CannotParseFile(path.clone())))
     }
 }
 
@@ -60,6 +67,60 @@ pub fn convert_kml_geom(item: kml::types::Geometry) -> Result<(Geometry<f64>, Km
     }
 }
 
+/// Convert a kml geometry into geo-type geometries, flattening MultiGeometries at any depth into
+/// their parts.
+pub fn flatten_kml_geom(
+    item: kml::types::Geometry,
+) -> Box<dyn Iterator<Item = Result<(Geometry<f64>, KmlItem), Error>>> {
+    match item {
+        kml::types::Geometry::MultiGeometry(mg) => {
+            Box::new(mg.geometries.into_iter().flat_map(flatten_kml_geom))
+        }
+        item => Box::new(once(convert_kml_geom(item))),
+    }
+}
+
+/// Iterate the named attributes stored in the `<ExtendedData>` of a Placemark, as pairs of name and
+/// value. Both the untyped `<Data name=".."><value>` form and the schema-typed
+/// `<SchemaData><SimpleData name="..">` form are read.
+pub fn extended_data<'a>(
+    placemark: &'a Placemark,
+) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+    placemark
+        .children
+        .iter()
+        .filter(|c| c.name == "ExtendedData")
+        .flat_map(|ed| ed.children.iter())
+        .flat_map(|c| -> Box<dyn Iterator<Item = (&'a str, &'a str)> + 'a> {
+            match c.name.as_str() {
+                "Data" => Box::new(
+                    c.attrs
+                        .get("name")
+                        .map(|name| {
+                            let value = c
+                                .children
+                                .iter()
+                                .find(|v| v.name == "value")
+                                .and_then(|v| v.content.as_deref());
+                            (name.as_str(), value.unwrap_or_default())
+                        })
+                        .into_iter(),
+                ),
+                "SchemaData" => Box::new(
+                    c.children
+                        .iter()
+                        .filter(|s| s.name == "SimpleData")
+                        .filter_map(|s| {
+                            s.attrs.get("name").map(|name| {
+                                (name.as_str(), s.content.as_deref().unwrap_or_default())
+                            })
+                        }),
+                ),
+                _ => Box::new(empty()),
+            }
+        })
+}
+
 /// Wrapper around a Kml enum for custom iterators. These custom iterators only emit the kml
 /// components that are useful for proximity processing - i.e. the ones that contain geometries.
 pub struct Kml {
@@ -68,15 +129,191 @@ pub struct Kml {
 
 impl Kml {
     /// Build a new Kml document from the path to a KML or KMZ file.
+    ///
+    /// Each Placemark is annotated with the synthetic attributes [`FOLDER_PATH_FIELD`],
+    /// [`STYLE_URL_FIELD`], [`ICON_COLOR_FIELD`] and [`LINE_COLOR_FIELD`], as the folder hierarchy
+    /// and shared styles are lost once the iterators flatten the document.
     pub fn from_path(path: &PathBuf) -> Result<Self, Error> {
-        Ok(Self {
-            kml: read_kml(path)?,
-        })
+        let mut kml = read_kml(path)?;
+
+        let mut styles = Styles::default();
+        styles.collect(&kml);
+        annotate_placemarks(&mut kml, &styles, &mut Vec::new());
+
+        Ok(Self { kml })
     }
 
     pub fn iter(&self) -> IntoIterRef {
         self.into_iter()
     }
+
+    /// The fields declared by `<SimpleField>` elements in any `<Schema>` in the document, mapped to
+    /// their declared type.
+    pub fn schema_types(&self) -> HashMap<String, String> {
+        let mut types = HashMap::new();
+        collect_schema_types(&self.kml, &mut types);
+
+        types
+    }
+}
+
+fn collect_schema_types(kml: &kml::Kml, types: &mut HashMap<String, String>) {
+    match kml {
+        kml::Kml::KmlDocument(d) => {
+            for k in &d.elements {
+                collect_schema_types(k, types);
+            }
+        }
+        kml::Kml::Document { attrs: _, elements } | kml::Kml::Folder { attrs: _, elements } => {
+            for k in elements {
+                collect_schema_types(k, types);
+            }
+        }
+        kml::Kml::Element(e) if e.name == "Schema" => {
+            for field in e.children.iter().filter(|f| f.name == "SimpleField") {
+                if let (Some(name), Some(field_type)) =
+                    (field.attrs.get("name"), field.attrs.get("type"))
+                {
+                    types.insert(name.to_string(), field_type.to_string());
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Synthetic Placemark attribute holding the names of the enclosing `<Folder>`s, joined with `/`.
+pub const FOLDER_PATH_FIELD: &str = "folder_path";
+/// Synthetic Placemark attribute holding the `styleUrl`, resolved through any `<StyleMap>` to the
+/// URL of its normal style.
+pub const STYLE_URL_FIELD: &str = "style_url";
+/// Synthetic Placemark attribute holding the `<IconStyle>` colour, in KML `aabbggrr` form.
+pub const ICON_COLOR_FIELD: &str = "icon_color";
+/// Synthetic Placemark attribute holding the `<LineStyle>` colour, in KML `aabbggrr` form.
+pub const LINE_COLOR_FIELD: &str = "line_color";
+
+/// Shared styles declared in a document, keyed by their id.
+#[derive(Default)]
+struct Styles {
+    /// Icon and line colours of each `<Style>`.
+    colors: HashMap<String, (Option<String>, Option<String>)>,
+    /// URL of the normal style of each `<StyleMap>`.
+    maps: HashMap<String, String>,
+}
+
+impl Styles {
+    fn collect(&mut self, kml: &kml::Kml) {
+        match kml {
+            kml::Kml::KmlDocument(d) => {
+                for k in &d.elements {
+                    self.collect(k);
+                }
+            }
+            kml::Kml::Document { attrs: _, elements } | kml::Kml::Folder { attrs: _, elements } => {
+                for k in elements {
+                    self.collect(k);
+                }
+            }
+            kml::Kml::Style(s) => {
+                if let Some(id) = &s.id {
+                    let icon = s.icon.as_ref().map(|i| i.color.to_string());
+                    let line = s.line.as_ref().map(|l| l.color.to_string());
+                    self.colors.insert(id.to_string(), (icon, line));
+                }
+            }
+            kml::Kml::StyleMap(m) => {
+                let normal = m.pairs.iter().find(|p| p.key == "normal");
+                if let (Some(id), Some(normal)) = (&m.id, normal) {
+                    self.maps
+                        .insert(id.to_string(), normal.style_url.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Resolve a style URL through any StyleMap to the URL of a Style. Only references to styles in
+    /// the same document, of the form `#id`, can be resolved.
+    fn resolve<'a>(&'a self, url: &'a str) -> &'a str {
+        url.strip_prefix('#')
+            .and_then(|id| self.maps.get(id))
+            .map(String::as_str)
+            .unwrap_or(url)
+    }
+
+    fn colors(&self, url: &str) -> (Option<String>, Option<String>) {
+        url.strip_prefix('#')
+            .and_then(|id| self.colors.get(id))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Add the folder path and style attributes to every Placemark below `kml`. `path` holds the names
+/// of the folders enclosing `kml`.
+fn annotate_placemarks(kml: &mut kml::Kml, styles: &Styles, path: &mut Vec<String>) {
+    match kml {
+        kml::Kml::KmlDocument(d) => {
+            for k in d.elements.iter_mut() {
+                annotate_placemarks(k, styles, path);
+            }
+        }
+        kml::Kml::Document { attrs: _, elements } => {
+            for k in elements.iter_mut() {
+                annotate_placemarks(k, styles, path);
+            }
+        }
+        kml::Kml::Folder { attrs: _, elements } => {
+            let name = elements.iter().find_map(|k| match k {
+                kml::Kml::Element(e) if e.name == "name" => e.content.clone(),
+                _ => None,
+            });
+            path.push(name.unwrap_or_default().trim().to_string());
+            for k in elements.iter_mut() {
+                annotate_placemarks(k, styles, path);
+            }
+            path.pop();
+        }
+        kml::Kml::Placemark(p) => {
+            p.attrs
+                .insert(FOLDER_PATH_FIELD.to_string(), path.join("/"));
+
+            let style_url = p.style_url.clone().or_else(|| {
+                p.children
+                    .iter()
+                    .find(|c| c.name == "styleUrl")
+                    .and_then(|c| c.content.clone())
+            });
+            let (mut icon, mut line) = (None, None);
+            if let Some(url) = style_url {
+                let url = styles.resolve(url.trim());
+                (icon, line) = styles.colors(url);
+                p.attrs.insert(STYLE_URL_FIELD.to_string(), url.to_string());
+            }
+
+            // Colours from an inline style take priority over the shared style
+            let inline = |style: &str| {
+                p.children
+                    .iter()
+                    .filter(|c| c.name == "Style")
+                    .flat_map(|c| c.children.iter())
+                    .filter(|c| c.name == style)
+                    .flat_map(|c| c.children.iter())
+                    .find(|c| c.name == "color")
+                    .and_then(|c| c.content.as_deref().map(|s| s.trim().to_string()))
+            };
+            icon = inline("IconStyle").or(icon);
+            line = inline("LineStyle").or(line);
+
+            if let Some(icon) = icon {
+                p.attrs.insert(ICON_COLOR_FIELD.to_string(), icon);
+            }
+            if let Some(line) = line {
+                p.attrs.insert(LINE_COLOR_FIELD.to_string(), line);
+            }
+        }
+        _ => {}
+    }
 }
 
 impl From<kml::Kml> for Kml {
@@ -194,6 +431,12 @@ impl<'a> IntoIterRef<'a> {
                 IntoIterRef::Iter(Box::new(elements.iter().flat_map(|k| IntoIterRef::new(k))))
             }
             kml::Kml::MultiGeometry(d) => IntoIterRef::Once(KmlItemRef::MultiGeometry(d)),
+            kml::Kml::LinearRing(d) => IntoIterRef::Once(KmlItemRef::LinearRing(d)),
+            kml::Kml::LineString(d) => IntoIterRef::Once(KmlItemRef::LineString(d)),
+            kml::Kml::Location(d) => IntoIterRef::Once(KmlItemRef::Location(d)),
+            kml::Kml::Point(d) => IntoIterRef::Once(KmlItemRef::Point(d)),
+            kml::Kml::Placemark(d) => IntoIterRef::Once(KmlItemRef::Placemark(d)),
+            kml::Kml::Polygon(d) => IntoIterRef::Once(KmlItemRef::Polygon(d)),
             // Ignore all else
             _ => IntoIterRef::Empty,
         }
@@ -206,7 +449,14 @@ impl<'a> Iterator for IntoIterRef<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             IntoIterRef::Iter(iter) => iter.next(),
-            IntoIterRef::Once(item) => Some(item.clone()),
+            // Swap to the empty state so the item is only emitted once
+            once @ IntoIterRef::Once(_) => {
+                if let IntoIterRef::Once(item) = std::mem::replace(once, IntoIterRef::Empty) {
+                    Some(item)
+                } else {
+                    unreachable!()
+                }
+            }
             IntoIterRef::Empty => None,
         }
     }
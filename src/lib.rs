@@ -1,5 +1,11 @@
+pub mod archive;
+pub mod encoding;
 pub mod error;
 pub mod geojson;
+pub mod gpx;
+pub mod json;
 pub mod kml;
+pub mod osm;
 pub mod qt;
 pub mod shp;
+pub mod wkt;
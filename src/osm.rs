@@ -0,0 +1,578 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    fs::File,
+    io::BufReader,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use geo::{Contains, Coord, LineString, Point, Polygon, Rect};
+use quadtree::{Geometry, ToRadians};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::error::Error;
+
+/// The metadata fields available for every OSM feature, in addition to its tags.
+pub const OSM_FIELDS: [&str; 2] = ["osm_id", "osm_type"];
+
+/// Keys whose presence makes a closed way an area rather than a line, unless it has `area=no`.
+const AREA_KEYS: [&str; 15] = [
+    "building",
+    "building:part",
+    "landuse",
+    "amenity",
+    "leisure",
+    "natural",
+    "shop",
+    "tourism",
+    "man_made",
+    "military",
+    "place",
+    "aeroway",
+    "historic",
+    "office",
+    "craft",
+];
+
+/// Values of `natural` that are lines even when the way is closed.
+const NATURAL_LINES: [&str; 4] = ["coastline", "cliff", "ridge", "tree_row"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OsmKind {
+    Node,
+    Way,
+    Relation,
+}
+
+impl fmt::Display for OsmKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            OsmKind::Node => "node",
+            OsmKind::Way => "way",
+            OsmKind::Relation => "relation",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// Filter on the tags of an element, parsed from `key` to match any value or `key=value` to match
+/// only that value.
+#[derive(Clone, Debug)]
+pub struct TagFilter {
+    key: String,
+    value: Option<String>,
+}
+
+impl TagFilter {
+    fn matches(&self, tags: &HashMap<String, String>) -> bool {
+        match (tags.get(&self.key), &self.value) {
+            (Some(v), Some(value)) => v == value,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+impl FromStr for TagFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = match s.split_once('=') {
+            Some((key, value)) => (key, Some(value.to_string())),
+            None => (s, None),
+        };
+
+        if key.is_empty() {
+            return Err(Error::InvalidTagFilter(s.to_string()));
+        }
+
+        Ok(Self {
+            key: key.to_string(),
+            value,
+        })
+    }
+}
+
+/// Metadata of a node, way or relation.
+#[derive(Clone, Debug)]
+pub struct OsmRecord {
+    pub kind: OsmKind,
+    pub id: i64,
+    pub tags: HashMap<String, String>,
+}
+
+impl OsmRecord {
+    /// The string value of one of the [`OSM_FIELDS`] or a tag, empty for any tag not set.
+    pub fn field(&self, field: &str) -> String {
+        match field {
+            "osm_id" => self.id.to_string(),
+            "osm_type" => self.kind.to_string(),
+            _ => self.tags.get(field).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+struct Member {
+    kind: OsmKind,
+    id: i64,
+    role: String,
+}
+
+/// A node, way or relation as read from the file. Only nodes and ways that are referenced by
+/// others need their positions and node lists, which are kept separately in [`Osm`].
+struct Element {
+    record: OsmRecord,
+    refs: Vec<i64>,
+    members: Vec<Member>,
+}
+
+/// The contents of an OSM XML file.
+pub struct Osm {
+    pub bounds: Option<Rect>,
+    nodes: HashMap<i64, Coord>,
+    ways: HashMap<i64, Vec<i64>>,
+    relation_count: usize,
+    /// Tagged nodes, tagged ways and multipolygon relations in file order.
+    elements: Vec<Element>,
+}
+
+/// Read an OSM XML file. Every node position and way is kept, so that ways and relations can be
+/// built, but only tagged nodes and ways and multipolygon relations become features.
+pub fn read_osm(path: &PathBuf) -> Result<Osm, Error> {
+    let file = File::open(path).map_err(|_| Error::CannotReadFile(path.clone()))?;
+    let mut reader = Reader::from_reader(BufReader::new(file));
+
+    let mut osm = Osm {
+        bounds: None,
+        nodes: HashMap::new(),
+        ways: HashMap::new(),
+        relation_count: 0,
+        elements: Vec::new(),
+    };
+    let mut current = None;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader
+            .read_event_into(&mut buf)
+            .map_err(|_| Error::CannotParseFile(path.clone()))?
+        {
+            Event::Start(e) => osm.start(&e, &mut current),
+            Event::Empty(e) => {
+                osm.start(&e, &mut current);
+                if is_element(e.name().as_ref()) {
+                    osm.finish(current.take());
+                }
+            }
+            Event::End(e) if is_element(e.name().as_ref()) => osm.finish(current.take()),
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(osm)
+}
+
+fn is_element(name: &[u8]) -> bool {
+    matches!(name, b"node" | b"way" | b"relation")
+}
+
+/// The unescaped value of the attribute `name`.
+fn attr(e: &BytesStart, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name)
+        .and_then(|a| a.unescape_value().ok().map(|v| v.into_owned()))
+}
+
+fn attr_num<T: FromStr>(e: &BytesStart, name: &[u8]) -> Option<T> {
+    attr(e, name)?.parse().ok()
+}
+
+impl Osm {
+    /// Handle an opening tag. Nodes, ways and relations without an id are ignored along with
+    /// their children.
+    fn start(&mut self, e: &BytesStart, current: &mut Option<Element>) {
+        let new_element = |kind| {
+            Some(Element {
+                record: OsmRecord {
+                    kind,
+                    id: attr_num(e, b"id")?,
+                    tags: HashMap::new(),
+                },
+                refs: Vec::new(),
+                members: Vec::new(),
+            })
+        };
+
+        match e.name().as_ref() {
+            b"bounds" => {
+                let corner =
+                    |lng: &[u8], lat: &[u8]| Some(Point::new(attr_num(e, lng)?, attr_num(e, lat)?));
+                if let (Some(min), Some(max)) =
+                    (corner(b"minlon", b"minlat"), corner(b"maxlon", b"maxlat"))
+                {
+                    self.bounds = Some(Rect::new(min, max));
+                }
+            }
+            b"node" => {
+                *current = new_element(OsmKind::Node);
+                if let (Some(el), Some(lng), Some(lat)) =
+                    (current.as_ref(), attr_num(e, b"lon"), attr_num(e, b"lat"))
+                {
+                    self.nodes.insert(el.record.id, Coord { x: lng, y: lat });
+                }
+            }
+            b"way" => *current = new_element(OsmKind::Way),
+            b"relation" => *current = new_element(OsmKind::Relation),
+            b"tag" => {
+                if let (Some(el), Some(k), Some(v)) =
+                    (current.as_mut(), attr(e, b"k"), attr(e, b"v"))
+                {
+                    el.record.tags.insert(k, v);
+                }
+            }
+            b"nd" => {
+                if let (Some(el), Some(id)) = (current.as_mut(), attr_num(e, b"ref")) {
+                    el.refs.push(id);
+                }
+            }
+            b"member" => {
+                let kind = match attr(e, b"type").as_deref() {
+                    Some("node") => OsmKind::Node,
+                    Some("way") => OsmKind::Way,
+                    _ => OsmKind::Relation,
+                };
+                if let (Some(el), Some(id)) = (current.as_mut(), attr_num(e, b"ref")) {
+                    el.members.push(Member {
+                        kind,
+                        id,
+                        role: attr(e, b"role").unwrap_or_default(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Keep a complete element if it is a feature, and the node list of every way.
+    fn finish(&mut self, element: Option<Element>) {
+        let Some(element) = element else {
+            return;
+        };
+
+        let is_feature = match element.record.kind {
+            OsmKind::Node => !element.record.tags.is_empty(),
+            OsmKind::Way => {
+                self.ways.insert(element.record.id, element.refs.clone());
+                !element.record.tags.is_empty()
+            }
+            OsmKind::Relation => {
+                self.relation_count += 1;
+                element.record.tags.get("type").map(String::as_str) == Some("multipolygon")
+            }
+        };
+
+        if is_feature {
+            self.elements.push(element);
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn way_count(&self) -> usize {
+        self.ways.len()
+    }
+
+    pub fn relation_count(&self) -> usize {
+        self.relation_count
+    }
+
+    /// The elements that are features and match any of the filters, or all of them if there are
+    /// no filters.
+    fn matching<'a>(&'a self, filter: &'a [TagFilter]) -> impl Iterator<Item = &'a Element> {
+        self.elements
+            .iter()
+            .filter(move |e| filter.is_empty() || filter.iter().any(|f| f.matches(&e.record.tags)))
+    }
+
+    /// The number of features matching the filter.
+    pub fn count(&self, filter: &[TagFilter]) -> usize {
+        self.matching(filter).count()
+    }
+
+    /// The keys of every tag on the features matching the filter.
+    pub fn tag_keys(&self, filter: &[TagFilter]) -> BTreeSet<String> {
+        self.matching(filter)
+            .flat_map(|e| e.record.tags.keys().cloned())
+            .collect()
+    }
+
+    /// Iterate the features matching the filter in file order, each with its geometries in
+    /// radians and its metadata. Nodes are Points, ways are LineStrings or Polygons for closed
+    /// areas, and multipolygon relations are a Polygon for each outer ring. Nodes missing from
+    /// the extract are skipped, so ways and relations cut by the extract bounds may be partial
+    /// or have no geometry.
+    pub fn features<'a>(
+        &'a self,
+        filter: &'a [TagFilter],
+    ) -> impl Iterator<Item = (Vec<Geometry<f64>>, OsmRecord)> + 'a {
+        self.matching(filter).map(move |e| {
+            let geoms = match e.record.kind {
+                OsmKind::Node => self
+                    .nodes
+                    .get(&e.record.id)
+                    .map(|c| {
+                        let mut point: Point = (*c).into();
+                        point.to_radians_in_place();
+                        Geometry::Point(point)
+                    })
+                    .into_iter()
+                    .collect(),
+                OsmKind::Way => self.way_geometry(&e.refs, &e.record.tags),
+                OsmKind::Relation => self.multipolygon(&e.members),
+            };
+
+            (geoms, e.record.clone())
+        })
+    }
+
+    fn line(&self, refs: &[i64]) -> LineString {
+        refs.iter()
+            .filter_map(|id| self.nodes.get(id))
+            .copied()
+            .collect()
+    }
+
+    fn way_geometry(&self, refs: &[i64], tags: &HashMap<String, String>) -> Vec<Geometry<f64>> {
+        let mut line = self.line(refs);
+        line.to_radians_in_place();
+
+        if line.0.len() < 2 {
+            Vec::new()
+        } else if line.0.len() >= 4 && line.is_closed() && is_area(tags) {
+            vec![Geometry::Polygon(Polygon::new(line, Vec::new()))]
+        } else {
+            vec![Geometry::LineString(line)]
+        }
+    }
+
+    /// Assemble the outer and inner ways of a multipolygon into rings, then each outer ring into a
+    /// Polygon with the inner rings that fall inside it. Members without a role are outer.
+    fn multipolygon(&self, members: &[Member]) -> Vec<Geometry<f64>> {
+        let rings = |outer: bool| {
+            let ways = members
+                .iter()
+                .filter(|m| m.kind == OsmKind::Way && (m.role == "inner") != outer)
+                .filter_map(|m| self.ways.get(&m.id))
+                .map(|refs| {
+                    refs.iter()
+                        .copied()
+                        .filter(|id| self.nodes.contains_key(id))
+                        .collect::<Vec<_>>()
+                })
+                .filter(|refs| refs.len() >= 2)
+                .collect();
+            join_rings(ways)
+                .into_iter()
+                .map(|ring| self.line(&ring))
+                .collect::<Vec<_>>()
+        };
+
+        let mut inners = rings(false);
+        rings(true)
+            .into_iter()
+            .map(|outer| {
+                let outer = Polygon::new(outer, Vec::new());
+                let (holes, rest): (Vec<_>, Vec<_>) =
+                    inners.drain(..).partition(|inner: &LineString| {
+                        inner.0.first().is_some_and(|c| outer.contains(c))
+                    });
+                inners = rest;
+
+                let mut polygon = Polygon::new(outer.exterior().clone(), holes);
+                polygon.to_radians_in_place();
+                Geometry::Polygon(polygon)
+            })
+            .collect()
+    }
+}
+
+/// Join ways into closed rings where they share end nodes, reversing ways as needed. Ways that
+/// cannot be closed into a ring are dropped.
+fn join_rings(mut ways: Vec<Vec<i64>>) -> Vec<Vec<i64>> {
+    let mut rings = Vec::new();
+
+    while let Some(mut ring) = ways.pop() {
+        loop {
+            if ring.len() >= 4 && ring.first() == ring.last() {
+                rings.push(ring);
+                break;
+            }
+
+            let end = ring.last().copied();
+            match ways
+                .iter()
+                .position(|w| w.first().copied() == end || w.last().copied() == end)
+            {
+                Some(i) => {
+                    let mut way = ways.swap_remove(i);
+                    if way.first().copied() != end {
+                        way.reverse();
+                    }
+                    ring.extend(way.into_iter().skip(1));
+                }
+                None => break,
+            }
+        }
+    }
+
+    rings
+}
+
+fn is_area(tags: &HashMap<String, String>) -> bool {
+    match tags.get("area").map(String::as_str) {
+        Some("yes") => true,
+        Some("no") => false,
+        _ => AREA_KEYS.iter().any(|k| match tags.get(*k) {
+            Some(v) if *k == "natural" => !NATURAL_LINES.contains(&v.as_str()),
+            Some(_) => true,
+            None => false,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_file, write};
+
+    use super::*;
+
+    /// Two outer squares made of ways that only join when one is reversed, an outer way that
+    /// cannot be closed, and a hole in the second square split the same way.
+    const MULTIPOLYGON: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osm version="0.6">
+  <node id="1" lon="0" lat="0"/>
+  <node id="2" lon="1" lat="0"/>
+  <node id="3" lon="1" lat="1"/>
+  <node id="4" lon="0" lat="1"/>
+  <node id="11" lon="10" lat="10"/>
+  <node id="12" lon="20" lat="10"/>
+  <node id="13" lon="20" lat="20"/>
+  <node id="14" lon="10" lat="20"/>
+  <node id="21" lon="12" lat="12"/>
+  <node id="22" lon="14" lat="12"/>
+  <node id="23" lon="14" lat="14"/>
+  <node id="24" lon="12" lat="14"/>
+  <node id="31" lon="5" lat="5"/>
+  <node id="32" lon="6" lat="5"/>
+  <node id="33" lon="6" lat="6"/>
+  <way id="101"><nd ref="1"/><nd ref="2"/><nd ref="3"/></way>
+  <way id="102"><nd ref="1"/><nd ref="4"/><nd ref="3"/></way>
+  <way id="103"><nd ref="11"/><nd ref="12"/><nd ref="13"/><nd ref="14"/><nd ref="11"/></way>
+  <way id="104"><nd ref="21"/><nd ref="22"/><nd ref="23"/></way>
+  <way id="105"><nd ref="21"/><nd ref="24"/><nd ref="23"/></way>
+  <way id="106"><nd ref="31"/><nd ref="32"/><nd ref="33"/></way>
+  <relation id="201">
+    <member type="way" ref="101" role="outer"/>
+    <member type="way" ref="106" role="outer"/>
+    <member type="way" ref="104" role="inner"/>
+    <member type="way" ref="102" role="outer"/>
+    <member type="way" ref="103" role=""/>
+    <member type="way" ref="105" role="inner"/>
+    <tag k="type" v="multipolygon"/>
+    <tag k="landuse" v="forest"/>
+  </relation>
+</osm>
+"#;
+
+    fn read(name: &str, content: &str) -> Osm {
+        let dir = temp_dir().join(format!("geo-munge-osm-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        write(&path, content).unwrap();
+        let osm = read_osm(&path);
+        remove_file(&path).unwrap();
+        osm.unwrap()
+    }
+
+    /// The ring in degrees, starting from its lowest node so rings can be compared.
+    fn ring(line: &LineString) -> Vec<(i64, i64)> {
+        let mut coords: Vec<_> = line
+            .coords()
+            .map(|c| {
+                (
+                    c.x.to_degrees().round() as i64,
+                    c.y.to_degrees().round() as i64,
+                )
+            })
+            .collect();
+        coords.pop();
+        let start = (0..coords.len()).min_by_key(|&i| coords[i]).unwrap();
+        coords.rotate_left(start);
+        coords
+    }
+
+    fn is_ring(ring: &[i64], nodes: &[i64]) -> bool {
+        ring.first() == ring.last()
+            && ring.len() == nodes.len() + 1
+            && nodes.iter().all(|n| ring.contains(n))
+    }
+
+    #[test]
+    fn ways_are_joined_into_rings() {
+        let rings = join_rings(vec![vec![1, 2, 3], vec![1, 4, 3], vec![5, 6, 5, 7]]);
+        assert_eq!(rings.len(), 1);
+        assert!(is_ring(&rings[0], &[1, 2, 3, 4]));
+
+        let rings = join_rings(vec![vec![3, 4, 1], vec![1, 2], vec![3, 2]]);
+        assert_eq!(rings.len(), 1);
+        assert!(is_ring(&rings[0], &[1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn unclosable_ways_are_dropped() {
+        let rings = join_rings(vec![vec![1, 2, 3], vec![11, 12, 13, 11], vec![3, 4]]);
+        assert_eq!(rings, vec![vec![11, 12, 13, 11]]);
+    }
+
+    #[test]
+    fn multipolygon() {
+        let osm = read("multipolygon.osm", MULTIPOLYGON);
+        let features: Vec<_> = osm.features(&[]).collect();
+        assert_eq!(features.len(), 1);
+
+        let (geoms, record) = &features[0];
+        assert_eq!(record.kind, OsmKind::Relation);
+        assert_eq!(record.field("landuse"), "forest");
+
+        let mut polygons: Vec<_> = geoms
+            .iter()
+            .map(|g| match g {
+                Geometry::Polygon(p) => (
+                    ring(p.exterior()),
+                    p.interiors().iter().map(ring).collect::<Vec<_>>(),
+                ),
+                _ => panic!("{g:?}"),
+            })
+            .collect();
+        polygons.sort();
+
+        assert_eq!(
+            polygons,
+            vec![
+                (vec![(0, 0), (0, 1), (1, 1), (1, 0)], vec![]),
+                (
+                    vec![(10, 10), (20, 10), (20, 20), (10, 20)],
+                    vec![vec![(12, 12), (12, 14), (14, 14), (14, 12)]]
+                ),
+            ]
+        );
+    }
+}
@@ -1,15 +1,24 @@
-use std::{collections::HashMap, fs::File, io::BufReader, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    iter::once,
+    path::PathBuf,
+    sync::Arc,
+};
 
-use csv::{ReaderBuilder, StringRecord};
+use csv::{Reader, ReaderBuilder, StringRecord};
 use geo::Point;
 use quadtree::Geometry;
+use serde_json::Value;
 
+use crate::encoding::TextEncoding;
 use crate::error::{Error, ParseType};
+use crate::json::{lookup, parse_path, value_to_string, Segment};
+use crate::wkt::{flatten_geometry, parse_geometry_text};
 
-use super::{
-    datum::{BaseData, Datum},
-    QtData, Quadtree,
-};
+use super::datum::{BaseData, Datum};
+use super::source::{DatumIter, Source};
 
 /// Test point, id field, and metadata extracted from an input comparison point.
 pub struct ParsedRecord {
@@ -27,50 +36,256 @@ pub struct ParsedRecord {
     pub id: Option<String>,
 }
 
-pub fn csv_field_val(record: &HashMap<String, String>, field: &String) -> String {
-    record.get(field).map(|s| s.to_string()).unwrap_or_default()
+/// Make an output string from a field name and a record. Fields that are not a column can be a
+/// path into a column holding JSON text, such as `tags[0]` or `address.city`.
+pub fn csv_field_val(
+    record: &HashMap<String, String>,
+    field: &String,
+    nested_json: bool,
+) -> String {
+    if let Some(value) = record.get(field) {
+        return value.to_string();
+    }
+
+    parse_path(field)
+        .and_then(|segments| match segments.split_first()? {
+            (Segment::Key(column), rest) => {
+                let value = serde_json::from_str::<Value>(record.get(*column)?).ok()?;
+                Some(value_to_string(lookup(&value, rest), nested_json))
+            }
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Delimiters that are detected from the header row, the most frequent of which is used.
+pub const DELIMITERS: [u8; 4] = [b',', b'\t', b';', b'|'];
+
+/// Detect the delimiter of a header row as the most frequent of the [`DELIMITERS`], or None if the
+/// row contains none of them. Ties go to the earliest in the list.
+pub fn detect_delimiter(header: &str) -> Option<u8> {
+    let count = |d: u8| header.bytes().filter(|b| *b == d).count();
+
+    DELIMITERS
+        .into_iter()
+        .filter(|d| count(*d) > 0)
+        .rev()
+        .max_by_key(|d| count(*d))
+}
+
+/// Headers that are recognised as a geometry column when there are no lng and lat headers.
+const GEOMETRY_HEADERS: [&str; 4] = ["wkt", "wkb", "geometry", "geom"];
+
+/// Where the geometry of each row is read from, as column indexes.
+#[derive(Clone, Copy, Debug)]
+pub enum CsvLocation {
+    LngLat((usize, usize)),
+    Geometry(usize),
+}
+
+/// Options for reading CSV reference data.
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    /// The field delimiter, detected from the header row with [`detect_delimiter`] when not set,
+    /// and otherwise a comma.
+    pub delimiter: Option<u8>,
+
+    /// The quote character.
+    pub quote: u8,
+
+    /// Whether the first row is a header row. Files without headers must set `lng_lat`, and their
+    /// metadata fields are named by the 0-indexed column position.
+    pub has_headers: bool,
+
+    /// Positional indexes of the lng and lat columns, used instead of looking up the lng and lat
+    /// headers.
+    pub lng_lat: Option<(usize, usize)>,
+
+    /// Column holding a WKT or hex WKB geometry for each row, used instead of lng and lat. Files
+    /// without headers name the column by its 0-indexed position.
+    pub geometry: Option<String>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: None,
+            quote: b'"',
+            has_headers: true,
+            lng_lat: None,
+            geometry: None,
+        }
+    }
+}
+
+/// [`Source`] for CSV files. Points are read from case insensitive lat and lng column headers, or
+/// from the column positions set in the [`CsvOptions`]. Other geometries can be read from a column
+/// of WKT or hex WKB, either set in the options or found from a wkt, wkb, geometry or geom header
+/// when there are no lng and lat headers.
+pub struct CsvSource {
+    path: PathBuf,
+    opts: CsvOptions,
+    encoding: TextEncoding,
 }
 
-// CSVs as input data only support points based on a case insensitive lat and lng field as column
-// headers in the input file.
-pub fn build_csv(path: PathBuf, opts: QtData) -> Result<Quadtree, Error> {
-    let file = BufReader::new(File::open(path.clone()).map_err(|_| Error::CannotReadFile(path))?);
-    let mut reader = ReaderBuilder::new()
-        .has_headers(true)
-        // TODO: Can we pass the delimiter from the args to this function?
-        .delimiter(b',')
-        .from_reader(file);
-
-    // We need to store the headers with each record to ensure that we can extract any metadata on
-    // retrieval, then get the indicies of the lng and the lat from these headers
-    let headers = reader
-        .headers()
-        .map_err(|err| Error::CsvParseError(err))?
-        .to_owned();
-    let lng_lat_i = get_lng_lat_index(&headers)?;
-
-    // Run through all the records producing datums for all valid data
-    let mut qt = Quadtree::new(opts);
-    let results = reader.into_records().enumerate().map(|(i, res)| {
-        res.map_err(|_| Error::CannotParseRecord(i, ParseType::Csv))
-            .and_then(|record| {
-                Ok(Datum::new(
-                    point_from_record(&record, i, lng_lat_i)?,
-                    BaseData::Csv(make_record_map(&record, &headers)),
-                    i,
-                ))
+impl CsvSource {
+    /// Build the source for the csv at `path`, decoding it from `encoding`. A UTF-8 byte order
+    /// mark is always stripped.
+    pub fn new(path: PathBuf, opts: CsvOptions, encoding: TextEncoding) -> Self {
+        Self {
+            path,
+            opts,
+            encoding,
+        }
+    }
+
+    /// A csv reader over the decoded file, using the delimiter, quote and headers options.
+    pub fn reader(&self) -> Result<Reader<Box<dyn Read>>, Error> {
+        Ok(ReaderBuilder::new()
+            .has_headers(self.opts.has_headers)
+            .delimiter(self.delimiter()?)
+            .quote(self.opts.quote)
+            .from_reader(self.decoded()?))
+    }
+
+    /// The delimiter set in the options, or otherwise detected from the first row.
+    pub fn delimiter(&self) -> Result<u8, Error> {
+        if let Some(delimiter) = self.opts.delimiter {
+            return Ok(delimiter);
+        }
+
+        let mut header = String::new();
+        BufReader::new(self.decoded()?)
+            .read_line(&mut header)
+            .map_err(|_| Error::CannotReadFile(self.path.clone()))?;
+
+        Ok(detect_delimiter(&header).unwrap_or(b','))
+    }
+
+    /// The file decoded to UTF-8.
+    fn decoded(&self) -> Result<Box<dyn Read>, Error> {
+        let file = BufReader::new(
+            File::open(self.path.clone()).map_err(|_| Error::CannotReadFile(self.path.clone()))?,
+        );
+
+        self.encoding
+            .reader(file)
+            .map_err(|_| Error::CannotReadFile(self.path.clone()))
+    }
+
+    /// Find where the geometry of each row is read from.
+    pub fn location(&self) -> Result<CsvLocation, Error> {
+        let mut reader = self.reader()?;
+        let headers = self.headers(&mut reader)?;
+
+        self.locate(&headers)
+    }
+
+    /// Find where the geometry of each row is read from the `headers`. Explicit options take
+    /// priority, then lng and lat headers, then a geometry header.
+    fn locate(&self, headers: &StringRecord) -> Result<CsvLocation, Error> {
+        let find = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+
+        if let Some(column) = &self.opts.geometry {
+            return find(column)
+                .map(CsvLocation::Geometry)
+                .ok_or_else(|| Error::MissingGeometryField(column.to_string()));
+        }
+        if let Some(lng_lat_i) = self.opts.lng_lat {
+            return Ok(CsvLocation::LngLat(lng_lat_i));
+        }
+
+        get_lng_lat_index(headers)
+            .map(CsvLocation::LngLat)
+            .or_else(|err| {
+                GEOMETRY_HEADERS
+                    .iter()
+                    .find_map(|&h| find(h))
+                    .map(CsvLocation::Geometry)
+                    .ok_or(err)
             })
-            .and_then(|datum| qt.insert(datum))
-    });
+    }
 
-    for res in results {
-        match res {
-            Err(err) => eprintln!("{err}"),
-            Ok(_) => (),
+    /// The headers of the file, or the column positions when the file has no headers.
+    fn headers(&self, reader: &mut Reader<Box<dyn Read>>) -> Result<StringRecord, Error> {
+        let headers = reader.headers().map_err(|err| Error::CsvParseError(err))?;
+
+        if self.opts.has_headers {
+            Ok(headers.to_owned())
+        } else {
+            Ok((0..headers.len()).map(|i| i.to_string()).collect())
         }
     }
+}
+
+impl Source for CsvSource {
+    fn datums(&mut self) -> Result<DatumIter<'_>, Error> {
+        let mut reader = self.reader()?;
+
+        // We need to store the headers with each record to ensure that we can extract any
+        // metadata on retrieval, then find where the geometry is from these headers
+        let headers = self.headers(&mut reader)?;
+        let location = self.locate(&headers)?;
+
+        // Run through all the records producing datums for all valid data
+        Ok(Box::new(reader.into_records().enumerate().flat_map(
+            move |(i, res)| -> DatumIter<'static> {
+                let record = match res {
+                    Ok(record) => record,
+                    Err(_) => {
+                        return Box::new(once(Err(Error::CannotParseRecord(i, ParseType::Csv))))
+                    }
+                };
 
-    Ok(qt)
+                match location {
+                    CsvLocation::LngLat(lng_lat_i) => Box::new(once(
+                        point_from_record(&record, i, lng_lat_i).map(|point| {
+                            Datum::new(
+                                point,
+                                BaseData::Csv(Arc::new(make_record_map(&record, &headers))),
+                                i,
+                            )
+                        }),
+                    )),
+                    CsvLocation::Geometry(geom_i) => {
+                        let geometry = parse_geometry_text(record.get(geom_i).unwrap_or_default())
+                            .map_err(|_| Error::CannotParseRecord(i, ParseType::Wkt));
+                        match geometry {
+                            Ok(geometry) => {
+                                let parts = flatten_geometry(geometry);
+                                if parts.is_empty() {
+                                    return Box::new(once(Err(Error::CannotParseRecord(
+                                        i,
+                                        ParseType::MissingGeometry,
+                                    ))));
+                                }
+                                let meta = Arc::new(make_record_map(&record, &headers));
+                                Box::new(parts.into_iter().map(move |g| {
+                                    Ok(Datum::new(g, BaseData::Csv(Arc::clone(&meta)), i))
+                                }))
+                            }
+                            Err(err) => Box::new(once(Err(err))),
+                        }
+                    }
+                }
+            },
+        )))
+    }
+
+    /// Fields are the lower-cased column headers, or the column positions without headers.
+    fn fields(&self) -> Result<Option<Vec<String>>, Error> {
+        let headers = self
+            .headers(&mut self.reader()?)?
+            .iter()
+            .map(|h| h.to_lowercase())
+            .collect();
+
+        Ok(Some(headers))
+    }
+
+    fn count(&self) -> Result<Option<usize>, Error> {
+        Ok(Some(self.reader()?.into_records().count()))
+    }
 }
 
 // Make sure we caputure the index of the lat and the lng fields, terminating if they are not
@@ -102,14 +317,18 @@ fn point_from_record(
     index: usize,
     (lng_index, lat_index): (usize, usize),
 ) -> Result<Geometry<f64>, Error> {
+    // Positional indexes may be out of range for short rows, so treat a missing value as a parse
+    // failure rather than panicking
     let lng = record
         .get(lng_index)
-        .unwrap()
+        .unwrap_or_default()
+        .trim()
         .parse::<f64>()
         .map_err(|_| Error::CannotParseRecord(index, ParseType::Lng))?;
     let lat = record
         .get(lat_index)
-        .unwrap()
+        .unwrap_or_default()
+        .trim()
         .parse::<f64>()
         .map_err(|_| Error::CannotParseRecord(index, ParseType::Lat))?;
 
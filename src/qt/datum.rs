@@ -5,10 +5,13 @@ use geojson::Feature;
 use quadtree::{AsGeom, AsPoint, Geometry, GeometryRef};
 use shapefile::dbase::Record;
 
+use crate::gpx::GpxRecord;
 use crate::kml::KmlItem;
+use crate::osm::OsmRecord;
 
 use super::{
-    csv::csv_field_val, geojson::json_field_val, kml::kml_field_val, shapefile::shp_field_val,
+    csv::csv_field_val, geojson::json_field_val, gpx::gpx_field_val, kml::kml_field_val,
+    osm::osm_field_val, shapefile::shp_field_val,
 };
 
 /// Datum to store in the quadtree, includes the index from the input file and the underlying data
@@ -28,12 +31,18 @@ impl Datum {
         self.index
     }
 
+    /// The geometry of the datum, in radians.
+    pub fn geometry(&self) -> &Geometry<f64> {
+        &self.geom
+    }
+
     /// Pass through to the underlying meta implementation for building the field string.
     pub fn meta_iter<'a>(
         &'a self,
         fields: &'a Option<Vec<String>>,
+        nested_json: bool,
     ) -> Box<dyn Iterator<Item = String> + 'a> {
-        self.base.iter_str(fields)
+        self.base.iter_str(fields, nested_json)
     }
 }
 
@@ -61,29 +70,38 @@ impl AsPoint for Datum {
 pub enum BaseData {
     Shp(Arc<Record>),
     Json(Arc<Feature>),
-    // Kml doesn't need RC because in this setup there are no mutligeometries with metadata that
-    // get broken up with each requiring a reference to the KmlItem
-    Kml(KmlItem),
-    // Csvs only support points and therefore can never be spilt apart
-    // But we have to store the records as a hasmap for efficient lookup later
-    Csv(HashMap<String, String>),
+    // Placemarks with MultiGeometries are broken up into parts that each require a reference to
+    // the Placemark
+    Kml(Arc<KmlItem>),
+    // Csv records are stored as a hashmap for efficient lookup later. Rows with a multi-part
+    // geometry column are broken up into parts that each require a reference to the record
+    Csv(Arc<HashMap<String, String>>),
+    // Tracks are broken up into segments that each require a reference to the track
+    Gpx(Arc<GpxRecord>),
+    // Multipolygon relations are broken up into polygons that each require a reference to the
+    // relation
+    Osm(Arc<OsmRecord>),
     None,
 }
 
 impl BaseData {
     /// Iterate through the stored underlying data only retrieving a `String` version of metadata
-    /// fields matching the keys provided in the `fields` vector.
+    /// fields matching the keys provided in the `fields` vector. Nested JSON values are written as
+    /// compact JSON text when `nested_json` is set, and are empty otherwise.
     pub fn iter_str<'a>(
         &'a self,
         fields: &'a Option<Vec<String>>,
+        nested_json: bool,
     ) -> Box<dyn Iterator<Item = String> + 'a> {
         // Deal with the case that there are no fields first
         if let Some(fields) = fields {
             Box::new(fields.iter().map(move |f| match self {
                 Self::Shp(record) => shp_field_val(record, f),
-                Self::Json(feature) => json_field_val(feature, f),
+                Self::Json(feature) => json_field_val(feature, f, nested_json),
                 Self::Kml(kml) => kml_field_val(kml, f),
-                Self::Csv(record) => csv_field_val(record, f),
+                Self::Csv(record) => csv_field_val(record, f, nested_json),
+                Self::Gpx(record) => gpx_field_val(record, f),
+                Self::Osm(record) => osm_field_val(record, f),
                 Self::None => String::default(),
             }))
         } else {
@@ -1,20 +1,22 @@
+use std::collections::BTreeSet;
 use std::iter::once;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use geo::Point;
+use geo::{Point, Rect};
 use geojson::feature::Id;
 use geojson::{Feature, GeoJson};
-use serde_json::Value;
 
 use crate::error::{Error, ParseType};
-use crate::geojson::{convert_geom, read_geojson};
+use crate::geojson::{convert_geom, stream_geojson};
+use crate::json::{lookup_field, value_to_string};
 
 use super::datum::{BaseData, Datum};
-use super::QtData;
-use super::Quadtree;
+use super::source::{DatumIter, RecordIter, Source};
 
-pub fn json_field_val(feature: &Feature, field: &String) -> String {
+/// Make an output string from a field name and a Feature. Fields can be paths into nested
+/// properties, such as `address.city` or `tags[0]`, optionally starting with `properties.`.
+pub fn json_field_val(feature: &Feature, field: &String, nested_json: bool) -> String {
     // Special handling of id as it is a named property
     if field == "id" {
         match &feature.id {
@@ -23,76 +25,123 @@ pub fn json_field_val(feature: &Feature, field: &String) -> String {
             None => String::default(),
         }
     } else if let Some(props) = &feature.properties {
-        match props.get(field) {
-            Some(Value::Null) => String::default(),
-            Some(Value::Number(n)) => n.to_string(),
-            Some(Value::String(s)) => s.to_owned(),
-            Some(Value::Bool(b)) => b.to_string(),
-            Some(Value::Array(_)) => String::default(),
-            Some(Value::Object(_)) => String::default(),
-            None => String::default(),
-        }
+        let value = lookup_field(props, field).or_else(|| {
+            field
+                .strip_prefix("properties.")
+                .and_then(|f| lookup_field(props, f))
+        });
+        value_to_string(value, nested_json)
     } else {
         String::default()
     }
 }
 
-pub fn build_geojson(path: PathBuf, opts: QtData) -> Result<Quadtree, Error> {
-    let geojson = read_geojson(&path)?;
-    let mut qt = Quadtree::new(opts);
-
-    // Create an iterator that runs through and flattens all geometries in the GeoJson, preparing
-    // them for adding to the qt
-    let geometries: Box<dyn Iterator<Item = _>> = match geojson {
-        GeoJson::Geometry(g) => {
-            // Note that geometries don't contain any metadata, so using the None meta here
-            Box::new(convert_geom(&g).map(|res| {
-                res.map(|geom| Datum::new(geom, BaseData::None, 0))
-                    .map_err(|_| Error::CannotParseRecord(0, ParseType::GeoJson))
-            }))
-        }
-        GeoJson::Feature(f) => {
-            // For features, there is still only a single index and geometry is an option
-            map_feature((0, f))
+/// [`Source`] for GeoJSON files. Geometries have no metadata, Features and FeatureCollections use
+/// the Feature id and properties.
+pub struct GeoJsonSource {
+    path: PathBuf,
+}
+
+impl GeoJsonSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Source for GeoJsonSource {
+    /// Datums are streamed from the file one record at a time, so large FeatureCollections and
+    /// sequences never need to be held in memory whole.
+    fn datums(&mut self) -> Result<DatumIter<'_>, Error> {
+        let stream = stream_geojson(&self.path)?;
+
+        Ok(Box::new(stream.enumerate().flat_map(map_record)))
+    }
+
+    /// Geometries have no metadata, so have the None meta.
+    fn records(&mut self) -> Result<Option<RecordIter<'_>>, Error> {
+        let stream = stream_geojson(&self.path)?;
+
+        Ok(Some(Box::new(stream.map(|record| match record? {
+            GeoJson::Feature(f) => Ok(BaseData::Json(Arc::new(f))),
+            _ => Ok(BaseData::None),
+        }))))
+    }
+
+    /// The bbox of a FeatureCollection may come after the `features` array, in which case the
+    /// Features are skipped through to find it. Sequences have no overall bbox.
+    fn bbox(&self) -> Result<Option<Rect>, Error> {
+        let mut stream = stream_geojson(&self.path)?;
+        if stream.collection().is_some_and(|fc| fc.bbox.is_none()) {
+            stream.skip_features()?;
         }
-        GeoJson::FeatureCollection(fc) => {
-            // Feature collections we need to flatmap through the features vector and do the same
-            // thing as an individual feature
-            Box::new(fc.features.into_iter().enumerate().flat_map(map_feature))
+
+        let bbox = match stream.collection() {
+            Some(fc) => fc.bbox.clone(),
+            None => match stream.single().ok().flatten() {
+                Some(GeoJson::Feature(f)) => f.bbox,
+                Some(GeoJson::Geometry(g)) => g.bbox,
+                _ => None,
+            },
+        };
+
+        match bbox {
+            // Ensure that the bounding box has length 4 to guarantee we can build a proper
+            // bouding box
+            Some(bbox) if bbox.len() != 4 => Err(Error::InvalidBoundingBox),
+            Some(bbox) => Ok(Some(Rect::new(
+                Point::new(bbox[0], bbox[1]),
+                Point::new(bbox[2], bbox[3]),
+            ))),
+            None => Ok(None),
         }
-    };
+    }
+
+    /// Fields are the id and the first level of the properties of every Feature. Files holding
+    /// only Geometries have no fields.
+    fn fields(&self) -> Result<Option<Vec<String>>, Error> {
+        let mut has_features = false;
+        let mut fields = BTreeSet::new();
 
-    // Insert into the quadtree, chaining errors to print to stderr if the insertion fails
-    for datum in geometries {
-        if let Some(err) = datum.and_then(|d| qt.insert(d)).err() {
-            eprintln!("{err}");
+        for record in stream_geojson(&self.path)? {
+            if let GeoJson::Feature(f) = record? {
+                has_features = true;
+                if f.id.is_some() {
+                    fields.insert("id".to_string());
+                }
+                if let Some(props) = f.properties {
+                    fields.extend(props.into_iter().map(|(k, _)| k));
+                }
+            }
         }
+
+        Ok(has_features.then(|| fields.into_iter().collect()))
     }
 
-    Ok(qt)
+    fn count(&self) -> Result<Option<usize>, Error> {
+        Ok(Some(stream_geojson(&self.path)?.count()))
+    }
 }
 
-pub fn geojson_bbox(path: &PathBuf) -> Result<(Point, Point), Error> {
-    let json = read_geojson(path)?;
-    let bbox = match json {
-        GeoJson::Feature(f) => f.bbox,
-        GeoJson::Geometry(g) => g.bbox,
-        GeoJson::FeatureCollection(fc) => fc.bbox,
-    };
-    let bbox = bbox.ok_or(Error::MissingBoundingBox)?;
-
-    // Ensure that the bounding box has length 4 to guarantee we can build a proper
-    // bouding box
-    if bbox.len() != 2 {
-        return Err(Error::InvalidBoundingBox);
+/// Map a record from the stream and its index to an iterator of datums. Geometries don't contain
+/// any metadata, so use the None meta.
+fn map_record((i, record): (usize, Result<GeoJson, Error>)) -> DatumIter<'static> {
+    match record {
+        Ok(GeoJson::Feature(f)) => map_feature((i, f)),
+        Ok(GeoJson::Geometry(g)) => Box::new(convert_geom(&g).map(move |res| {
+            res.map(|geom| Datum::new(geom, BaseData::None, i))
+                .map_err(|_| Error::CannotParseRecord(i, ParseType::GeoJson))
+        })),
+        // The stream expands all FeatureCollections into their Features, so this is not expected
+        Ok(GeoJson::FeatureCollection(_)) => {
+            Box::new(once(Err(Error::CannotParseRecord(i, ParseType::GeoJson))))
+        }
+        Err(err) => Box::new(once(Err(err))),
     }
-
-    Ok((Point::new(bbox[0], bbox[1]), Point::new(bbox[2], bbox[3])))
 }
 
 /// Convenience function to build a feature iterator over a single geojson feature, using, for
 /// convenience, output in the form of an `enumerate` on an `Iterator`.
-fn map_feature((i, f): (usize, Feature)) -> Box<dyn Iterator<Item = Result<Datum, Error>>> {
+pub(super) fn map_feature((i, f): (usize, Feature)) -> DatumIter<'static> {
     // The feature needs to be an Rc so it can be duplicated into each datum
     let f = Arc::new(f);
 
@@ -0,0 +1,66 @@
+use std::iter::once;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use geo::Rect;
+
+use crate::error::{Error, ParseType};
+use crate::gpx::{gpx_features, read_gpx, GpxRecord, GPX_FIELDS};
+
+use super::datum::{BaseData, Datum};
+use super::source::{DatumIter, Source};
+
+pub fn gpx_field_val(record: &GpxRecord, field: &String) -> String {
+    record.field(field)
+}
+
+/// [`Source`] for GPX files. Waypoints, routes and tracks are each a feature, and the segments of a
+/// track are parts of the same feature.
+pub struct GpxSource {
+    path: PathBuf,
+}
+
+impl GpxSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Source for GpxSource {
+    fn datums(&mut self) -> Result<DatumIter<'_>, Error> {
+        let gpx = read_gpx(&self.path)?;
+
+        Ok(Box::new(gpx_features(gpx).enumerate().flat_map(
+            |(index, (geoms, record))| -> DatumIter<'static> {
+                if geoms.is_empty() {
+                    return Box::new(once(Err(Error::CannotParseRecord(
+                        index,
+                        ParseType::MissingGeometry,
+                    ))));
+                }
+
+                let record = Arc::new(record);
+                Box::new(geoms.into_iter().map(move |geom| {
+                    Ok(Datum::new(geom, BaseData::Gpx(Arc::clone(&record)), index))
+                }))
+            },
+        )))
+    }
+
+    /// The bounds from the file metadata.
+    fn bbox(&self) -> Result<Option<Rect>, Error> {
+        Ok(read_gpx(&self.path)?.metadata.and_then(|m| m.bounds))
+    }
+
+    fn fields(&self) -> Result<Option<Vec<String>>, Error> {
+        Ok(Some(GPX_FIELDS.iter().map(|f| f.to_string()).collect()))
+    }
+
+    fn count(&self) -> Result<Option<usize>, Error> {
+        let gpx = read_gpx(&self.path)?;
+
+        Ok(Some(
+            gpx.waypoints.len() + gpx.routes.len() + gpx.tracks.len(),
+        ))
+    }
+}
@@ -1,20 +1,19 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     iter::{once, Once},
     path::PathBuf,
+    sync::Arc,
 };
 
 use quadtree::{Geometry, ToRadians};
 
 use crate::{
     error::{Error, ParseType},
-    kml::{convert_kml_geom, Kml, KmlItem},
+    kml::{extended_data, flatten_kml_geom, Kml, KmlItem, KmlItemRef},
 };
 
-use super::{
-    datum::{BaseData, Datum},
-    QtData, Quadtree,
-};
+use super::datum::{BaseData, Datum};
+use super::source::{DatumIter, RecordIter, Source};
 
 /// Make output strings from a field name and the Kml item.
 pub fn kml_field_val(kml: &KmlItem, field: &String) -> String {
@@ -29,8 +28,19 @@ pub fn kml_field_val(kml: &KmlItem, field: &String) -> String {
                 p.name.to_owned().unwrap_or_default()
             } else if field == "description" {
                 p.description.to_owned().unwrap_or_default()
+            } else if let Some(value) = p.attrs.get(field) {
+                value.to_string()
+            } else if let Some((_, value)) =
+                extended_data(p).find(|(name, _)| *name == field.as_str())
+            {
+                value.to_string()
             } else {
-                make_string(&p.attrs, field)
+                // Fall back to the content of any unparsed child element
+                p.children
+                    .iter()
+                    .find(|c| &c.name == field)
+                    .and_then(|c| c.content.to_owned())
+                    .unwrap_or_default()
             }
         }
         KmlItem::MultiGeometry(_) => unreachable!("Nested MultiGeometries not allowed"),
@@ -41,26 +51,75 @@ fn make_string(attrs: &HashMap<String, String>, field: &String) -> String {
     attrs.get(field).map(|s| s.to_string()).unwrap_or_default()
 }
 
-/// Build the quadtree for kml-based input data
-pub fn build_kml(path: PathBuf, opts: QtData) -> Result<Quadtree, Error> {
-    let kml = Kml::from_path(&path)?;
-    let mut qt = Quadtree::new(opts);
+/// [`Source`] for KML and KMZ files. Only components that contain geometries are read.
+pub struct KmlSource {
+    path: PathBuf,
+}
+
+impl KmlSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Source for KmlSource {
+    fn datums(&mut self) -> Result<DatumIter<'_>, Error> {
+        let kml = Kml::from_path(&self.path)?;
+
+        Ok(Box::new(kml.into_iter().enumerate().flat_map(map_kml_item)))
+    }
 
-    // Insert into the qt, printing any errors to stdout
-    for datum in kml.into_iter().enumerate().flat_map(map_kml_item) {
-        if let Some(err) = datum.and_then(|d| qt.insert(d)).err() {
-            eprintln!("{err}");
+    /// Placemarks are read without their geometry, as in the datums. MultiGeometries outside a
+    /// Placemark take their metadata from each part, so have the None meta.
+    fn records(&mut self) -> Result<Option<RecordIter<'_>>, Error> {
+        let kml = Kml::from_path(&self.path)?;
+
+        Ok(Some(Box::new(kml.into_iter().map(|item| {
+            Ok(match item {
+                KmlItem::Placemark(mut p) => {
+                    p.geometry = None;
+                    BaseData::Kml(Arc::new(KmlItem::Placemark(p)))
+                }
+                KmlItem::MultiGeometry(_) => BaseData::None,
+                item => BaseData::Kml(Arc::new(item)),
+            })
+        }))))
+    }
+
+    /// Fields are only reported from Placemarks, which can always have a name and description, and
+    /// include the named attributes in their extended data and the synthetic folder path and style
+    /// attributes added by [`Kml::from_path`].
+    fn fields(&self) -> Result<Option<Vec<String>>, Error> {
+        let kml = Kml::from_path(&self.path)?;
+        let mut fields = BTreeSet::from(["name".to_string(), "description".to_string()]);
+
+        for item in kml.iter() {
+            if let KmlItemRef::Placemark(p) = item {
+                fields.extend(p.attrs.keys().cloned());
+                fields.extend(
+                    p.children
+                        .iter()
+                        .filter(|c| c.name != "ExtendedData")
+                        .map(|c| c.name.to_string()),
+                );
+                fields.extend(extended_data(p).map(|(name, _)| name.to_string()));
+            }
         }
+
+        Ok(Some(fields.into_iter().collect()))
     }
 
-    Ok(qt)
+    fn count(&self) -> Result<Option<usize>, Error> {
+        Ok(Some(Kml::from_path(&self.path)?.into_iter().count()))
+    }
 }
 
 /// Map from a [`KmlItem`] and its associated index to an iterator of [`IndexedDatum`]. Most items
-/// are wrapped in a single item iterator, but multi-kml types are expanded. This relies on copying
+/// are wrapped in a single item iterator, but MultiGeometries are flattened into their parts at any
+/// depth. Parts of a Placemark share the Placemark's metadata and index. This relies on copying
 /// which is both time and space inefficient for large geometries, but this is required in order to
 /// keep both.
-fn map_kml_item((index, item): (usize, KmlItem)) -> Box<dyn Iterator<Item = Result<Datum, Error>>> {
+fn map_kml_item((index, item): (usize, KmlItem)) -> DatumIter<'static> {
     match item {
         KmlItem::Point(ref p) => {
             let mut geo = geo::Point::from(p.clone());
@@ -82,29 +141,41 @@ fn map_kml_item((index, item): (usize, KmlItem)) -> Box<dyn Iterator<Item = Resu
             geo.to_radians_in_place();
             bood(Geometry::LineString(geo), item, index)
         }
-        KmlItem::Placemark(p) => Box::new(once(
-            p.geometry
-                .clone()
-                .ok_or(Error::CannotParseRecord(index, ParseType::MissingGeometry))
-                .and_then(convert_kml_geom)
-                .map(|(geom, _)| Datum::new(geom, BaseData::Kml(KmlItem::Placemark(p)), index)),
-        )),
-        KmlItem::Location(ref l) => bood(
-            Geometry::Point(geo::point! {
-                x: l.latitude,
-                y: l.longitude,
+        // The geometry is taken out of the Placemark as the metadata does not need it
+        KmlItem::Placemark(mut p) => match p.geometry.take() {
+            Some(geometry) => {
+                let meta = Arc::new(KmlItem::Placemark(p));
+                Box::new(flatten_kml_geom(geometry).map(move |res| {
+                    res.map(|(geom, _)| Datum::new(geom, BaseData::Kml(Arc::clone(&meta)), index))
+                }))
+            }
+            None => Box::new(once(Err(Error::CannotParseRecord(
+                index,
+                ParseType::MissingGeometry,
+            )))),
+        },
+        KmlItem::Location(ref l) => {
+            let mut geo = geo::point! {
+                x: l.longitude,
+                y: l.latitude,
+            };
+            geo.to_radians_in_place();
+            bood(Geometry::Point(geo), item, index)
+        }
+        KmlItem::MultiGeometry(mg) => Box::new(
+            flatten_kml_geom(kml::types::Geometry::MultiGeometry(mg)).map(move |res| {
+                res.map(|(geom, meta)| Datum::new(geom, BaseData::Kml(Arc::new(meta)), index))
             }),
-            item,
-            index,
         ),
-        KmlItem::MultiGeometry(mg) => Box::new(mg.geometries.into_iter().map(move |g| {
-            convert_kml_geom(g).map(|(geom, meta)| Datum::new(geom, BaseData::Kml(meta), index))
-        })),
     }
 }
 
 /// (B)ox (O)nce (O)k (D)atum. Convenience function to wrap the inputs in the appropriate
 /// containers for subsequent use.
 fn bood(geom: Geometry<f64>, meta: KmlItem, index: usize) -> Box<Once<Result<Datum, Error>>> {
-    Box::new(once(Ok(Datum::new(geom, BaseData::Kml(meta), index))))
+    Box::new(once(Ok(Datum::new(
+        geom,
+        BaseData::Kml(Arc::new(meta)),
+        index,
+    ))))
 }
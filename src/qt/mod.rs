@@ -2,32 +2,39 @@ pub mod datum;
 
 mod csv;
 mod geojson;
+mod gpx;
 mod kml;
+mod osm;
+mod query;
 mod shapefile;
+mod source;
+mod topojson;
+mod wkt;
 
-use std::path::PathBuf;
+use std::{collections::HashSet, path::PathBuf};
 
 use geo::{Point, Rect};
 use quadtree::{
-    AsGeom, BoundsQuadTree, CalcMethod, GeometryRef, PointQuadTree, QuadTree as QT, QuadTreeSearch,
-    ToRadians,
+    AsGeom, AsPoint, BoundsQuadTree, CalcMethod, GeometryRef, PointQuadTree, QuadTree as QT,
+    QuadTreeSearch, ToRadians,
 };
+use rayon::prelude::*;
 
 use crate::error::Error;
 use datum::*;
 
-use self::csv::build_csv;
-use self::geojson::{build_geojson, geojson_bbox};
-use self::kml::build_kml;
-use self::shapefile::{build_shp, shp_bbox};
+use self::query::Indexed;
 
-pub use self::csv::ParsedRecord;
+pub use self::csv::{CsvLocation, CsvOptions, CsvSource, ParsedRecord};
+pub use self::query::{Degrees, Query};
+pub use self::source::{open_source, DatumIter, Format, RecordIter, Source, SourceOptions};
 
 pub struct QtData {
     pub is_point_qt: bool,
     pub bounds: Rect<f64>,
     pub depth: u8,
     pub max_children: usize,
+    pub source: SourceOptions,
 }
 
 impl QtData {
@@ -36,12 +43,14 @@ impl QtData {
         bounds: Rect,
         depth: Option<u8>,
         max_children: Option<usize>,
+        source: SourceOptions,
     ) -> Self {
         Self {
             is_point_qt: is_bounds,
             bounds,
             depth: depth.unwrap_or(10),
             max_children: max_children.unwrap_or(10),
+            source,
         }
     }
 }
@@ -67,6 +76,7 @@ impl Quadtree {
             bounds,
             depth,
             max_children,
+            ..
         } = opts;
 
         if is_point_qt {
@@ -86,18 +96,19 @@ impl Quadtree {
         }
     }
 
+    /// Build the quadtree from the file at `path`, read with the source options in `opts`.
     pub fn from_path(path: PathBuf, opts: QtData) -> Result<Self, Error> {
-        match path
-            .extension()
-            .and_then(|e| e.to_str())
-            .ok_or(Error::CannotParseFileExtension(path.clone()))?
-        {
-            "shp" => build_shp(path, opts),
-            "json" => build_geojson(path, opts),
-            "kml" | "kmz" => build_kml(path, opts),
-            "csv" => build_csv(path, opts),
-            _ => Err(Error::UnsupportedFileType),
+        let mut source = open_source(&path, &opts.source)?;
+        let mut qt = Quadtree::new(opts);
+
+        // Insert into the quadtree, chaining errors to print to stderr if the insertion fails
+        for datum in source.datums()? {
+            if let Some(err) = datum.and_then(|d| qt.insert(d)).err() {
+                eprintln!("{err}");
+            }
         }
+
+        Ok(qt)
     }
 
     pub fn size(&self) -> usize {
@@ -128,29 +139,184 @@ impl Quadtree {
         }
     }
 
-    pub fn find<'a>(
+    /// Find the nearest datum to the query, optionally constrained to the radius `r`.
+    ///
+    /// The query can be a [`ParsedRecord`] from a csv input stream, or a plain point or geometry
+    /// in radians, searched from its centroid. Queries in degrees can be wrapped in [`Degrees`].
+    /// Distances in the result are in radians.
+    pub fn find<'a, Q>(&'a self, query: &Q, r: Option<f64>) -> Result<SearchResult<'a>, Error>
+    where
+        Q: Query + ?Sized,
+    {
+        let (index, point) = query_point(query)?;
+        match self {
+            Self::Bounds(b) => b.find_r(&point, r.unwrap_or(f64::INFINITY)),
+            Self::Point(p) => p.find_r(&point, r.unwrap_or(f64::INFINITY)),
+        }
+        .map_err(|err| Error::FindError(index, err))
+    }
+
+    /// Find the `k` nearest datums to the query, optionally constrained to the radius `r`,
+    /// ordered by distance.
+    pub fn knn<'a, Q>(
         &'a self,
-        record: &ParsedRecord,
+        query: &Q,
+        k: usize,
         r: Option<f64>,
-    ) -> Result<SearchResult<'a>, Error> {
+    ) -> Result<Vec<SearchResult<'a>>, Error>
+    where
+        Q: Query + ?Sized,
+    {
+        let (index, point) = query_point(query)?;
         match self {
-            Self::Bounds(b) => b.find_r(&record.point, r.unwrap_or(f64::INFINITY)),
-            Self::Point(p) => p.find_r(&record.point, r.unwrap_or(f64::INFINITY)),
+            Self::Bounds(b) => b.knn_r(&point, k, r.unwrap_or(f64::INFINITY)),
+            Self::Point(p) => p.knn_r(&point, k, r.unwrap_or(f64::INFINITY)),
         }
-        .map_err(|err| Error::FindError(record.index, err))
+        .map_err(|err| Error::FindError(index, err))
+    }
+
+    /// Run [`Quadtree::find`] for each of the queries in parallel, returning the results in the
+    /// same order as the queries. Errors report the position of the query in the slice.
+    pub fn find_batch<'a, Q>(
+        &'a self,
+        queries: &[Q],
+        r: Option<f64>,
+    ) -> Vec<Result<SearchResult<'a>, Error>>
+    where
+        Q: Query + Sync,
+    {
+        queries
+            .par_iter()
+            .enumerate()
+            .map(|(i, query)| self.find(&Indexed(i, query), r))
+            .collect()
+    }
+
+    /// Run [`Quadtree::knn`] for each of the queries in parallel, returning the results in the
+    /// same order as the queries. Errors report the position of the query in the slice.
+    pub fn knn_batch<'a, Q>(
+        &'a self,
+        queries: &[Q],
+        k: usize,
+        r: Option<f64>,
+    ) -> Vec<Result<Vec<SearchResult<'a>>, Error>>
+    where
+        Q: Query + Sync,
+    {
+        queries
+            .par_iter()
+            .enumerate()
+            .map(|(i, query)| self.knn(&Indexed(i, query), k, r))
+            .collect()
     }
 
-    pub fn knn<'a>(
+    /// Retrieve the `k` nearest distinct source features.
+    ///
+    /// Multi-part geometries are split into several datums sharing the same index, so a plain knn
+    /// can return the same feature more than once. Here only the nearest part for each index is
+    /// kept, and the search is widened until `k` distinct features are found or the quadtree is
+    /// exhausted.
+    pub fn knn_distinct<'a, Q>(
         &'a self,
-        record: &ParsedRecord,
+        query: &Q,
         k: usize,
         r: Option<f64>,
+    ) -> Result<Vec<SearchResult<'a>>, Error>
+    where
+        Q: Query + ?Sized,
+    {
+        let size = self.size();
+        let mut n = k.min(size).max(1);
+
+        loop {
+            let results = self.knn(query, n, r)?;
+            // Fewer results than requested means we have hit the radius or the full tree
+            let exhausted = results.len() < n || n >= size;
+
+            // Results are ordered by distance, so the first datum seen for an index is nearest
+            let mut seen = HashSet::new();
+            let distinct: Vec<_> = results
+                .into_iter()
+                .filter(|(datum, _)| seen.insert(datum.index()))
+                .take(k)
+                .collect();
+
+            if distinct.len() == k || exhausted {
+                return Ok(distinct);
+            }
+
+            n = n.saturating_mul(2).min(size);
+        }
+    }
+
+    /// Retrieve every datum within the radius `r` of the point, ordered by distance. Unlike the
+    /// other searches, finding nothing in range is not an error and produces an empty vector. The
+    /// index is used for error reporting only.
+    pub fn within<'a>(
+        &'a self,
+        point: &Point,
+        index: usize,
+        r: f64,
     ) -> Result<Vec<SearchResult<'a>>, Error> {
-        match self {
-            Self::Bounds(b) => b.knn_r(&record.point, k, r.unwrap_or(f64::INFINITY)),
-            Self::Point(p) => p.knn_r(&record.point, k, r.unwrap_or(f64::INFINITY)),
+        let k = self.size();
+        let results = match self {
+            Self::Bounds(b) => b.knn_r(point, k, r),
+            Self::Point(p) => p.knn_r(point, k, r),
+        };
+
+        match results {
+            Err(quadtree::Error::NoneInRadius) => Ok(Vec::new()),
+            res => res.map_err(|err| Error::FindError(index, err)),
+        }
+    }
+
+    /// Find the nearest datum to one that is itself stored in the quadtree, skipping the datum.
+    ///
+    /// As the query must be a point, only point datums are supported. A datum is considered to
+    /// be the query datum if it shares the index and the point, and if `exclude_parts` is set then
+    /// any other part of the same source feature is skipped too.
+    pub fn find_neighbour<'a>(
+        &'a self,
+        datum: &Datum,
+        exclude_parts: bool,
+        r: Option<f64>,
+    ) -> Result<SearchResult<'a>, Error> {
+        let index = datum.index();
+        if !matches!(datum.as_geom(), GeometryRef::Point::<f64>(_)) {
+            return Err(Error::QueryRequiresPoint(index));
+        }
+        let point = datum.as_point();
+
+        let is_self = |found: &Datum| {
+            found.index() == index
+                && (exclude_parts
+                    || (matches!(found.as_geom(), GeometryRef::Point::<f64>(_))
+                        && found.as_point() == point))
+        };
+
+        // Start by looking for one more than the query itself, widening until we find a
+        // neighbour or run out of datums
+        let size = self.size();
+        let r = r.unwrap_or(f64::INFINITY);
+        let mut n = 2.min(size).max(1);
+
+        loop {
+            let results = match self {
+                Self::Bounds(b) => b.knn_r(&point, n, r),
+                Self::Point(p) => p.knn_r(&point, n, r),
+            }
+            .map_err(|err| Error::FindError(index, err))?;
+            let exhausted = results.len() < n || n >= size;
+
+            if let Some(found) = results.into_iter().find(|(found, _)| !is_self(found)) {
+                return Ok(found);
+            }
+            if exhausted {
+                return Err(Error::FindError(index, quadtree::Error::NoneInRadius));
+            }
+
+            n = n.saturating_mul(2).min(size);
         }
-        .map_err(|err| Error::FindError(record.index, err))
     }
 }
 
@@ -163,8 +329,21 @@ impl std::fmt::Display for Quadtree {
     }
 }
 
+/// Extract the index and the point to search from for a query.
+fn query_point<Q: Query + ?Sized>(query: &Q) -> Result<(usize, Point), Error> {
+    let index = query.index();
+    let point = query.point().ok_or(Error::QueryRequiresPoint(index))?;
+
+    Ok((index, point))
+}
+
 /// Build the Bounding Box from provided arguments.
-pub fn make_bbox(path: &PathBuf, sphere: bool, bbox: &Option<String>) -> Result<Rect, Error> {
+pub fn make_bbox(
+    path: &PathBuf,
+    source: &SourceOptions,
+    sphere: bool,
+    bbox: &Option<String>,
+) -> Result<Rect, Error> {
     // Get the right bbox points given the argument values
     let (a, b) = if sphere {
         // Sphere option builds sphere bounds broken at the antimeridian
@@ -179,19 +358,11 @@ pub fn make_bbox(path: &PathBuf, sphere: bool, bbox: &Option<String>) -> Result<
             Point::new(bbox_next(&mut pts)?, bbox_next(&mut pts)?),
         )
     } else {
-        // Default to the bbox available on the input file, matching on the file type
-        match path
-            .extension()
-            .and_then(|e| e.to_str())
-            .ok_or(Error::CannotParseFileExtension(path.clone()))?
-        {
-            "shp" => shp_bbox(path)?,
-            "json" => geojson_bbox(path)?,
-            "kml" | "kmz" | "csv" => {
-                // Appears to be no overall bbox embedded in kml files and csv files, so default to sphere
-                (Point::new(-180.0, -90.0), Point::new(180.0, 90.0))
-            }
-            _ => Err(Error::UnsupportedFileType)?,
+        // Default to the bbox available on the input file, falling back to the sphere for
+        // formats that have no overall bbox embedded
+        match open_source(path, source)?.bbox()? {
+            Some(rect) => (rect.min().into(), rect.max().into()),
+            None => (Point::new(-180.0, -90.0), Point::new(180.0, 90.0)),
         }
     };
 
@@ -0,0 +1,75 @@
+use std::iter::once;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use geo::Rect;
+
+use crate::error::{Error, ParseType};
+use crate::osm::{read_osm, OsmRecord, TagFilter, OSM_FIELDS};
+
+use super::datum::{BaseData, Datum};
+use super::source::{DatumIter, Source};
+
+pub fn osm_field_val(record: &OsmRecord, field: &String) -> String {
+    record.field(field)
+}
+
+/// [`Source`] for OSM XML files. Tagged nodes and ways and multipolygon relations are each a
+/// feature, limited to those matching any of the tag filters when there are some.
+pub struct OsmSource {
+    path: PathBuf,
+    filter: Vec<TagFilter>,
+}
+
+impl OsmSource {
+    pub fn new(path: PathBuf, filter: Vec<TagFilter>) -> Self {
+        Self { path, filter }
+    }
+}
+
+impl Source for OsmSource {
+    fn datums(&mut self) -> Result<DatumIter<'_>, Error> {
+        let osm = read_osm(&self.path)?;
+
+        // Features borrow from the file contents, so are collected before it is dropped
+        let features: Vec<_> = osm.features(&self.filter).collect();
+
+        Ok(Box::new(features.into_iter().enumerate().flat_map(
+            |(index, (geoms, record))| -> DatumIter<'static> {
+                if geoms.is_empty() {
+                    return Box::new(once(Err(Error::CannotParseRecord(
+                        index,
+                        ParseType::MissingGeometry,
+                    ))));
+                }
+
+                let record = Arc::new(record);
+                Box::new(geoms.into_iter().map(move |geom| {
+                    Ok(Datum::new(geom, BaseData::Osm(Arc::clone(&record)), index))
+                }))
+            },
+        )))
+    }
+
+    /// The bounds declared in the file, which most extracts include.
+    fn bbox(&self) -> Result<Option<Rect>, Error> {
+        Ok(read_osm(&self.path)?.bounds)
+    }
+
+    /// Fields are the id and type of each feature, then the keys of all their tags.
+    fn fields(&self) -> Result<Option<Vec<String>>, Error> {
+        let tags = read_osm(&self.path)?.tag_keys(&self.filter);
+
+        Ok(Some(
+            OSM_FIELDS
+                .iter()
+                .map(|f| f.to_string())
+                .chain(tags)
+                .collect(),
+        ))
+    }
+
+    fn count(&self) -> Result<Option<usize>, Error> {
+        Ok(Some(read_osm(&self.path)?.count(&self.filter)))
+    }
+}
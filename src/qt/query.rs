@@ -0,0 +1,87 @@
+use geo::{Centroid, Point};
+use quadtree::Geometry;
+
+use super::ParsedRecord;
+
+/// Types that can be used as the query for a search of the [`super::Quadtree`].
+///
+/// The quadtree searches from a point, so a query must resolve to a point in radians, matching the
+/// data stored in the quadtree. Geometries other than points are searched from their centroid, and
+/// empty geometries, which have no centroid, fail with
+/// [`crate::error::Error::QueryRequiresPoint`]. Wrap queries in degrees with [`Degrees`].
+pub trait Query {
+    /// The point in radians to search from, if the query can be represented as one.
+    fn point(&self) -> Option<Point>;
+
+    /// The index of the query, used when reporting errors. Defaults to zero for queries that
+    /// carry no index of their own.
+    fn index(&self) -> usize {
+        0
+    }
+}
+
+impl Query for Point {
+    fn point(&self) -> Option<Point> {
+        Some(*self)
+    }
+}
+
+/// Searches from the centroid, or the point itself for points.
+impl Query for geo::Geometry {
+    fn point(&self) -> Option<Point> {
+        self.centroid()
+    }
+}
+
+/// Searches from the centroid, or the point itself for points, as for [`geo::Geometry`].
+impl Query for Geometry<f64> {
+    fn point(&self) -> Option<Point> {
+        match self {
+            Geometry::Point(p) => Some(p.centroid()),
+            Geometry::LineString(l) => l.centroid(),
+            Geometry::Polygon(p) => p.centroid(),
+        }
+    }
+}
+
+impl Query for ParsedRecord {
+    fn point(&self) -> Option<Point> {
+        Some(self.point)
+    }
+
+    fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// Wrapper for a query in degrees, such as a point read from user input, converting it to the
+/// radians used by the quadtree. Geometries are searched from their centroid in degrees.
+///
+/// ```ignore
+/// let result = qt.find(&Degrees(Point::new(-0.1276, 51.5072)), None)?;
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Degrees<Q>(pub Q);
+
+impl<Q: Query> Query for Degrees<Q> {
+    fn point(&self) -> Option<Point> {
+        self.0.point().map(|p| p.to_radians())
+    }
+
+    fn index(&self) -> usize {
+        self.0.index()
+    }
+}
+
+/// Wrapper to attach a position in a batch to a query so that errors identify the failed query.
+pub(super) struct Indexed<'q, Q: ?Sized>(pub usize, pub &'q Q);
+
+impl<Q: Query + ?Sized> Query for Indexed<'_, Q> {
+    fn point(&self) -> Option<Point> {
+        self.1.point()
+    }
+
+    fn index(&self) -> usize {
+        self.0
+    }
+}
@@ -1,58 +1,118 @@
+use std::iter::once;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use geo::Point;
-use shapefile::{dbase::Record, Reader};
+use geo::{Point, Rect};
+use shapefile::dbase::Record;
 
+use crate::encoding::TextEncoding;
 use crate::error::{Error, ParseType};
 use crate::shp::convert_dbase_field_opt;
 use crate::shp::convert_shape;
+use crate::shp::dbf::{open_shapefile, ShpReader};
+use crate::shp::prj::read_prj;
 
 use super::datum::{BaseData, Datum};
-use super::QtData;
-use super::Quadtree;
+use super::source::{DatumIter, RecordIter, Source};
 
 pub fn shp_field_val(record: &Record, field: &String) -> String {
     convert_dbase_field_opt(record.get(field))
 }
 
-pub fn build_shp(path: PathBuf, opts: QtData) -> Result<Quadtree, Error> {
-    let mut shapefile = Reader::from_path(path.clone()).map_err(|_| Error::CannotReadFile(path))?;
-    let shp_iter = shapefile
-        .iter_shapes_and_records()
-        .enumerate()
-        .map(|(i, res)| {
-            (
-                i,
-                res.map_err(|_| Error::CannotParseRecord(i, ParseType::Shapefile)),
-            )
-        });
-    let mut qt = Quadtree::new(opts);
-
-    for (index, shp) in shp_iter {
-        match shp {
-            Ok((shp, record)) => {
-                // Use an RC here to simplify: we don't need to keep a master list around and manage
-                // the references, but can still avoid duplicating the records
-                let record = Arc::new(record);
-                for geom in convert_shape(shp) {
-                    if let Some(err) = geom
-                        .map(|g| Datum::new(g, BaseData::Shp(Arc::clone(&record)), index))
-                        .and_then(|datum| qt.insert(datum))
-                        .err()
-                    {
-                        eprintln!("{err}");
-                    }
-                }
-            }
-            Err(err) => eprintln!("{err}"),
+/// [`Source`] for shapefiles, with metadata from the accompanying dbf file.
+pub struct ShapefileSource {
+    path: PathBuf,
+    encoding: Option<TextEncoding>,
+    // The datum iterator borrows the reader, so it is held here while iterating
+    reader: Option<ShpReader>,
+}
+
+impl ShapefileSource {
+    pub fn new(path: PathBuf, encoding: Option<TextEncoding>) -> Self {
+        Self {
+            path,
+            encoding,
+            reader: None,
         }
     }
 
-    Ok(qt)
+    fn open_reader(&self) -> Result<ShpReader, Error> {
+        open_shapefile(&self.path, self.encoding)
+    }
 }
 
-pub fn shp_bbox(path: &PathBuf) -> Result<(Point, Point), Error> {
-    let shp = Reader::from_path(&path).map_err(|_| Error::CannotReadFile(path.clone()))?;
-    Ok((shp.header().bbox.min.into(), shp.header().bbox.max.into()))
+impl Source for ShapefileSource {
+    fn datums(&mut self) -> Result<DatumIter<'_>, Error> {
+        let crs = read_prj(&self.path)?;
+        let reader = self.open_reader()?;
+        let reader = self.reader.insert(reader);
+
+        Ok(Box::new(
+            reader.iter_shapes_and_records().enumerate().flat_map(
+                move |(index, res)| -> DatumIter<'static> {
+                    match res {
+                        Ok((shp, record)) => {
+                            // Use an RC here to simplify: we don't need to keep a master list
+                            // around and manage the references, but can still avoid duplicating
+                            // the records
+                            let record = Arc::new(record);
+                            Box::new(convert_shape(shp, crs).map(move |geom| {
+                                geom.map(|g| {
+                                    Datum::new(g, BaseData::Shp(Arc::clone(&record)), index)
+                                })
+                            }))
+                        }
+                        Err(_) => Box::new(once(Err(Error::CannotParseRecord(
+                            index,
+                            ParseType::Shapefile,
+                        )))),
+                    }
+                },
+            ),
+        ))
+    }
+
+    /// Records are read from the dbf file without the .prj, so they can be read even when the
+    /// coordinate system is not supported.
+    fn records(&mut self) -> Result<Option<RecordIter<'_>>, Error> {
+        let reader = self.open_reader()?;
+        let reader = self.reader.insert(reader);
+
+        Ok(Some(Box::new(
+            reader
+                .iter_shapes_and_records()
+                .enumerate()
+                .map(|(index, res)| {
+                    res.map(|(_, record)| BaseData::Shp(Arc::new(record)))
+                        .map_err(|_| Error::CannotParseRecord(index, ParseType::Shapefile))
+                }),
+        )))
+    }
+
+    /// The header bbox, reprojected to lng/lat if the shapefile is projected.
+    fn bbox(&self) -> Result<Option<Rect>, Error> {
+        let shp = self.open_reader()?;
+        let min: Point = shp.header().bbox.min.into();
+        let max: Point = shp.header().bbox.max.into();
+
+        Ok(Some(
+            read_prj(&self.path)?.reproject_rect(Rect::new(min, max)),
+        ))
+    }
+
+    /// Fields are taken from the first record in the dbf file.
+    fn fields(&self) -> Result<Option<Vec<String>>, Error> {
+        let (_, record) = self
+            .open_reader()?
+            .iter_shapes_and_records()
+            .next()
+            .ok_or(Error::UnexpectedEndOfInput)
+            .and_then(|r| r.map_err(|err| Error::ShapefileParseError(err)))?;
+
+        Ok(Some(record.into_iter().map(|(name, _)| name).collect()))
+    }
+
+    fn count(&self) -> Result<Option<usize>, Error> {
+        Ok(Some(self.open_reader()?.iter_shapes_and_records().count()))
+    }
 }
@@ -0,0 +1,505 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use geo::Rect;
+use zip::ZipArchive;
+
+use crate::archive::Input;
+use crate::encoding::TextEncoding;
+use crate::error::Error;
+use crate::osm::TagFilter;
+
+use super::csv::{detect_delimiter, CsvOptions, CsvSource};
+use super::datum::{BaseData, Datum};
+use super::geojson::GeoJsonSource;
+use super::gpx::GpxSource;
+use super::kml::KmlSource;
+use super::osm::OsmSource;
+use super::shapefile::ShapefileSource;
+use super::topojson::TopoJsonSource;
+use super::wkt::WktSource;
+
+/// Iterator of the datums read from a [`Source`].
+pub type DatumIter<'a> = Box<dyn Iterator<Item = Result<Datum, Error>> + 'a>;
+
+/// Iterator of the metadata of every feature read from a [`Source`].
+pub type RecordIter<'a> = Box<dyn Iterator<Item = Result<BaseData, Error>> + 'a>;
+
+/// A readable geographic data format.
+///
+/// Sources are the single place where a file format is turned into datums, so that the quadtree
+/// and all the binaries treat a format the same way. Adding a format means implementing this
+/// trait and registering it in [`Format`].
+pub trait Source {
+    /// Read every datum from the source. Each datum carries the index of the feature it came
+    /// from, a geometry in radians, and the metadata of the feature. Multi-part features produce
+    /// one datum per part, all with the same index. Errors for individual features are emitted in
+    /// the iterator, errors that prevent the source being read at all are returned directly.
+    fn datums(&mut self) -> Result<DatumIter<'_>, Error>;
+
+    /// Read the metadata of every feature, whether or not its geometry can be read. Records are in
+    /// the same order as the datums, so the index of a record is its position in the iterator.
+    /// Formats that can only read metadata along with the geometry return None.
+    fn records(&mut self) -> Result<Option<RecordIter<'_>>, Error> {
+        Ok(None)
+    }
+
+    /// The bounding box embedded in the source, in degrees, if the format has one.
+    fn bbox(&self) -> Result<Option<Rect>, Error> {
+        Ok(None)
+    }
+
+    /// The metadata field names available from the source, if they can be determined.
+    fn fields(&self) -> Result<Option<Vec<String>>, Error> {
+        Ok(None)
+    }
+
+    /// The number of top-level features in the source, if it can be determined.
+    fn count(&self) -> Result<Option<usize>, Error> {
+        Ok(None)
+    }
+}
+
+/// Number of bytes read from the start of a file to detect its format.
+const SNIFF_LEN: u64 = 4096;
+
+/// Magic number at the start of every shapefile main file, the big-endian file code 9994.
+const SHP_MAGIC: [u8; 4] = [0x00, 0x00, 0x27, 0x0a];
+
+/// Signature at the start of a zip archive. Other archives are unpacked before detection, so only
+/// KMZ is left, which is recognized by the KML member.
+const ZIP_MAGIC: [u8; 4] = *b"PK\x03\x04";
+
+/// Options controlling how a [`Source`] is opened.
+#[derive(Clone, Debug, Default)]
+pub struct SourceOptions {
+    /// The format of the file, detected from the file itself if not provided.
+    pub format: Option<Format>,
+
+    /// The text encoding of the file, overriding any encoding declared by the file itself.
+    pub encoding: Option<TextEncoding>,
+
+    /// Reader options used when the file is a csv.
+    pub csv: CsvOptions,
+
+    /// Tag filters used when the file is OSM XML. Only features matching any of the filters are
+    /// read, or every feature when there are none.
+    pub osm_tags: Vec<TagFilter>,
+
+    /// The member to read when the file is a zip archive, by its name or file name. By default
+    /// the only shapefile or the only member is read.
+    pub member: Option<String>,
+}
+
+/// Registry of the supported file formats.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Shapefile,
+    GeoJson,
+    Kml,
+    Csv,
+    Gpx,
+    Wkt,
+    TopoJson,
+    Osm,
+}
+
+impl Format {
+    /// Determine the format of the file at `path`.
+    ///
+    /// Formats with an unambiguous signature are detected from the content first, so mislabelled
+    /// files are still read correctly. The extension is then used, ignoring case, then weaker
+    /// signs in the content, and finally any file that looks like delimited text with a header
+    /// row is treated as csv.
+    pub fn from_path(path: &PathBuf) -> Result<Self, Error> {
+        let head = read_head(path)?;
+
+        Self::from_signature(path, &head)
+            .or_else(|| Self::from_extension(path, &head))
+            .or_else(|| Self::from_content(&head))
+            .or_else(|| is_delimited(&head).then_some(Self::Csv))
+            .ok_or(Error::CannotDetectFormat(path.clone()))
+    }
+
+    /// Resolve the format for `path`, using `format` if it is provided and detecting it from the
+    /// file otherwise.
+    pub fn resolve(path: &PathBuf, format: Option<Format>) -> Result<Self, Error> {
+        match format {
+            Some(format) => Ok(format),
+            None => Self::from_path(path),
+        }
+    }
+
+    /// Detect the format from a signature that no other format can have: the shapefile file code,
+    /// a zip archive holding KML, or the root element of an XML document.
+    fn from_signature(path: &PathBuf, head: &[u8]) -> Option<Self> {
+        if head.starts_with(&SHP_MAGIC) {
+            return Some(Self::Shapefile);
+        }
+        if head.starts_with(&ZIP_MAGIC) {
+            return has_kml_member(path).then_some(Self::Kml);
+        }
+
+        let text = String::from_utf8_lossy(head);
+        match root_element(text.trim_start_matches('\u{feff}'))? {
+            "kml" => Some(Self::Kml),
+            "gpx" => Some(Self::Gpx),
+            "osm" => Some(Self::Osm),
+            _ => None,
+        }
+    }
+
+    /// Detect the format from the extension. A `.json` file may hold GeoJSON or TopoJSON, so the
+    /// content decides between them.
+    fn from_extension(path: &PathBuf, head: &[u8]) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "shp" => Some(Self::Shapefile),
+            "json" if is_topojson(&String::from_utf8_lossy(head)) => Some(Self::TopoJson),
+            "json" | "geojson" | "geojsons" | "geojsonl" | "geojsonseq" | "ndjson" | "jsonl" => {
+                Some(Self::GeoJson)
+            }
+            "kml" | "kmz" => Some(Self::Kml),
+            "csv" => Some(Self::Csv),
+            "gpx" => Some(Self::Gpx),
+            "wkt" => Some(Self::Wkt),
+            "topojson" => Some(Self::TopoJson),
+            "osm" => Some(Self::Osm),
+            _ => None,
+        }
+    }
+
+    /// Detect the format from text content that is typical of a format but could also appear in
+    /// another, so is only used when the extension is not known.
+    fn from_content(head: &[u8]) -> Option<Self> {
+        let text = String::from_utf8_lossy(head);
+        // GeoJSON Text Sequences start each record with a record separator
+        let text = text.trim_start_matches(['\u{feff}', '\u{1e}']).trim_start();
+        // A topology is also a JSON object with a type, so must be checked before GeoJSON
+        if is_topojson(text) {
+            Some(Self::TopoJson)
+        } else if text.starts_with('{') && text.contains("\"type\"") {
+            Some(Self::GeoJson)
+        } else if starts_with_wkt(text) {
+            Some(Self::Wkt)
+        } else {
+            None
+        }
+    }
+
+    /// Build the [`Source`] for the file at `path` in this format.
+    pub fn open(&self, path: PathBuf, opts: &SourceOptions) -> Box<dyn Source> {
+        match self {
+            Self::Shapefile => Box::new(ShapefileSource::new(path, opts.encoding)),
+            Self::GeoJson => Box::new(GeoJsonSource::new(path)),
+            Self::Kml => Box::new(KmlSource::new(path)),
+            Self::Csv => Box::new(CsvSource::new(
+                path,
+                opts.csv.clone(),
+                opts.encoding.unwrap_or_default(),
+            )),
+            Self::Gpx => Box::new(GpxSource::new(path)),
+            Self::Wkt => Box::new(WktSource::new(path)),
+            Self::TopoJson => Box::new(TopoJsonSource::new(path)),
+            Self::Osm => Box::new(OsmSource::new(path, opts.osm_tags.clone())),
+        }
+    }
+}
+
+/// Parse a format name, as passed to `--format` on the command line.
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "shp" | "shapefile" => Ok(Self::Shapefile),
+            "json" | "geojson" => Ok(Self::GeoJson),
+            "kml" | "kmz" => Ok(Self::Kml),
+            "csv" => Ok(Self::Csv),
+            "gpx" => Ok(Self::Gpx),
+            "wkt" => Ok(Self::Wkt),
+            "topojson" => Ok(Self::TopoJson),
+            "osm" => Ok(Self::Osm),
+            _ => Err(Error::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// Read the first bytes of the file at `path` for format detection.
+fn read_head(path: &PathBuf) -> Result<Vec<u8>, Error> {
+    let mut head = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(SNIFF_LEN).read_to_end(&mut head))
+        .map_err(|_| Error::CannotReadFile(path.clone()))?;
+
+    Ok(head)
+}
+
+/// The name of the root element of XML content, without any namespace prefix, skipping the XML
+/// declaration, processing instructions, comments and the doctype.
+fn root_element(text: &str) -> Option<&str> {
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start().strip_prefix('<')?;
+        if let Some(comment) = rest.strip_prefix("!--") {
+            rest = comment.split_once("-->")?.1;
+        } else if rest.starts_with(['?', '!']) {
+            rest = rest.split_once('>')?.1;
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                .unwrap_or(rest.len());
+            return rest[..end].rsplit(':').next();
+        }
+    }
+}
+
+/// Check whether a zip archive holds a KML file, so can be read as KMZ.
+fn has_kml_member(path: &PathBuf) -> bool {
+    File::open(path)
+        .ok()
+        .and_then(|file| ZipArchive::new(file).ok())
+        .is_some_and(|archive| {
+            archive
+                .file_names()
+                .any(|name| name.to_lowercase().ends_with(".kml"))
+        })
+}
+
+/// Check whether the content is a JSON object with the TopoJSON `"type": "Topology"` member.
+fn is_topojson(text: &str) -> bool {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    text.starts_with('{') && has_type(text, "Topology")
+}
+
+/// Check whether the content has a `"type"` member with the string `value`, allowing whitespace
+/// around the colon. Matching the member rather than the bare string keeps the same string in a
+/// property value from matching.
+fn has_type(text: &str, value: &str) -> bool {
+    text.match_indices("\"type\"").any(|(i, key)| {
+        text[i + key.len()..]
+            .trim_start()
+            .strip_prefix(':')
+            .and_then(|rest| rest.trim_start().strip_prefix('"'))
+            .and_then(|rest| rest.strip_prefix(value))
+            .is_some_and(|rest| rest.starts_with('"'))
+    })
+}
+
+/// Check whether the content starts with a WKT geometry, optionally after an EWKT SRID. The type
+/// must be followed by its optional dimensions and then the opening bracket or `EMPTY`, so words
+/// that only start with a type, such as a `point_id` column header, do not match.
+fn starts_with_wkt(text: &str) -> bool {
+    let text = text.to_ascii_uppercase();
+    let text = match text.strip_prefix("SRID=") {
+        Some(rest) => rest.split_once(';').map(|(_, s)| s).unwrap_or_default(),
+        None => text.as_str(),
+    };
+    let is_body = |rest: &str| {
+        let rest = rest.trim_start();
+        let rest = ["ZM", "Z", "M"]
+            .iter()
+            .find_map(|dim| rest.strip_prefix(dim))
+            .unwrap_or(rest)
+            .trim_start();
+        rest.starts_with('(')
+            || rest
+                .strip_prefix("EMPTY")
+                .is_some_and(|r| !r.starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+    };
+
+    [
+        "POINT",
+        "LINESTRING",
+        "POLYGON",
+        "MULTIPOINT",
+        "MULTILINESTRING",
+        "MULTIPOLYGON",
+        "GEOMETRYCOLLECTION",
+    ]
+    .iter()
+    .filter_map(|tag| text.strip_prefix(tag))
+    .any(is_body)
+}
+
+/// Check whether the content looks like delimited text with a header row: text without any null
+/// bytes, where the first line has at least two fields split by one of the detected delimiters and
+/// at least one of them is not a number.
+fn is_delimited(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+
+    let text = String::from_utf8_lossy(head);
+    let header = text
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .next()
+        .unwrap_or("");
+    match detect_delimiter(header) {
+        Some(delimiter) => {
+            let fields: Vec<_> = header.split(delimiter as char).map(str::trim).collect();
+            fields.len() > 1 && fields.iter().any(|f| f.parse::<f64>().is_err())
+        }
+        None => false,
+    }
+}
+
+/// Open the [`Source`] for the file at `path`. Gzip files and zip archives are unpacked first, see
+/// [`Input::open`]. The format is detected from the file unless it is set in the options.
+pub fn open_source(path: &PathBuf, opts: &SourceOptions) -> Result<Box<dyn Source>, Error> {
+    let input = Input::open(path, opts.member.as_deref())?;
+    let source = Format::resolve(input.path(), opts.format)?.open(input.path().clone(), opts);
+
+    Ok(Box::new(UnpackedSource {
+        source,
+        _input: input,
+    }))
+}
+
+/// A [`Source`] read from an [`Input`], keeping the input alive so any unpacked files are only
+/// removed once the source is dropped.
+struct UnpackedSource {
+    source: Box<dyn Source>,
+    _input: Input,
+}
+
+impl Source for UnpackedSource {
+    fn datums(&mut self) -> Result<DatumIter<'_>, Error> {
+        self.source.datums()
+    }
+
+    fn records(&mut self) -> Result<Option<RecordIter<'_>>, Error> {
+        self.source.records()
+    }
+
+    fn bbox(&self) -> Result<Option<Rect>, Error> {
+        self.source.bbox()
+    }
+
+    fn fields(&self) -> Result<Option<Vec<String>>, Error> {
+        self.source.fields()
+    }
+
+    fn count(&self) -> Result<Option<usize>, Error> {
+        self.source.count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, remove_file, write};
+
+    use super::*;
+
+    /// Detect the format of `content` written to a temporary file called `name`. Names must be
+    /// unique across tests, as they run in parallel.
+    fn detect(name: &str, content: &str) -> Option<Format> {
+        let dir = std::env::temp_dir().join(format!("geo-munge-format-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        write(&path, content).unwrap();
+        let format = Format::from_path(&path).ok();
+        remove_file(&path).unwrap();
+
+        format
+    }
+
+    #[test]
+    fn csv_without_extension() {
+        let csv = "name,lng,lat\nA,-0.1,51.5\n";
+        assert_eq!(detect("comma", csv), Some(Format::Csv));
+
+        let csv = "name;lng;lat\nA;-0.1;51.5\n";
+        assert_eq!(detect("semicolon", csv), Some(Format::Csv));
+
+        assert_eq!(detect("numbers", "1,2\n3,4\n"), None);
+    }
+
+    #[test]
+    fn csv_headers_starting_with_wkt_types() {
+        let csv = "point_id,lng,lat\n1,-0.1,51.5\n";
+        assert_eq!(detect("point_id.csv", csv), Some(Format::Csv));
+        assert_eq!(detect("point_id", csv), Some(Format::Csv));
+
+        let csv = "Polygon_name;lng;lat\nA;-0.1;51.5\n";
+        assert_eq!(detect("polygon_name", csv), Some(Format::Csv));
+
+        let csv = "pointname\tlng\tlat\nA\t-0.1\t51.5\n";
+        assert_eq!(detect("pointname", csv), Some(Format::Csv));
+    }
+
+    #[test]
+    fn wkt_without_extension() {
+        assert_eq!(detect("point", "POINT (1 2)\n"), Some(Format::Wkt));
+        assert_eq!(detect("point_z", "point z(1 2 3)\n"), Some(Format::Wkt));
+        assert_eq!(detect("pointzm", "POINTZM (1 2 3 4)\n"), Some(Format::Wkt));
+        assert_eq!(
+            detect("ewkt", "SRID=4326;MULTIPOLYGON EMPTY\n"),
+            Some(Format::Wkt)
+        );
+        assert_eq!(
+            detect("collection.txt", "GEOMETRYCOLLECTION(POINT(1 2))\n"),
+            Some(Format::Wkt)
+        );
+    }
+
+    #[test]
+    fn extension_beats_content() {
+        // A JSON object with a type, but a known extension wins over the weak content check
+        assert_eq!(
+            detect(
+                "geojson.csv",
+                "{\"type\": \"Point\", \"coordinates\": [1, 2]}\n"
+            ),
+            Some(Format::Csv)
+        );
+        assert_eq!(
+            detect(
+                "geojson",
+                "{\"type\": \"Point\", \"coordinates\": [1, 2]}\n"
+            ),
+            Some(Format::GeoJson)
+        );
+
+        // Valid WKT text, but a known extension wins over the weak text check
+        assert_eq!(detect("wkt.csv", "POINT (1 2)\n"), Some(Format::Csv));
+        assert_eq!(
+            detect(
+                "geojson.wkt",
+                "{\"type\": \"Point\", \"coordinates\": [1, 2]}\n"
+            ),
+            Some(Format::Wkt)
+        );
+    }
+
+    #[test]
+    fn signature_beats_extension() {
+        let kml = "<?xml version=\"1.0\"?>\n<!-- comment -->\n<kml xmlns=\"http://www.opengis.net/kml/2.2\"/>";
+        assert_eq!(detect("kml.csv", kml), Some(Format::Kml));
+
+        let gpx = "<?xml version=\"1.0\"?><gpx version=\"1.1\"></gpx>";
+        assert_eq!(detect("gpx.json", gpx), Some(Format::Gpx));
+    }
+
+    #[test]
+    fn topojson() {
+        let topology = "{\"type\":\"Topology\",\"objects\":{},\"arcs\":[]}";
+        assert_eq!(detect("topology.json", topology), Some(Format::TopoJson));
+        assert_eq!(detect("topology", topology), Some(Format::TopoJson));
+
+        // The type member can come after the arcs, beyond the detected content
+        let arcs = ["[[0,0],[1,1]]"; 1000].join(",");
+        let topology = format!("{{\"arcs\":[{arcs}],\"objects\":{{}},\"type\":\"Topology\"}}");
+        assert_eq!(
+            detect("late_type.topojson", &topology),
+            Some(Format::TopoJson)
+        );
+
+        let geojson =
+            "{\"type\":\"Feature\",\"properties\":{\"kind\":\"Topology\"},\"geometry\":null}";
+        assert_eq!(detect("property.json", geojson), Some(Format::GeoJson));
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use geo::{Point, Rect};
+
+use crate::error::Error;
+use crate::geojson::read_topojson;
+
+use super::datum::BaseData;
+use super::geojson::map_feature;
+use super::source::{DatumIter, RecordIter, Source};
+
+/// [`Source`] for TopoJSON files. Each geometry of each object in the topology is decoded into a
+/// GeoJSON Feature, so the metadata is the same as for GeoJSON.
+pub struct TopoJsonSource {
+    path: PathBuf,
+}
+
+impl TopoJsonSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Source for TopoJsonSource {
+    fn datums(&mut self) -> Result<DatumIter<'_>, Error> {
+        let topology = read_topojson(&self.path)?;
+
+        Ok(Box::new(
+            topology.features().enumerate().flat_map(map_feature),
+        ))
+    }
+
+    fn records(&mut self) -> Result<Option<RecordIter<'_>>, Error> {
+        let topology = read_topojson(&self.path)?;
+
+        Ok(Some(Box::new(
+            topology.features().map(|f| Ok(BaseData::Json(Arc::new(f)))),
+        )))
+    }
+
+    fn bbox(&self) -> Result<Option<Rect>, Error> {
+        match read_topojson(&self.path)?.bbox {
+            Some(bbox) if bbox.len() != 4 => Err(Error::InvalidBoundingBox),
+            Some(bbox) => Ok(Some(Rect::new(
+                Point::new(bbox[0], bbox[1]),
+                Point::new(bbox[2], bbox[3]),
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    /// Fields are the id and the first level of the properties of every geometry in every object.
+    fn fields(&self) -> Result<Option<Vec<String>>, Error> {
+        let mut fields = BTreeSet::new();
+
+        for f in read_topojson(&self.path)?.features() {
+            if f.id.is_some() {
+                fields.insert("id".to_string());
+            }
+            if let Some(props) = f.properties {
+                fields.extend(props.into_iter().map(|(k, _)| k));
+            }
+        }
+
+        Ok(Some(fields.into_iter().collect()))
+    }
+
+    fn count(&self) -> Result<Option<usize>, Error> {
+        Ok(Some(read_topojson(&self.path)?.features().count()))
+    }
+}
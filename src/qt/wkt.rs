@@ -0,0 +1,66 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::iter::once;
+use std::path::PathBuf;
+
+use crate::error::{Error, ParseType};
+use crate::wkt::{flatten_geometry, parse_geometry_text};
+
+use super::datum::{BaseData, Datum};
+use super::source::{DatumIter, Source};
+
+/// [`Source`] for line-oriented geometry dumps, with one WKT or hex WKB geometry per line. Blank
+/// lines are skipped and do not count towards the index. There is no metadata.
+pub struct WktSource {
+    path: PathBuf,
+}
+
+impl WktSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The non-blank lines of the file.
+    fn lines(&self) -> Result<impl Iterator<Item = std::io::Result<String>>, Error> {
+        let file = File::open(&self.path).map_err(|_| Error::CannotReadFile(self.path.clone()))?;
+
+        Ok(BufReader::new(file)
+            .lines()
+            .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty())))
+    }
+}
+
+impl Source for WktSource {
+    fn datums(&mut self) -> Result<DatumIter<'_>, Error> {
+        Ok(Box::new(self.lines()?.enumerate().flat_map(
+            |(index, line)| -> DatumIter<'static> {
+                let parse_error = || Error::CannotParseRecord(index, ParseType::Wkt);
+                let geometry = line
+                    .map_err(|_| parse_error())
+                    .and_then(|line| parse_geometry_text(&line).map_err(|_| parse_error()));
+
+                match geometry {
+                    Ok(geometry) => {
+                        let parts = flatten_geometry(geometry);
+                        if parts.is_empty() {
+                            return Box::new(once(Err(Error::CannotParseRecord(
+                                index,
+                                ParseType::MissingGeometry,
+                            ))));
+                        }
+                        Box::new(
+                            parts
+                                .into_iter()
+                                .map(move |geom| Ok(Datum::new(geom, BaseData::None, index))),
+                        )
+                    }
+                    Err(err) => Box::new(once(Err(err))),
+                }
+            },
+        )))
+    }
+
+    fn count(&self) -> Result<Option<usize>, Error> {
+        Ok(Some(self.lines()?.count()))
+    }
+}
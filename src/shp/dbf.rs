@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::fs::{read_to_string, File};
+use std::io::{BufReader, Read};
+use std::path::PathBuf;
+
+use shapefile::dbase::yore::code_pages::CP437;
+use shapefile::dbase::{self, FieldValue, Record};
+use shapefile::header::Header;
+use shapefile::{Reader, Shape, ShapeReader};
+
+use crate::encoding::TextEncoding;
+use crate::error::Error;
+
+/// Offset of the language driver ID in the dbf header.
+const LANGUAGE_DRIVER_OFFSET: usize = 29;
+
+/// Shapefile reader over buffered files, with the text attributes from the dbf decoded in the
+/// encoding from [`dbf_encoding`].
+///
+/// dbase does not expose the error types needed to implement its `Encoding` trait, so the dbf is
+/// read as [`CP437`], which maps every byte to a distinct character. Text fields are then mapped
+/// back to their bytes and decoded with the [`TextEncoding`].
+pub struct ShpReader {
+    reader: Reader<BufReader<File>, BufReader<File>>,
+    encoding: TextEncoding,
+}
+
+impl ShpReader {
+    pub fn header(&self) -> &Header {
+        self.reader.header()
+    }
+
+    pub fn iter_shapes_and_records(
+        &mut self,
+    ) -> impl Iterator<Item = Result<(Shape, Record), shapefile::Error>> + '_ {
+        let encoding = self.encoding;
+
+        self.reader
+            .iter_shapes_and_records()
+            .map(move |res| res.map(|(shape, record)| (shape, decode_record(record, encoding))))
+    }
+}
+
+/// Open the shapefile at `path`, decoding the text attributes with the encoding from
+/// [`dbf_encoding`].
+pub fn open_shapefile(path: &PathBuf, encoding: Option<TextEncoding>) -> Result<ShpReader, Error> {
+    let encoding = dbf_encoding(path, encoding)?;
+
+    let shapes = ShapeReader::from_path(path).map_err(|_| Error::CannotReadFile(path.clone()))?;
+    let dbf_path = path.with_extension("dbf");
+    let records = dbase::Reader::from_path_with_encoding(&dbf_path, CP437)
+        .map_err(|_| Error::CannotReadFile(dbf_path))?;
+
+    Ok(ShpReader {
+        reader: Reader::new(shapes, records),
+        encoding,
+    })
+}
+
+/// Determine the encoding of the text attributes for the shapefile at `path`.
+///
+/// An explicit `encoding` takes priority, then the `.cpg` sidecar file, then the language driver
+/// ID in the dbf header. Files with neither are read as UTF-8. A `.cpg` naming an unsupported
+/// encoding is an error rather than a silent fallback, as the text would come out garbled.
+pub fn dbf_encoding(path: &PathBuf, encoding: Option<TextEncoding>) -> Result<TextEncoding, Error> {
+    if let Some(encoding) = encoding {
+        return Ok(encoding);
+    }
+
+    let cpg_path = path.with_extension("cpg");
+    if cpg_path.exists() {
+        return read_to_string(&cpg_path)
+            .map_err(|_| Error::CannotReadFile(cpg_path))?
+            .parse();
+    }
+
+    Ok(language_driver(path)
+        .and_then(encoding_from_language_driver)
+        .unwrap_or_default())
+}
+
+/// Read the language driver ID from the dbf header, if the file is long enough to have one.
+fn language_driver(path: &PathBuf) -> Option<u8> {
+    let mut header = [0; LANGUAGE_DRIVER_OFFSET + 1];
+    File::open(path.with_extension("dbf"))
+        .and_then(|mut f| f.read_exact(&mut header))
+        .ok()?;
+
+    Some(header[LANGUAGE_DRIVER_OFFSET])
+}
+
+/// Map the common language driver IDs to their code page.
+fn encoding_from_language_driver(id: u8) -> Option<TextEncoding> {
+    let code_page = match id {
+        0x01 => 437,
+        0x02 => 850,
+        0x03 | 0x57 | 0x58 | 0x59 => 1252,
+        0x7d => 1255,
+        0x7e => 1256,
+        0xc8 => 1250,
+        0xc9 => 1251,
+        0xca => 1254,
+        0xcb => 1253,
+        0xcc => 1257,
+        _ => return None,
+    };
+
+    TextEncoding::from_code_page(code_page)
+}
+
+/// Decode the text fields of a record read as [`CP437`] with `encoding`.
+fn decode_record(record: Record, encoding: TextEncoding) -> Record {
+    let fields: HashMap<String, FieldValue> = record.into();
+
+    fields
+        .into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                FieldValue::Character(Some(s)) => FieldValue::Character(Some(decode(s, encoding))),
+                FieldValue::Memo(s) => FieldValue::Memo(decode(s, encoding)),
+                value => value,
+            };
+            (name, value)
+        })
+        .collect::<HashMap<_, _>>()
+        .into()
+}
+
+/// Map text read as [`CP437`] back to its bytes and decode them with `encoding`. ASCII is the same
+/// in all the encodings so is left as is.
+fn decode(s: String, encoding: TextEncoding) -> String {
+    if s.is_ascii() {
+        return s;
+    }
+
+    match CP437.encode(&s) {
+        Ok(bytes) => encoding.decode(&bytes).into_owned(),
+        Err(_) => s,
+    }
+}
@@ -1,3 +1,6 @@
+pub mod dbf;
+pub mod prj;
+
 use std::iter::once;
 
 use quadtree::*;
@@ -5,6 +8,8 @@ use shapefile::{dbase::FieldValue, Shape};
 
 use crate::error::{Error, UnsupportedGeoType};
 
+use self::prj::Crs;
+
 /// Convert dbase fields to a string representation for inclusion in csv output.
 pub fn convert_dbase_field(f: &FieldValue) -> String {
     match f {
@@ -45,21 +50,25 @@ pub fn convert_dbase_field_opt(f: Option<&FieldValue>) -> String {
 }
 
 /// Convert shapefile shapes to their geo-type equivalents. This will only
-/// convert those types that are valid in quadtrees.
-pub fn convert_shape(shape: Shape) -> Box<dyn Iterator<Item = Result<Geometry<f64>, Error>>> {
+/// convert those types that are valid in quadtrees. Coordinates are
+/// reprojected from the shapefile's `crs` before conversion to radians.
+pub fn convert_shape(
+    shape: Shape,
+    crs: Crs,
+) -> Box<dyn Iterator<Item = Result<Geometry<f64>, Error>>> {
     match shape {
-        Shape::Point(p) => point_to_iter(p),
-        Shape::PointM(p) => point_to_iter(p),
-        Shape::PointZ(p) => point_to_iter(p),
-        Shape::Polyline(p) => mls_to_iter(p),
-        Shape::PolylineM(p) => mls_to_iter(p),
-        Shape::PolylineZ(p) => mls_to_iter(p),
-        Shape::Multipoint(p) => mp_to_iter(p),
-        Shape::MultipointM(p) => mp_to_iter(p),
-        Shape::MultipointZ(p) => mp_to_iter(p),
-        Shape::Polygon(p) => mpoly_to_iter(p),
-        Shape::PolygonM(p) => mpoly_to_iter(p),
-        Shape::PolygonZ(p) => mpoly_to_iter(p),
+        Shape::Point(p) => point_to_iter(p, crs),
+        Shape::PointM(p) => point_to_iter(p, crs),
+        Shape::PointZ(p) => point_to_iter(p, crs),
+        Shape::Polyline(p) => mls_to_iter(p, crs),
+        Shape::PolylineM(p) => mls_to_iter(p, crs),
+        Shape::PolylineZ(p) => mls_to_iter(p, crs),
+        Shape::Multipoint(p) => mp_to_iter(p, crs),
+        Shape::MultipointM(p) => mp_to_iter(p, crs),
+        Shape::MultipointZ(p) => mp_to_iter(p, crs),
+        Shape::Polygon(p) => mpoly_to_iter(p, crs),
+        Shape::PolygonM(p) => mpoly_to_iter(p, crs),
+        Shape::PolygonZ(p) => mpoly_to_iter(p, crs),
         // NullShape and MultiPatch are not covered
         Shape::Multipatch(_) => Box::new(once(Err(Error::UnsupportedGeometry(
             UnsupportedGeoType::MultipatchShp,
@@ -70,43 +79,47 @@ pub fn convert_shape(shape: Shape) -> Box<dyn Iterator<Item = Result<Geometry<f6
     }
 }
 
-fn point_to_iter<S>(shape: S) -> Box<dyn Iterator<Item = Result<Geometry<f64>, Error>>>
+fn point_to_iter<S>(shape: S, crs: Crs) -> Box<dyn Iterator<Item = Result<Geometry<f64>, Error>>>
 where
     S: Into<geo::Point>,
 {
     let mut p: geo::Point = shape.into();
+    crs.reproject(&mut p);
     p.to_radians_in_place();
     Box::new(once(Ok(Geometry::Point(p))))
 }
 
-fn mls_to_iter<S>(shape: S) -> Box<dyn Iterator<Item = Result<Geometry<f64>, Error>>>
+fn mls_to_iter<S>(shape: S, crs: Crs) -> Box<dyn Iterator<Item = Result<Geometry<f64>, Error>>>
 where
     S: Into<geo::MultiLineString>,
 {
     let mls: geo::MultiLineString = shape.into();
     Box::new(mls.into_iter().map(move |mut item| {
+        crs.reproject(&mut item);
         item.to_radians_in_place();
         Ok(Geometry::LineString(item))
     }))
 }
 
-fn mp_to_iter<S>(shape: S) -> Box<dyn Iterator<Item = Result<Geometry<f64>, Error>>>
+fn mp_to_iter<S>(shape: S, crs: Crs) -> Box<dyn Iterator<Item = Result<Geometry<f64>, Error>>>
 where
     S: Into<geo::MultiPoint>,
 {
     let mp: geo::MultiPoint = shape.into();
     Box::new(mp.into_iter().map(move |mut item| {
+        crs.reproject(&mut item);
         item.to_radians_in_place();
         Ok(Geometry::Point(item))
     }))
 }
 
-fn mpoly_to_iter<S>(shape: S) -> Box<dyn Iterator<Item = Result<Geometry<f64>, Error>>>
+fn mpoly_to_iter<S>(shape: S, crs: Crs) -> Box<dyn Iterator<Item = Result<Geometry<f64>, Error>>>
 where
     S: Into<geo::MultiPolygon>,
 {
     let mp: geo::MultiPolygon = shape.into();
     Box::new(mp.into_iter().map(move |mut item| {
+        crs.reproject(&mut item);
         item.to_radians_in_place();
         Ok(Geometry::Polygon(item))
     }))
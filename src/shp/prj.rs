@@ -0,0 +1,829 @@
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+use std::fs::read_to_string;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::str::Chars;
+
+use geo::{Coord, MapCoordsInPlace, Rect};
+
+use crate::error::Error;
+
+/// Iteration limit for the latitude series in the inverse conformal projections.
+const MAX_ITERATIONS: usize = 15;
+
+/// Convergence tolerance for the latitude series, in radians.
+const TOLERANCE: f64 = 1e-12;
+
+/// Tolerance for the angular unit of a geographic system, in radians per unit.
+const UNIT_TOLERANCE: f64 = 1e-12;
+
+/// Datums that are within a meter or two of WGS84, so can be read without a datum shift. Names are
+/// matched after lower casing, replacing spaces and hyphens with underscores, and dropping the
+/// `d_` prefix ESRI puts on datum names.
+const DATUMS: [&str; 10] = [
+    "wgs_1984",
+    "wgs84",
+    "world_geodetic_system_1984",
+    "north_american_datum_1983",
+    "north_american_1983",
+    "nad83",
+    "european_terrestrial_reference_system_1989",
+    "european_terrestrial_reference_system_1989_ensemble",
+    "etrs_1989",
+    "etrs89",
+];
+
+/// EPSG codes for Web Mercator, which are often written with a plain Mercator projection.
+const WEB_MERCATOR_CODES: [&str; 2] = ["3857", "3785"];
+
+/// Number of points sampled along each edge of a rectangle when reprojecting it, since projected
+/// straight lines are curves in lng/lat.
+const EDGE_SAMPLES: usize = 8;
+
+/// Coordinate reference system of a shapefile, read from the `.prj` file next to it.
+///
+/// Only the projection is applied, so the datum must be WGS84, NAD83 or ETRS89, which are all read
+/// as WGS84. Angles must be in degrees from the Greenwich meridian.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Crs {
+    /// Lng/lat in degrees, requiring no reprojection.
+    Geographic,
+    Mercator(Mercator),
+    TransverseMercator(TransverseMercator),
+    LambertConformalConic(LambertConformalConic),
+}
+
+/// Shape of the earth used by a projection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ellipsoid {
+    /// Semi-major axis in meters.
+    a: f64,
+    /// First eccentricity squared.
+    e2: f64,
+}
+
+/// Values common to all the projected systems: the ellipsoid, the projection origin in radians,
+/// the false origin in meters, and the scale of the linear unit in meters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProjectionBase {
+    ellipsoid: Ellipsoid,
+    lng0: f64,
+    lat0: f64,
+    false_easting: f64,
+    false_northing: f64,
+    unit: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mercator {
+    base: ProjectionBase,
+    k0: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransverseMercator {
+    base: ProjectionBase,
+    k0: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LambertConformalConic {
+    base: ProjectionBase,
+    n: f64,
+    /// Product of the semi-major axis, the F constant, and the scale factor.
+    a_f: f64,
+    rho0: f64,
+}
+
+/// Read the [`Crs`] for the shapefile at `path` from its `.prj` file. Shapefiles without a `.prj`
+/// are assumed to be lng/lat, as they always have been.
+pub fn read_prj(path: &PathBuf) -> Result<Crs, Error> {
+    let prj_path = path.with_extension("prj");
+    if !prj_path.exists() {
+        return Ok(Crs::Geographic);
+    }
+
+    let wkt = read_to_string(&prj_path).map_err(|_| Error::CannotReadFile(prj_path.clone()))?;
+    let node =
+        parse_wkt(&mut wkt.chars().peekable()).ok_or(Error::CannotParseFile(prj_path.clone()))?;
+
+    Crs::from_wkt(&node)
+}
+
+impl Crs {
+    /// Build the system from the root node of a WKT definition.
+    fn from_wkt(node: &WktNode) -> Result<Self, Error> {
+        match node.keyword.as_str() {
+            "GEOGCS" | "GEOGCRS" => check_geogcs(node).map(|_| Self::Geographic),
+            "PROJCS" => {
+                let geogcs = node
+                    .child("GEOGCS")
+                    .ok_or(Error::UnsupportedCrs(node.text(0).unwrap_or_default()))?;
+                check_geogcs(geogcs)?;
+                Self::from_projcs(node)
+            }
+            _ => Err(Error::UnsupportedCrs(node.keyword.clone())),
+        }
+    }
+
+    fn from_projcs(node: &WktNode) -> Result<Self, Error> {
+        let name = node
+            .child("PROJECTION")
+            .and_then(|p| p.text(0))
+            .ok_or(Error::UnsupportedCrs(node.text(0).unwrap_or_default()))?;
+        let unsupported = || Error::UnsupportedCrs(name.clone());
+
+        let spheroid = node
+            .child("GEOGCS")
+            .and_then(|g| g.child("DATUM"))
+            .and_then(|d| d.child("SPHEROID").or(d.child("ELLIPSOID")))
+            .ok_or_else(unsupported)?;
+        let a = spheroid.number(1).ok_or_else(unsupported)?;
+        let inv_f = spheroid.number(2).ok_or_else(unsupported)?;
+        let f = if inv_f == 0.0 { 0.0 } else { 1.0 / inv_f };
+
+        // Parameters are in degrees for angles, and in the linear unit for the false origin
+        let param = |name: &str| node.parameter(name);
+        let unit = node.child("UNIT").and_then(|u| u.number(1)).unwrap_or(1.0);
+        let base = ProjectionBase {
+            ellipsoid: Ellipsoid {
+                a,
+                e2: 2.0 * f - f * f,
+            },
+            lng0: param("central_meridian")
+                .or(param("longitude_of_origin"))
+                .unwrap_or(0.0)
+                .to_radians(),
+            lat0: param("latitude_of_origin").unwrap_or(0.0).to_radians(),
+            false_easting: param("false_easting").unwrap_or(0.0) * unit,
+            false_northing: param("false_northing").unwrap_or(0.0) * unit,
+            unit,
+        };
+        let k0 = param("scale_factor").unwrap_or(1.0);
+
+        // Web Mercator always projects onto a sphere with the radius of the semi-major axis
+        let sphere = |mut base: ProjectionBase| {
+            base.ellipsoid.e2 = 0.0;
+            Ok(Self::Mercator(Mercator { base, k0 }))
+        };
+
+        match normalize(&name).as_str() {
+            "mercator_auxiliary_sphere" | "popular_visualisation_pseudo_mercator" => sphere(base),
+            // Web Mercator is also written as plain Mercator, told apart by its code or name
+            "mercator" | "mercator_1sp" if is_web_mercator(node) => sphere(base),
+            "mercator" | "mercator_1sp" | "mercator_2sp" => {
+                // The two standard parallel variant sets the scale from the parallel instead
+                let k0 = match param("standard_parallel_1") {
+                    Some(lat1) => base.ellipsoid.m(lat1.to_radians()),
+                    None => k0,
+                };
+                Ok(Self::Mercator(Mercator { base, k0 }))
+            }
+            "transverse_mercator" => Ok(Self::TransverseMercator(TransverseMercator { base, k0 })),
+            "lambert_conformal_conic"
+            | "lambert_conformal_conic_1sp"
+            | "lambert_conformal_conic_2sp" => {
+                let lat1 = param("standard_parallel_1").map(f64::to_radians);
+                let lat2 = param("standard_parallel_2").map(f64::to_radians);
+                LambertConformalConic::new(base, k0, lat1, lat2)
+                    .map(Self::LambertConformalConic)
+                    .ok_or_else(unsupported)
+            }
+            _ => Err(unsupported()),
+        }
+    }
+
+    /// Convert a coordinate in this system to lng/lat degrees.
+    pub fn to_wgs84(&self, c: Coord) -> Coord {
+        let (lng, lat) = match self {
+            Self::Geographic => return c,
+            Self::Mercator(p) => p.inverse(p.base.shift(c)),
+            Self::TransverseMercator(p) => p.inverse(p.base.shift(c)),
+            Self::LambertConformalConic(p) => p.inverse(p.base.shift(c)),
+        };
+
+        Coord {
+            x: lng.to_degrees(),
+            y: lat.to_degrees(),
+        }
+    }
+
+    /// Convert a geometry in this system to lng/lat degrees in place.
+    pub fn reproject<G: MapCoordsInPlace<f64>>(&self, geom: &mut G) {
+        if *self != Self::Geographic {
+            geom.map_coords_in_place(|c| self.to_wgs84(c));
+        }
+    }
+
+    /// Convert a rectangle in this system to the lng/lat rectangle that contains it.
+    pub fn reproject_rect(&self, rect: Rect) -> Rect {
+        if *self == Self::Geographic {
+            return rect;
+        }
+
+        let (min, max) = (rect.min(), rect.max());
+        let (width, height) = (max.x - min.x, max.y - min.y);
+        let step = 1.0 / EDGE_SAMPLES as f64;
+        let coords = (0..=EDGE_SAMPLES).flat_map(|i| {
+            let t = i as f64 * step;
+            [
+                Coord {
+                    x: min.x + t * width,
+                    y: min.y,
+                },
+                Coord {
+                    x: min.x + t * width,
+                    y: max.y,
+                },
+                Coord {
+                    x: min.x,
+                    y: min.y + t * height,
+                },
+                Coord {
+                    x: max.x,
+                    y: min.y + t * height,
+                },
+            ]
+        });
+
+        let mut out_min = Coord {
+            x: f64::MAX,
+            y: f64::MAX,
+        };
+        let mut out_max = Coord {
+            x: f64::MIN,
+            y: f64::MIN,
+        };
+        for c in coords.map(|c| self.to_wgs84(c)) {
+            out_min = Coord {
+                x: out_min.x.min(c.x),
+                y: out_min.y.min(c.y),
+            };
+            out_max = Coord {
+                x: out_max.x.max(c.x),
+                y: out_max.y.max(c.y),
+            };
+        }
+
+        Rect::new(out_min, out_max)
+    }
+}
+
+impl Ellipsoid {
+    fn e(&self) -> f64 {
+        self.e2.sqrt()
+    }
+
+    /// Ratio of the parallel radius to the semi-major axis at `lat`.
+    fn m(&self, lat: f64) -> f64 {
+        lat.cos() / (1.0 - self.e2 * lat.sin().powi(2)).sqrt()
+    }
+
+    /// Isometric latitude function used by the conformal projections.
+    fn t(&self, lat: f64) -> f64 {
+        let e_sin = self.e() * lat.sin();
+        (FRAC_PI_4 - lat / 2.0).tan() / ((1.0 - e_sin) / (1.0 + e_sin)).powf(self.e() / 2.0)
+    }
+
+    /// Invert [`Self::t`] by iteration.
+    fn lat_from_t(&self, t: f64) -> f64 {
+        let e = self.e();
+        let mut lat = FRAC_PI_2 - 2.0 * t.atan();
+        for _ in 0..MAX_ITERATIONS {
+            let e_sin = e * lat.sin();
+            let next = FRAC_PI_2 - 2.0 * (t * ((1.0 - e_sin) / (1.0 + e_sin)).powf(e / 2.0)).atan();
+            let done = (next - lat).abs() < TOLERANCE;
+            lat = next;
+            if done {
+                break;
+            }
+        }
+
+        lat
+    }
+
+    /// Meridian distance from the equator to `lat`.
+    fn meridian_distance(&self, lat: f64) -> f64 {
+        let (e2, e4, e6) = (self.e2, self.e2.powi(2), self.e2.powi(3));
+        self.a
+            * ((1.0 - e2 / 4.0 - 3.0 * e4 / 64.0 - 5.0 * e6 / 256.0) * lat
+                - (3.0 * e2 / 8.0 + 3.0 * e4 / 32.0 + 45.0 * e6 / 1024.0) * (2.0 * lat).sin()
+                + (15.0 * e4 / 256.0 + 45.0 * e6 / 1024.0) * (4.0 * lat).sin()
+                - (35.0 * e6 / 3072.0) * (6.0 * lat).sin())
+    }
+}
+
+impl ProjectionBase {
+    /// Convert a coordinate to meters relative to the false origin.
+    fn shift(&self, c: Coord) -> (f64, f64) {
+        (
+            c.x * self.unit - self.false_easting,
+            c.y * self.unit - self.false_northing,
+        )
+    }
+}
+
+impl Mercator {
+    fn inverse(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let Ellipsoid { a, .. } = self.base.ellipsoid;
+        let t = (-y / (a * self.k0)).exp();
+
+        (
+            x / (a * self.k0) + self.base.lng0,
+            self.base.ellipsoid.lat_from_t(t),
+        )
+    }
+}
+
+impl TransverseMercator {
+    /// Inverse of the ellipsoidal transverse Mercator, using the series from Snyder's Map
+    /// Projections: A Working Manual.
+    fn inverse(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let ellipsoid = self.base.ellipsoid;
+        let Ellipsoid { a, e2 } = ellipsoid;
+        let ep2 = e2 / (1.0 - e2);
+
+        // Footpoint latitude
+        let m = ellipsoid.meridian_distance(self.base.lat0) + y / self.k0;
+        let mu = m / (a * (1.0 - e2 / 4.0 - 3.0 * e2.powi(2) / 64.0 - 5.0 * e2.powi(3) / 256.0));
+        let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+        let lat1 = mu
+            + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+            + (21.0 * e1.powi(2) / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+            + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+            + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+
+        let (sin1, cos1, tan1) = (lat1.sin(), lat1.cos(), lat1.tan());
+        let c1 = ep2 * cos1.powi(2);
+        let t1 = tan1.powi(2);
+        let n1 = a / (1.0 - e2 * sin1.powi(2)).sqrt();
+        let r1 = a * (1.0 - e2) / (1.0 - e2 * sin1.powi(2)).powf(1.5);
+        let d = x / (n1 * self.k0);
+
+        let lat = lat1
+            - (n1 * tan1 / r1)
+                * (d.powi(2) / 2.0
+                    - (5.0 + 3.0 * t1 + 10.0 * c1 - 4.0 * c1.powi(2) - 9.0 * ep2) * d.powi(4)
+                        / 24.0
+                    + (61.0 + 90.0 * t1 + 298.0 * c1 + 45.0 * t1.powi(2)
+                        - 252.0 * ep2
+                        - 3.0 * c1.powi(2))
+                        * d.powi(6)
+                        / 720.0);
+        let lng = self.base.lng0
+            + (d - (1.0 + 2.0 * t1 + c1) * d.powi(3) / 6.0
+                + (5.0 - 2.0 * c1 + 28.0 * t1 - 3.0 * c1.powi(2) + 8.0 * ep2 + 24.0 * t1.powi(2))
+                    * d.powi(5)
+                    / 120.0)
+                / cos1;
+
+        (lng, lat)
+    }
+}
+
+impl LambertConformalConic {
+    /// Set up the cone from either one standard parallel at the latitude of origin with a scale
+    /// factor, or two standard parallels. Returns `None` if the parallels do not define a cone.
+    fn new(base: ProjectionBase, k0: f64, lat1: Option<f64>, lat2: Option<f64>) -> Option<Self> {
+        let ellipsoid = base.ellipsoid;
+        let lat1 = lat1.unwrap_or(base.lat0);
+        let lat2 = lat2.unwrap_or(lat1);
+
+        let (m1, t1) = (ellipsoid.m(lat1), ellipsoid.t(lat1));
+        let n = if (lat1 - lat2).abs() < TOLERANCE {
+            lat1.sin()
+        } else {
+            (m1.ln() - ellipsoid.m(lat2).ln()) / (t1.ln() - ellipsoid.t(lat2).ln())
+        };
+        if n == 0.0 || !n.is_finite() {
+            return None;
+        }
+
+        let a_f = ellipsoid.a * m1 / (n * t1.powf(n)) * k0;
+        let rho0 = a_f * ellipsoid.t(base.lat0).powf(n);
+
+        Some(Self { base, n, a_f, rho0 })
+    }
+
+    fn inverse(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let sign = self.n.signum();
+        let dy = self.rho0 - y;
+        let rho = sign * (x.powi(2) + dy.powi(2)).sqrt();
+        let theta = (sign * x).atan2(sign * dy);
+        let t = (rho / self.a_f).powf(1.0 / self.n);
+
+        (
+            theta / self.n + self.base.lng0,
+            self.base.ellipsoid.lat_from_t(t),
+        )
+    }
+}
+
+/// Check that a geographic system can be read as WGS84 lng/lat degrees: the datum must be one of
+/// [`DATUMS`] without a shift, the prime meridian must be Greenwich, and the unit degrees.
+fn check_geogcs(node: &WktNode) -> Result<(), Error> {
+    let datum = node
+        .child("DATUM")
+        .ok_or(Error::UnsupportedCrs(node.text(0).unwrap_or_default()))?;
+    let datum_name = datum.text(0).unwrap_or_default();
+    let normalized = normalize(&datum_name);
+    let normalized = normalized.strip_prefix("d_").unwrap_or(&normalized);
+    if !DATUMS.contains(&normalized) {
+        return Err(Error::UnsupportedCrs(datum_name));
+    }
+
+    // A zero shift is common for the datums read as WGS84, anything else moves the coordinates
+    let shifted = datum.child("TOWGS84").is_some_and(|t| {
+        t.values
+            .iter()
+            .any(|v| matches!(v, WktValue::Number(n) if *n != 0.0))
+    });
+    if shifted {
+        return Err(Error::UnsupportedCrs(datum_name));
+    }
+
+    let primem = node.child("PRIMEM");
+    if let Some(primem) = primem.filter(|p| p.number(1).is_some_and(|lng| lng != 0.0)) {
+        return Err(Error::UnsupportedCrs(primem.text(0).unwrap_or_default()));
+    }
+
+    let unit = node.child("UNIT");
+    let degrees = |u: f64| (u - PI / 180.0).abs() < UNIT_TOLERANCE;
+    if let Some(unit) = unit.filter(|u| u.number(1).is_some_and(|u| !degrees(u))) {
+        return Err(Error::UnsupportedCrs(unit.text(0).unwrap_or_default()));
+    }
+
+    Ok(())
+}
+
+/// Whether a projected system is Web Mercator, from its EPSG code or its name.
+fn is_web_mercator(node: &WktNode) -> bool {
+    let code = node
+        .child("AUTHORITY")
+        .filter(|a| a.text(0).is_some_and(|s| s.eq_ignore_ascii_case("EPSG")))
+        .and_then(|a| a.text(1).or(a.number(1).map(|n| n.to_string())));
+    let name = normalize(&node.text(0).unwrap_or_default());
+
+    code.is_some_and(|c| WEB_MERCATOR_CODES.contains(&c.as_str()))
+        || name.contains("pseudo_mercator")
+        || name.contains("web_mercator")
+}
+
+/// Lower case a WKT name and replace spaces and hyphens with underscores, for matching.
+fn normalize(name: &str) -> String {
+    name.to_lowercase().replace([' ', '-'], "_")
+}
+
+/// Node in a parsed WKT definition, for example `UNIT["Meter",1.0]`.
+struct WktNode {
+    keyword: String,
+    values: Vec<WktValue>,
+}
+
+enum WktValue {
+    Text(String),
+    Number(f64),
+    Node(WktNode),
+}
+
+impl WktNode {
+    /// The first child node with the keyword, ignoring case.
+    fn child(&self, keyword: &str) -> Option<&WktNode> {
+        self.values.iter().find_map(|v| match v {
+            WktValue::Node(n) if n.keyword.eq_ignore_ascii_case(keyword) => Some(n),
+            _ => None,
+        })
+    }
+
+    fn text(&self, i: usize) -> Option<String> {
+        match self.values.get(i)? {
+            WktValue::Text(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    fn number(&self, i: usize) -> Option<f64> {
+        match self.values.get(i)? {
+            WktValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The value of the named `PARAMETER` child, ignoring case.
+    fn parameter(&self, name: &str) -> Option<f64> {
+        self.values.iter().find_map(|v| match v {
+            WktValue::Node(n)
+                if n.keyword.eq_ignore_ascii_case("PARAMETER")
+                    && n.text(0).is_some_and(|s| s.eq_ignore_ascii_case(name)) =>
+            {
+                n.number(1)
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Parse a WKT node, including any children. Bare keywords without brackets, such as the axis
+/// directions, are returned as nodes without values.
+fn parse_wkt(chars: &mut Peekable<Chars>) -> Option<WktNode> {
+    skip_whitespace(chars);
+    let mut keyword = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+        keyword.push(c);
+    }
+    if keyword.is_empty() {
+        return None;
+    }
+    let keyword = keyword.to_uppercase();
+
+    skip_whitespace(chars);
+    if chars.next_if(|c| *c == '[' || *c == '(').is_none() {
+        return Some(WktNode {
+            keyword,
+            values: Vec::new(),
+        });
+    }
+
+    let mut values = Vec::new();
+    loop {
+        skip_whitespace(chars);
+        let value = match *chars.peek()? {
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next()? {
+                        // Quotes inside text are escaped by doubling
+                        '"' if chars.next_if_eq(&'"').is_some() => text.push('"'),
+                        '"' => break,
+                        c => text.push(c),
+                    }
+                }
+                WktValue::Text(text)
+            }
+            c if c.is_ascii_digit() || matches!(c, '-' | '+' | '.') => {
+                let mut number = String::new();
+                while let Some(c) = chars
+                    .next_if(|c| c.is_ascii_digit() || matches!(*c, '-' | '+' | '.' | 'e' | 'E'))
+                {
+                    number.push(c);
+                }
+                WktValue::Number(number.parse().ok()?)
+            }
+            _ => WktValue::Node(parse_wkt(chars)?),
+        };
+        values.push(value);
+
+        skip_whitespace(chars);
+        match chars.next()? {
+            ',' => continue,
+            ']' | ')' => break,
+            _ => return None,
+        }
+    }
+
+    Some(WktNode { keyword, values })
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Allowed difference from the EPSG examples in degrees, about 0.1 meters.
+    const DEGREES_TOLERANCE: f64 = 1e-6;
+
+    const WGS84: &str = r#"GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563]],PRIMEM["Greenwich",0],UNIT["degree",0.0174532925199433]]"#;
+
+    fn parse(wkt: &str) -> WktNode {
+        parse_wkt(&mut wkt.chars().peekable()).unwrap()
+    }
+
+    /// Build a projected system without checking the datum, since most of the EPSG examples are on
+    /// older datums.
+    fn projcs(geogcs: &str, projection: &str, params: &[(&str, f64)], unit: f64) -> Crs {
+        let params: String = params
+            .iter()
+            .map(|(name, value)| format!(r#",PARAMETER["{}",{}]"#, name, value))
+            .collect();
+        let wkt = format!(
+            r#"PROJCS["Example",{},PROJECTION["{}"]{},UNIT["unit",{}]]"#,
+            geogcs, projection, params, unit
+        );
+
+        Crs::from_projcs(&parse(&wkt)).unwrap()
+    }
+
+    /// EPSG worked examples project a known lng/lat forward, so inverting the projected point
+    /// must return to it.
+    fn assert_round_trip(crs: &Crs, projected: (f64, f64), lng_lat: (f64, f64)) {
+        let c = crs.to_wgs84(Coord {
+            x: projected.0,
+            y: projected.1,
+        });
+        assert!(
+            (c.x - lng_lat.0).abs() < DEGREES_TOLERANCE,
+            "lng {} != {}",
+            c.x,
+            lng_lat.0
+        );
+        assert!(
+            (c.y - lng_lat.1).abs() < DEGREES_TOLERANCE,
+            "lat {} != {}",
+            c.y,
+            lng_lat.1
+        );
+    }
+
+    fn dms(d: f64, m: f64, s: f64) -> f64 {
+        d.signum() * (d.abs() + m / 60.0 + s / 3600.0)
+    }
+
+    #[test]
+    fn web_mercator_by_code() {
+        let wkt = format!(
+            r#"PROJCS["WGS 84 / Pseudo-Mercator",{},PROJECTION["Mercator_1SP"],PARAMETER["central_meridian",0],PARAMETER["scale_factor",1],PARAMETER["false_easting",0],PARAMETER["false_northing",0],UNIT["metre",1],AUTHORITY["EPSG","3857"]]"#,
+            WGS84
+        );
+        let crs = Crs::from_wkt(&parse(&wkt)).unwrap();
+
+        assert_round_trip(
+            &crs,
+            (-11169055.58, 2800000.00),
+            (dms(-100.0, 20.0, 0.0), dms(24.0, 22.0, 54.433)),
+        );
+    }
+
+    #[test]
+    fn web_mercator_by_name() {
+        let wkt = format!(
+            r#"PROJCS["WGS 84 / Pseudo-Mercator",{},PROJECTION["Mercator"],UNIT["metre",1]]"#,
+            WGS84
+        );
+        let crs = Crs::from_wkt(&parse(&wkt)).unwrap();
+
+        assert_round_trip(
+            &crs,
+            (-11169055.58, 2800000.00),
+            (dms(-100.0, 20.0, 0.0), dms(24.0, 22.0, 54.433)),
+        );
+    }
+
+    #[test]
+    fn web_mercator_auxiliary_sphere() {
+        let wkt = format!(
+            r#"PROJCS["WGS_1984_Web_Mercator_Auxiliary_Sphere",{},PROJECTION["Mercator_Auxiliary_Sphere"],PARAMETER["Central_Meridian",0],UNIT["Meter",1]]"#,
+            WGS84.replace("WGS_1984", "D_WGS_1984")
+        );
+        let crs = Crs::from_wkt(&parse(&wkt)).unwrap();
+
+        assert_round_trip(
+            &crs,
+            (-11169055.58, 2800000.00),
+            (dms(-100.0, 20.0, 0.0), dms(24.0, 22.0, 54.433)),
+        );
+    }
+
+    #[test]
+    fn mercator_1sp() {
+        let crs = projcs(
+            r#"GEOGCS["Batavia",DATUM["Batavia",SPHEROID["Bessel 1841",6377397.155,299.1528128]]]"#,
+            "Mercator_1SP",
+            &[
+                ("central_meridian", 110.0),
+                ("scale_factor", 0.997),
+                ("false_easting", 3900000.0),
+                ("false_northing", 900000.0),
+            ],
+            1.0,
+        );
+
+        assert_round_trip(&crs, (5009726.58, 569150.82), (120.0, -3.0));
+    }
+
+    #[test]
+    fn mercator_2sp() {
+        let crs = projcs(
+            r#"GEOGCS["Pulkovo 1942",DATUM["Pulkovo_1942",SPHEROID["Krassowsky 1940",6378245,298.3]]]"#,
+            "Mercator_2SP",
+            &[("standard_parallel_1", 42.0), ("central_meridian", 51.0)],
+            1.0,
+        );
+
+        assert_round_trip(&crs, (165704.29, 5171848.07), (53.0, 53.0));
+    }
+
+    #[test]
+    fn transverse_mercator() {
+        let crs = projcs(
+            r#"GEOGCS["OSGB 1936",DATUM["OSGB_1936",SPHEROID["Airy 1830",6377563.396,299.3249646]]]"#,
+            "Transverse_Mercator",
+            &[
+                ("latitude_of_origin", 49.0),
+                ("central_meridian", -2.0),
+                ("scale_factor", 0.9996012717),
+                ("false_easting", 400000.0),
+                ("false_northing", -100000.0),
+            ],
+            1.0,
+        );
+
+        assert_round_trip(&crs, (577274.99, 69740.49), (0.5, 50.5));
+    }
+
+    #[test]
+    fn lambert_conformal_conic_1sp() {
+        let crs = projcs(
+            r#"GEOGCS["JAD69",DATUM["Jamaica_1969",SPHEROID["Clarke 1866",6378206.4,294.9786982]]]"#,
+            "Lambert_Conformal_Conic_1SP",
+            &[
+                ("latitude_of_origin", 18.0),
+                ("central_meridian", -77.0),
+                ("scale_factor", 1.0),
+                ("false_easting", 250000.0),
+                ("false_northing", 150000.0),
+            ],
+            1.0,
+        );
+
+        assert_round_trip(
+            &crs,
+            (255966.58, 142493.51),
+            (dms(-76.0, 56.0, 37.26), dms(17.0, 55.0, 55.80)),
+        );
+    }
+
+    #[test]
+    fn lambert_conformal_conic_2sp() {
+        let crs = projcs(
+            r#"GEOGCS["NAD27",DATUM["North_American_Datum_1927",SPHEROID["Clarke 1866",6378206.4,294.9786982]]]"#,
+            "Lambert_Conformal_Conic_2SP",
+            &[
+                ("standard_parallel_1", dms(28.0, 23.0, 0.0)),
+                ("standard_parallel_2", dms(30.0, 17.0, 0.0)),
+                ("latitude_of_origin", dms(27.0, 50.0, 0.0)),
+                ("central_meridian", -99.0),
+                ("false_easting", 2000000.0),
+                ("false_northing", 0.0),
+            ],
+            0.3048006096012192,
+        );
+
+        assert_round_trip(&crs, (2963503.91, 254759.80), (-96.0, 28.5));
+    }
+
+    #[test]
+    fn accepts_modern_datums() {
+        for datum in [
+            r#"DATUM["North_American_Datum_1983",SPHEROID["GRS 1980",6378137,298.257222101],TOWGS84[0,0,0,0,0,0,0]]"#,
+            r#"DATUM["D_North_American_1983",SPHEROID["GRS_1980",6378137,298.257222101]]"#,
+            r#"DATUM["European_Terrestrial_Reference_System_1989",SPHEROID["GRS 1980",6378137,298.257222101]]"#,
+            r#"DATUM["D_ETRS_1989",SPHEROID["GRS_1980",6378137,298.257222101]]"#,
+        ] {
+            let wkt = format!(r#"GEOGCS["Example",{}]"#, datum);
+            assert_eq!(Crs::from_wkt(&parse(&wkt)).unwrap(), Crs::Geographic);
+        }
+    }
+
+    #[test]
+    fn rejects_other_datums() {
+        for datum in [
+            r#"DATUM["North_American_Datum_1927",SPHEROID["Clarke 1866",6378206.4,294.9786982]]"#,
+            r#"DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563],TOWGS84[1,2,3,0,0,0,0]]"#,
+        ] {
+            let wkt = format!(r#"GEOGCS["Example",{}]"#, datum);
+            assert!(matches!(
+                Crs::from_wkt(&parse(&wkt)),
+                Err(Error::UnsupportedCrs(_))
+            ));
+        }
+
+        let wkt = format!(
+            r#"PROJCS["OSGB 1936 / British National Grid",{},PROJECTION["Transverse_Mercator"]]"#,
+            r#"GEOGCS["OSGB 1936",DATUM["OSGB_1936",SPHEROID["Airy 1830",6377563.396,299.3249646]]]"#
+        );
+        assert!(matches!(
+            Crs::from_wkt(&parse(&wkt)),
+            Err(Error::UnsupportedCrs(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_other_meridians_and_units() {
+        for wkt in [
+            WGS84.replace(r#"PRIMEM["Greenwich",0]"#, r#"PRIMEM["Paris",2.33722917]"#),
+            WGS84.replace(
+                r#"UNIT["degree",0.0174532925199433]"#,
+                r#"UNIT["grad",0.01570796326794897]"#,
+            ),
+        ] {
+            assert!(matches!(
+                Crs::from_wkt(&parse(&wkt)),
+                Err(Error::UnsupportedCrs(_))
+            ));
+        }
+    }
+}
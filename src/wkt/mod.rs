@@ -0,0 +1,456 @@
+mod wkb;
+mod write;
+
+use geo::MapCoordsInPlace;
+use quadtree::Geometry;
+
+use crate::error::Error;
+
+pub use self::wkb::{parse_hex_wkb, parse_wkb};
+pub use self::write::write_wkt;
+
+/// Parse a geometry from either WKT or hex-encoded WKB, detected from the content. Hex WKB is only
+/// ever made of hex digits, which no WKT can be.
+pub fn parse_geometry_text(s: &str) -> Result<geo::Geometry<f64>, Error> {
+    let s = s.trim();
+    let hex = s
+        .strip_prefix("\\x")
+        .or_else(|| s.strip_prefix("0x"))
+        .unwrap_or(s);
+
+    if !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        parse_hex_wkb(s)
+    } else {
+        parse_wkt(s)
+    }
+}
+
+/// Parse a geometry from Well-Known Text. Z and M coordinates are read but discarded, and an EWKT
+/// `SRID=..;` prefix is skipped, so coordinates are assumed to be lng/lat.
+pub fn parse_wkt(s: &str) -> Result<geo::Geometry<f64>, Error> {
+    let s = s.trim();
+    // The SRID is ignored, so take everything after the prefix
+    let s = match s.get(..5) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("srid=") => {
+            s.split_once(';').map(|(_, s)| s).unwrap_or_default()
+        }
+        _ => s,
+    };
+
+    let mut parser = WktParser { s, pos: 0 };
+    let geometry = parser.geometry()?;
+
+    parser.skip_whitespace();
+    if parser.pos < s.len() {
+        return Err(parser.error("unexpected trailing text"));
+    }
+
+    Ok(geometry)
+}
+
+/// Flatten a geometry into the quadtree geometries that make it up, converting to radians. See
+/// [`split_geometry`] for how it is broken up.
+pub fn flatten_geometry(mut geometry: geo::Geometry<f64>) -> Vec<Geometry<f64>> {
+    geometry.map_coords_in_place(|c| geo::coord! { x: c.x.to_radians(), y: c.y.to_radians() });
+
+    split_geometry(geometry)
+}
+
+/// Split a geometry into the quadtree geometries that make it up, keeping its coordinates. Multi
+/// geometries and GeometryCollections at any depth are broken up into their parts, and Lines,
+/// Rects and Triangles become LineStrings and Polygons. LineStrings with fewer than two points and
+/// Polygons without an exterior are dropped, so empty geometries have no parts.
+pub fn split_geometry(geometry: geo::Geometry<f64>) -> Vec<Geometry<f64>> {
+    let mut parts = Vec::new();
+    flatten_into(geometry, &mut parts);
+
+    parts
+}
+
+fn flatten_into(geometry: geo::Geometry<f64>, parts: &mut Vec<Geometry<f64>>) {
+    match geometry {
+        geo::Geometry::Point(p) => parts.push(Geometry::Point(p)),
+        geo::Geometry::Line(l) => parts.push(Geometry::LineString(l.into())),
+        geo::Geometry::LineString(l) if l.0.len() < 2 => {}
+        geo::Geometry::LineString(l) => parts.push(Geometry::LineString(l)),
+        geo::Geometry::Polygon(p) if p.exterior().0.is_empty() => {}
+        geo::Geometry::Polygon(p) => parts.push(Geometry::Polygon(p)),
+        geo::Geometry::MultiPoint(mp) => parts.extend(mp.into_iter().map(Geometry::Point)),
+        geo::Geometry::MultiLineString(mls) => {
+            for l in mls {
+                flatten_into(geo::Geometry::LineString(l), parts);
+            }
+        }
+        geo::Geometry::MultiPolygon(mp) => {
+            for p in mp {
+                flatten_into(geo::Geometry::Polygon(p), parts);
+            }
+        }
+        geo::Geometry::GeometryCollection(gc) => {
+            for g in gc {
+                flatten_into(g, parts);
+            }
+        }
+        geo::Geometry::Rect(r) => parts.push(Geometry::Polygon(r.to_polygon())),
+        geo::Geometry::Triangle(t) => parts.push(Geometry::Polygon(t.to_polygon())),
+    }
+}
+
+/// Recursive descent parser over WKT text.
+struct WktParser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl WktParser<'_> {
+    fn geometry(&mut self) -> Result<geo::Geometry<f64>, Error> {
+        let tag = self.word().to_ascii_uppercase();
+        // Dimensions can be attached to the tag, as in POINTZ, or a separate word
+        let tag = tag
+            .strip_suffix("ZM")
+            .or_else(|| tag.strip_suffix('Z'))
+            .or_else(|| tag.strip_suffix('M'))
+            .filter(|t| is_tag(t))
+            .unwrap_or(&tag)
+            .to_string();
+        if !is_tag(&tag) {
+            return Err(self.error("unknown geometry type"));
+        }
+
+        self.skip_whitespace();
+        if self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            let word = self.word().to_ascii_uppercase();
+            match word.as_str() {
+                "Z" | "M" | "ZM" => {}
+                "EMPTY" => return Ok(empty(&tag)),
+                _ => return Err(self.error("unexpected word")),
+            }
+        }
+
+        self.skip_whitespace();
+        if self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            if self.word().eq_ignore_ascii_case("EMPTY") {
+                return Ok(empty(&tag));
+            }
+            return Err(self.error("unexpected word"));
+        }
+
+        let geometry = match tag.as_str() {
+            "POINT" => {
+                self.expect('(')?;
+                let point = self.coord()?;
+                self.expect(')')?;
+                geo::Geometry::Point(point.into())
+            }
+            "LINESTRING" => geo::Geometry::LineString(self.line_string()?),
+            "POLYGON" => geo::Geometry::Polygon(self.polygon()?),
+            "MULTIPOINT" => {
+                // Points in a MultiPoint may or may not be wrapped in their own parentheses
+                let points = self.list(|p| {
+                    p.skip_whitespace();
+                    if p.peek() == Some('(') {
+                        p.expect('(')?;
+                        let point = p.coord()?;
+                        p.expect(')')?;
+                        Ok(point)
+                    } else {
+                        p.coord()
+                    }
+                })?;
+                geo::Geometry::MultiPoint(points.into_iter().map(geo::Point::from).collect())
+            }
+            "MULTILINESTRING" => {
+                geo::Geometry::MultiLineString(geo::MultiLineString(self.list(Self::line_string)?))
+            }
+            "MULTIPOLYGON" => {
+                geo::Geometry::MultiPolygon(geo::MultiPolygon(self.list(Self::polygon)?))
+            }
+            _ => geo::Geometry::GeometryCollection(geo::GeometryCollection(
+                self.list(Self::geometry)?,
+            )),
+        };
+
+        Ok(geometry)
+    }
+
+    fn line_string(&mut self) -> Result<geo::LineString<f64>, Error> {
+        Ok(geo::LineString(self.list(Self::coord)?))
+    }
+
+    fn polygon(&mut self) -> Result<geo::Polygon<f64>, Error> {
+        let mut rings = self.list(Self::line_string)?.into_iter();
+        let exterior = rings.next().unwrap_or_else(|| geo::LineString(Vec::new()));
+
+        Ok(geo::Polygon::new(exterior, rings.collect()))
+    }
+
+    /// Parse a parenthesised, comma separated list of items.
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, Error>,
+    ) -> Result<Vec<T>, Error> {
+        self.expect('(')?;
+
+        let mut items = vec![item(self)?];
+        loop {
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => items.push(item(self)?),
+                Some(')') => return Ok(items),
+                _ => return Err(self.error("expected ',' or ')'")),
+            }
+        }
+    }
+
+    /// Parse a coordinate, discarding any Z and M values.
+    fn coord(&mut self) -> Result<geo::Coord<f64>, Error> {
+        let x = self.number()?;
+        let y = self.number()?;
+
+        self.skip_whitespace();
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'))
+        {
+            self.number()?;
+            self.skip_whitespace();
+        }
+
+        Ok(geo::coord! { x: x, y: y })
+    }
+
+    fn number(&mut self) -> Result<f64, Error> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.'))
+        {
+            self.pos += 1;
+        }
+
+        self.s[start..self.pos]
+            .parse()
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    fn word(&mut self) -> &str {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+
+        &self.s[start..self.pos]
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        match self.bump() {
+            Some(next) if next == c => Ok(()),
+            _ => Err(self.error(&format!("expected '{c}'"))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.s.as_bytes().get(self.pos).map(|b| *b as char)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+
+        c
+    }
+
+    fn error(&self, reason: &str) -> Error {
+        Error::InvalidWkt(format!("{reason} at position {}", self.pos))
+    }
+}
+
+fn is_tag(tag: &str) -> bool {
+    matches!(
+        tag,
+        "POINT"
+            | "LINESTRING"
+            | "POLYGON"
+            | "MULTIPOINT"
+            | "MULTILINESTRING"
+            | "MULTIPOLYGON"
+            | "GEOMETRYCOLLECTION"
+    )
+}
+
+/// An empty geometry of the type `tag`. There is no empty Point, so it is an empty MultiPoint.
+fn empty(tag: &str) -> geo::Geometry<f64> {
+    match tag {
+        "LINESTRING" => geo::Geometry::LineString(geo::LineString(Vec::new())),
+        "POINT" | "MULTIPOINT" => geo::Geometry::MultiPoint(geo::MultiPoint(Vec::new())),
+        "MULTILINESTRING" => geo::Geometry::MultiLineString(geo::MultiLineString(Vec::new())),
+        "POLYGON" | "MULTIPOLYGON" => geo::Geometry::MultiPolygon(geo::MultiPolygon(Vec::new())),
+        _ => geo::Geometry::GeometryCollection(geo::GeometryCollection(Vec::new())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    use geo::{coord, line_string, point, polygon};
+
+    use super::*;
+
+    #[test]
+    fn parses_simple_geometries() {
+        assert_eq!(
+            parse_wkt("POINT (1 2)").unwrap(),
+            geo::Geometry::Point(point! { x: 1.0, y: 2.0 })
+        );
+        assert_eq!(
+            parse_wkt(" linestring(0 0,1.5 -2e1) ").unwrap(),
+            geo::Geometry::LineString(line_string![(x: 0.0, y: 0.0), (x: 1.5, y: -20.0)])
+        );
+        assert_eq!(
+            parse_wkt("POLYGON ((0 0, 1 0, 1 1, 0 0), (0.1 0.1, 0.2 0.1, 0.2 0.2, 0.1 0.1))")
+                .unwrap(),
+            geo::Geometry::Polygon(polygon!(
+                exterior: [(x: 0.0, y: 0.0), (x: 1.0, y: 0.0), (x: 1.0, y: 1.0), (x: 0.0, y: 0.0)],
+                interiors: [[(x: 0.1, y: 0.1), (x: 0.2, y: 0.1), (x: 0.2, y: 0.2), (x: 0.1, y: 0.1)]],
+            ))
+        );
+        assert_eq!(
+            parse_wkt("MULTIPOINT ((1 2), 3 4)").unwrap(),
+            geo::Geometry::MultiPoint(geo::MultiPoint(vec![
+                point! { x: 1.0, y: 2.0 },
+                point! { x: 3.0, y: 4.0 },
+            ]))
+        );
+    }
+
+    #[test]
+    fn discards_z_and_m() {
+        let expected = geo::Geometry::Point(point! { x: 1.0, y: 2.0 });
+        for wkt in [
+            "POINT Z (1 2 3)",
+            "POINTZ(1 2 3)",
+            "POINT M (1 2 4)",
+            "POINT ZM (1 2 3 4)",
+            "pointzm (1 2 3 4)",
+        ] {
+            assert_eq!(parse_wkt(wkt).unwrap(), expected, "{wkt}");
+        }
+
+        assert_eq!(
+            parse_wkt("LINESTRING ZM (0 0 1 2, 1 1 1 2)").unwrap(),
+            geo::Geometry::LineString(line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 1.0)])
+        );
+    }
+
+    #[test]
+    fn skips_ewkt_srid() {
+        assert_eq!(
+            parse_wkt("SRID=4326;POINT(1 2)").unwrap(),
+            geo::Geometry::Point(point! { x: 1.0, y: 2.0 })
+        );
+    }
+
+    #[test]
+    fn parses_empty_geometries() {
+        assert_eq!(
+            parse_wkt("POINT EMPTY").unwrap(),
+            geo::Geometry::MultiPoint(geo::MultiPoint(Vec::new()))
+        );
+        assert_eq!(
+            parse_wkt("LINESTRING EMPTY").unwrap(),
+            geo::Geometry::LineString(geo::LineString(Vec::new()))
+        );
+        assert_eq!(
+            parse_wkt("POLYGON Z EMPTY").unwrap(),
+            geo::Geometry::MultiPolygon(geo::MultiPolygon(Vec::new()))
+        );
+        assert_eq!(
+            parse_wkt("GEOMETRYCOLLECTION EMPTY").unwrap(),
+            geo::Geometry::GeometryCollection(geo::GeometryCollection(Vec::new()))
+        );
+    }
+
+    #[test]
+    fn parses_nested_collections() {
+        let geometry = parse_wkt(
+            "GEOMETRYCOLLECTION (POINT (1 2), GEOMETRYCOLLECTION (LINESTRING (0 0, 1 1), POINT EMPTY))",
+        )
+        .unwrap();
+
+        assert_eq!(
+            geometry,
+            geo::Geometry::GeometryCollection(geo::GeometryCollection(vec![
+                geo::Geometry::Point(point! { x: 1.0, y: 2.0 }),
+                geo::Geometry::GeometryCollection(geo::GeometryCollection(vec![
+                    geo::Geometry::LineString(line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 1.0)]),
+                    geo::Geometry::MultiPoint(geo::MultiPoint(Vec::new())),
+                ])),
+            ]))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_wkt() {
+        for wkt in [
+            "",
+            "POINT",
+            "POINT (1)",
+            "POINT (1 2",
+            "POINT (1 2) 3",
+            "CIRCLE (1 2)",
+            "POINT FULL",
+        ] {
+            assert!(parse_wkt(wkt).is_err(), "{wkt}");
+        }
+    }
+
+    #[test]
+    fn detects_wkt_or_hex_wkb() {
+        let expected = geo::Geometry::Point(point! { x: 1.0, y: 2.0 });
+
+        assert_eq!(parse_geometry_text("POINT (1 2)").unwrap(), expected);
+        assert_eq!(
+            parse_geometry_text("0101000000000000000000F03F0000000000000040").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_geometry_text("\\x0101000000000000000000F03F0000000000000040").unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn flattens_into_radians_without_empty_parts() {
+        let geometry = parse_wkt(
+            "GEOMETRYCOLLECTION (LINESTRING EMPTY, POLYGON EMPTY, MULTILINESTRING ((0 0), (0 0, 90 0)), POINT (180 0))",
+        )
+        .unwrap();
+        let parts = flatten_geometry(geometry);
+
+        assert_eq!(parts.len(), 2);
+        assert!(matches!(
+            &parts[0],
+            Geometry::LineString(l) if l.0 == vec![coord! { x: 0.0, y: 0.0 }, coord! { x: FRAC_PI_2, y: 0.0 }]
+        ));
+        assert!(matches!(&parts[1], Geometry::Point(p) if p.x() == PI && p.y() == 0.0));
+    }
+
+    #[test]
+    fn flattens_empty_wkb_line_string_to_nothing() {
+        let bytes = [1, 2, 0, 0, 0, 0, 0, 0, 0];
+
+        assert!(flatten_geometry(parse_wkb(&bytes).unwrap()).is_empty());
+    }
+}
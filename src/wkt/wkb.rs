@@ -0,0 +1,370 @@
+use crate::error::Error;
+
+/// EWKB flag for geometries with Z coordinates.
+const EWKB_Z: u32 = 0x8000_0000;
+/// EWKB flag for geometries with M coordinates.
+const EWKB_M: u32 = 0x4000_0000;
+/// EWKB flag for geometries followed by an SRID.
+const EWKB_SRID: u32 = 0x2000_0000;
+
+/// Parse a geometry from hex-encoded WKB, as written by most databases. A leading `\x` or `0x` is
+/// ignored.
+pub fn parse_hex_wkb(s: &str) -> Result<geo::Geometry<f64>, Error> {
+    let s = s.trim();
+    let s = s
+        .strip_prefix("\\x")
+        .or_else(|| s.strip_prefix("0x"))
+        .unwrap_or(s);
+    if !s.len().is_multiple_of(2) {
+        return Err(Error::InvalidWkb("odd number of hex digits".to_string()));
+    }
+
+    let bytes = (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| Error::InvalidWkb("invalid hex digit".to_string()))?;
+
+    parse_wkb(&bytes)
+}
+
+/// Parse a geometry from Well-Known Binary. Both ISO and extended (EWKB) geometry types are read.
+/// Z and M coordinates and any SRID are discarded, so coordinates are assumed to be lng/lat.
+pub fn parse_wkb(bytes: &[u8]) -> Result<geo::Geometry<f64>, Error> {
+    let mut reader = WkbReader { bytes, pos: 0 };
+    let geometry = reader.geometry()?;
+
+    if reader.pos < bytes.len() {
+        return Err(Error::InvalidWkb("unexpected trailing bytes".to_string()));
+    }
+
+    Ok(geometry)
+}
+
+struct WkbReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl WkbReader<'_> {
+    fn geometry(&mut self) -> Result<geo::Geometry<f64>, Error> {
+        let little_endian = match self.take(1)?[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(Error::InvalidWkb("invalid byte order".to_string())),
+        };
+
+        let code = self.u32(little_endian)?;
+        if code & EWKB_SRID != 0 {
+            self.u32(little_endian)?;
+        }
+
+        // ISO types add 1000 for Z, 2000 for M and 3000 for ZM to the base type
+        let iso = code & 0x0fff_ffff;
+        let (base, iso_dims) = (iso % 1000, iso / 1000);
+        let has_z = code & EWKB_Z != 0 || iso_dims == 1 || iso_dims == 3;
+        let has_m = code & EWKB_M != 0 || iso_dims == 2 || iso_dims == 3;
+        let dims = 2 + has_z as usize + has_m as usize;
+
+        let geometry = match base {
+            1 => {
+                let coord = self.coord(little_endian, dims)?;
+                // Empty points are written with NaN coordinates
+                if coord.x.is_nan() && coord.y.is_nan() {
+                    geo::Geometry::MultiPoint(geo::MultiPoint(Vec::new()))
+                } else {
+                    geo::Geometry::Point(coord.into())
+                }
+            }
+            2 => geo::Geometry::LineString(self.line_string(little_endian, dims)?),
+            3 => geo::Geometry::Polygon(self.polygon(little_endian, dims)?),
+            4..=7 => {
+                let n = self.u32(little_endian)?;
+                let parts = (0..n)
+                    .map(|_| self.geometry())
+                    .collect::<Result<Vec<_>, _>>()?;
+                multi(base, parts)?
+            }
+            _ => {
+                return Err(Error::InvalidWkb(format!(
+                    "unsupported geometry type {code}"
+                )))
+            }
+        };
+
+        Ok(geometry)
+    }
+
+    fn line_string(&mut self, le: bool, dims: usize) -> Result<geo::LineString<f64>, Error> {
+        let n = self.u32(le)?;
+        let coords = (0..n)
+            .map(|_| self.coord(le, dims))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(geo::LineString(coords))
+    }
+
+    fn polygon(&mut self, le: bool, dims: usize) -> Result<geo::Polygon<f64>, Error> {
+        let n = self.u32(le)?;
+        let mut rings = (0..n)
+            .map(|_| self.line_string(le, dims))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter();
+        let exterior = rings.next().unwrap_or_else(|| geo::LineString(Vec::new()));
+
+        Ok(geo::Polygon::new(exterior, rings.collect()))
+    }
+
+    /// Read a coordinate of `dims` values, keeping only x and y.
+    fn coord(&mut self, le: bool, dims: usize) -> Result<geo::Coord<f64>, Error> {
+        let x = self.f64(le)?;
+        let y = self.f64(le)?;
+        for _ in 2..dims {
+            self.f64(le)?;
+        }
+
+        Ok(geo::coord! { x: x, y: y })
+    }
+
+    fn u32(&mut self, le: bool) -> Result<u32, Error> {
+        let bytes: [u8; 4] = self.take(4)?.try_into().unwrap_or_default();
+
+        Ok(if le {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn f64(&mut self, le: bool) -> Result<f64, Error> {
+        let bytes: [u8; 8] = self.take(8)?.try_into().unwrap_or_default();
+
+        Ok(if le {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        })
+    }
+
+    fn take(&mut self, n: usize) -> Result<&[u8], Error> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| Error::InvalidWkb("unexpected end of input".to_string()))?;
+        self.pos += n;
+
+        Ok(bytes)
+    }
+}
+
+/// Build the multi geometry of WKB type `base` from its parts, checking each part is of the right
+/// type.
+fn multi(base: u32, parts: Vec<geo::Geometry<f64>>) -> Result<geo::Geometry<f64>, Error> {
+    let wrong_part = || Error::InvalidWkb(format!("invalid part in geometry type {base}"));
+
+    let geometry = match base {
+        4 => geo::Geometry::MultiPoint(geo::MultiPoint(
+            parts
+                .into_iter()
+                .filter_map(|p| match p {
+                    geo::Geometry::Point(p) => Some(Ok(p)),
+                    // Skip empty points
+                    geo::Geometry::MultiPoint(mp) if mp.0.is_empty() => None,
+                    _ => Some(Err(wrong_part())),
+                })
+                .collect::<Result<_, _>>()?,
+        )),
+        5 => geo::Geometry::MultiLineString(geo::MultiLineString(
+            parts
+                .into_iter()
+                .map(|p| match p {
+                    geo::Geometry::LineString(l) => Ok(l),
+                    _ => Err(wrong_part()),
+                })
+                .collect::<Result<_, _>>()?,
+        )),
+        6 => geo::Geometry::MultiPolygon(geo::MultiPolygon(
+            parts
+                .into_iter()
+                .map(|p| match p {
+                    geo::Geometry::Polygon(p) => Ok(p),
+                    _ => Err(wrong_part()),
+                })
+                .collect::<Result<_, _>>()?,
+        )),
+        _ => geo::Geometry::GeometryCollection(geo::GeometryCollection(parts)),
+    };
+
+    Ok(geometry)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{line_string, point};
+
+    use super::*;
+
+    /// Builder for WKB test input in either byte order.
+    struct Wkb {
+        le: bool,
+        bytes: Vec<u8>,
+    }
+
+    impl Wkb {
+        fn new(le: bool, code: u32) -> Self {
+            Self {
+                le,
+                bytes: vec![le as u8],
+            }
+            .u32(code)
+        }
+
+        fn u32(mut self, v: u32) -> Self {
+            let bytes = if self.le {
+                v.to_le_bytes()
+            } else {
+                v.to_be_bytes()
+            };
+            self.bytes.extend_from_slice(&bytes);
+            self
+        }
+
+        fn f64s(mut self, vs: &[f64]) -> Self {
+            for v in vs {
+                let bytes = if self.le {
+                    v.to_le_bytes()
+                } else {
+                    v.to_be_bytes()
+                };
+                self.bytes.extend_from_slice(&bytes);
+            }
+            self
+        }
+
+        fn part(mut self, part: Wkb) -> Self {
+            self.bytes.extend(part.bytes);
+            self
+        }
+    }
+
+    fn point_1_2() -> geo::Geometry<f64> {
+        geo::Geometry::Point(point! { x: 1.0, y: 2.0 })
+    }
+
+    #[test]
+    fn parses_either_byte_order() {
+        for le in [true, false] {
+            let wkb = Wkb::new(le, 1).f64s(&[1.0, 2.0]);
+            assert_eq!(parse_wkb(&wkb.bytes).unwrap(), point_1_2());
+
+            let wkb = Wkb::new(le, 2).u32(2).f64s(&[0.0, 0.0, 1.0, 1.0]);
+            assert_eq!(
+                parse_wkb(&wkb.bytes).unwrap(),
+                geo::Geometry::LineString(line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 1.0)])
+            );
+        }
+    }
+
+    #[test]
+    fn discards_iso_z_and_m() {
+        for (code, values) in [
+            (1001, &[1.0, 2.0, 3.0][..]),
+            (2001, &[1.0, 2.0, 4.0][..]),
+            (3001, &[1.0, 2.0, 3.0, 4.0][..]),
+        ] {
+            let wkb = Wkb::new(true, code).f64s(values);
+            assert_eq!(parse_wkb(&wkb.bytes).unwrap(), point_1_2(), "{code}");
+        }
+    }
+
+    #[test]
+    fn discards_ewkb_srid_z_and_m() {
+        let wkb = Wkb::new(true, EWKB_SRID | 1).u32(4326).f64s(&[1.0, 2.0]);
+        assert_eq!(parse_wkb(&wkb.bytes).unwrap(), point_1_2());
+
+        let wkb = Wkb::new(false, EWKB_SRID | EWKB_Z | EWKB_M | 1)
+            .u32(4326)
+            .f64s(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(parse_wkb(&wkb.bytes).unwrap(), point_1_2());
+
+        let wkb = Wkb::new(true, EWKB_Z | 2)
+            .u32(2)
+            .f64s(&[0.0, 0.0, 5.0, 1.0, 1.0, 5.0]);
+        assert_eq!(
+            parse_wkb(&wkb.bytes).unwrap(),
+            geo::Geometry::LineString(line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 1.0)])
+        );
+    }
+
+    #[test]
+    fn parses_empty_geometries() {
+        let wkb = Wkb::new(true, 1).f64s(&[f64::NAN, f64::NAN]);
+        assert_eq!(
+            parse_wkb(&wkb.bytes).unwrap(),
+            geo::Geometry::MultiPoint(geo::MultiPoint(Vec::new()))
+        );
+
+        let wkb = Wkb::new(true, 2).u32(0);
+        assert_eq!(
+            parse_wkb(&wkb.bytes).unwrap(),
+            geo::Geometry::LineString(geo::LineString(Vec::new()))
+        );
+
+        let wkb = Wkb::new(false, 7).u32(0);
+        assert_eq!(
+            parse_wkb(&wkb.bytes).unwrap(),
+            geo::Geometry::GeometryCollection(geo::GeometryCollection(Vec::new()))
+        );
+
+        // Empty points inside a MultiPoint are skipped
+        let wkb = Wkb::new(true, 4)
+            .u32(2)
+            .part(Wkb::new(true, 1).f64s(&[f64::NAN, f64::NAN]))
+            .part(Wkb::new(true, 1).f64s(&[1.0, 2.0]));
+        assert_eq!(
+            parse_wkb(&wkb.bytes).unwrap(),
+            geo::Geometry::MultiPoint(geo::MultiPoint(vec![point! { x: 1.0, y: 2.0 }]))
+        );
+    }
+
+    #[test]
+    fn parses_nested_collections_of_mixed_byte_order() {
+        let inner = Wkb::new(false, 7)
+            .u32(1)
+            .part(Wkb::new(true, 2).u32(2).f64s(&[0.0, 0.0, 1.0, 1.0]));
+        let wkb = Wkb::new(true, 7)
+            .u32(2)
+            .part(Wkb::new(false, 1).f64s(&[1.0, 2.0]))
+            .part(inner);
+
+        assert_eq!(
+            parse_wkb(&wkb.bytes).unwrap(),
+            geo::Geometry::GeometryCollection(geo::GeometryCollection(vec![
+                point_1_2(),
+                geo::Geometry::GeometryCollection(geo::GeometryCollection(vec![
+                    geo::Geometry::LineString(line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 1.0)]),
+                ])),
+            ]))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_wkb() {
+        let point = Wkb::new(true, 1).f64s(&[1.0, 2.0]).bytes;
+
+        assert!(parse_wkb(&point[..point.len() - 1]).is_err());
+        assert!(parse_wkb(&[point.as_slice(), &[0]].concat()).is_err());
+        assert!(parse_wkb(&[2, 1, 0, 0, 0]).is_err());
+        assert!(parse_wkb(&Wkb::new(true, 8).bytes).is_err());
+        assert!(parse_wkb(
+            &Wkb::new(true, 5)
+                .u32(1)
+                .part(Wkb::new(true, 1).f64s(&[1.0, 2.0]))
+                .bytes
+        )
+        .is_err());
+        assert!(parse_hex_wkb("0101000000000000000000F03F00000000000000").is_err());
+        assert!(parse_hex_wkb("010").is_err());
+        assert!(parse_hex_wkb("0g").is_err());
+    }
+}
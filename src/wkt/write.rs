@@ -0,0 +1,103 @@
+use std::fmt::Write;
+
+use geo::{Coord, Geometry, LineString, Polygon};
+
+/// Write a geometry as Well-Known Text. Lines, Rects and Triangles are written as LineStrings and
+/// Polygons, which are the closest WKT types.
+pub fn write_wkt(geometry: &Geometry<f64>) -> String {
+    let mut s = String::new();
+    write_geometry(&mut s, geometry);
+
+    s
+}
+
+fn write_geometry(s: &mut String, geometry: &Geometry<f64>) {
+    match geometry {
+        Geometry::Point(p) => {
+            s.push_str("POINT(");
+            write_coord(s, &p.0);
+            s.push(')');
+        }
+        Geometry::Line(l) => {
+            s.push_str("LINESTRING");
+            write_line_string(s, &LineString::from(*l));
+        }
+        Geometry::LineString(l) => {
+            s.push_str("LINESTRING");
+            write_line_string(s, l);
+        }
+        Geometry::Polygon(p) => {
+            s.push_str("POLYGON");
+            write_polygon(s, p);
+        }
+        Geometry::MultiPoint(mp) => {
+            s.push_str("MULTIPOINT");
+            write_list(s, &mp.0, |s, p| {
+                s.push('(');
+                write_coord(s, &p.0);
+                s.push(')');
+            });
+        }
+        Geometry::MultiLineString(mls) => {
+            s.push_str("MULTILINESTRING");
+            write_list(s, &mls.0, write_line_string);
+        }
+        Geometry::MultiPolygon(mp) => {
+            s.push_str("MULTIPOLYGON");
+            write_list(s, &mp.0, write_polygon);
+        }
+        Geometry::GeometryCollection(gc) => {
+            s.push_str("GEOMETRYCOLLECTION");
+            write_list(s, &gc.0, write_geometry);
+        }
+        Geometry::Rect(r) => {
+            s.push_str("POLYGON");
+            write_polygon(s, &r.to_polygon());
+        }
+        Geometry::Triangle(t) => {
+            s.push_str("POLYGON");
+            write_polygon(s, &t.to_polygon());
+        }
+    }
+}
+
+fn write_line_string(s: &mut String, line: &LineString<f64>) {
+    write_list(s, &line.0, write_coord);
+}
+
+fn write_polygon(s: &mut String, polygon: &Polygon<f64>) {
+    if polygon.exterior().0.is_empty() {
+        s.push_str(" EMPTY");
+        return;
+    }
+
+    s.push('(');
+    write_line_string(s, polygon.exterior());
+    for interior in polygon.interiors() {
+        s.push(',');
+        write_line_string(s, interior);
+    }
+    s.push(')');
+}
+
+/// Write a parenthesised, comma separated list of items, or `EMPTY` if there are none.
+fn write_list<T>(s: &mut String, items: &[T], mut write_item: impl FnMut(&mut String, &T)) {
+    if items.is_empty() {
+        s.push_str(" EMPTY");
+        return;
+    }
+
+    s.push('(');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            s.push(',');
+        }
+        write_item(s, item);
+    }
+    s.push(')');
+}
+
+fn write_coord(s: &mut String, c: &Coord<f64>) {
+    // Writing to a String cannot fail
+    let _ = write!(s, "{} {}", c.x, c.y);
+}